@@ -1,15 +1,18 @@
-use std::time::Duration;
+use std::{io::Write, os::fd::AsRawFd, time::Duration};
 
 use bf::{
     compiler::x86_64::CompileOptions,
     optimizer::Optimization::{self, *},
+    tape::TapeOptions,
     *,
 };
 use criterion::{criterion_group, criterion_main, Criterion};
 
 const MANDELBROT_BF: &str = include_str!("../programs/mandelbrot.bf");
 
-extern "C" fn putchar(_c: i32) {}
+extern "C" fn putchar(c: i32) -> i32 {
+    c
+}
 extern "C" fn getchar() -> i32 {
     0
 }
@@ -22,11 +25,57 @@ fn run(optimizations: &[Optimization]) {
         CompileOptions {
             putchar: Some(putchar),
             getchar: Some(getchar),
+            ..Default::default()
         },
     );
     runtime::native::run(&compiled);
 }
 
+/// VMは標準出力に直接書き込むので、計測中は`/dev/null`に向ける
+fn discard_stdout(f: impl FnOnce()) {
+    let null = std::fs::OpenOptions::new()
+        .write(true)
+        .open("/dev/null")
+        .unwrap();
+    unsafe {
+        let stdout = libc::dup(1);
+        libc::dup2(null.as_raw_fd(), 1);
+        f();
+        std::io::stdout().flush().unwrap();
+        libc::dup2(stdout, 1);
+        libc::close(stdout);
+    }
+}
+
+fn run_vm(engine: &str) {
+    let program = parser::parse(MANDELBROT_BF).unwrap();
+    let optimized = optimizer::optimize(program, &[All]);
+    let compiled = compiler::vm::compile(&optimized);
+    discard_stdout(|| match engine {
+        "match" => runtime::vm::run(&compiled, false).unwrap(),
+        "closure" => runtime::closure::compile(&compiled)
+            .run(TapeOptions::default(), runtime::Input::Stdin)
+            .unwrap(),
+        _ => unreachable!(),
+    });
+}
+
+fn bench_engines(c: &mut Criterion) {
+    let mut group = c.benchmark_group("engines");
+    group
+        .sample_size(10)
+        .warm_up_time(Duration::from_secs(10))
+        .measurement_time(Duration::from_secs(60));
+
+    group.bench_function("vm match loop", |b| b.iter(|| run_vm("match")));
+
+    group.bench_function("vm closure", |b| b.iter(|| run_vm("closure")));
+
+    group.bench_function("jit", |b| b.iter(|| run(&[All])));
+
+    group.finish();
+}
+
 fn bench(c: &mut Criterion) {
     let mut group = c.benchmark_group("mandelbrot");
     group
@@ -49,5 +98,5 @@ fn bench(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench);
+criterion_group!(benches, bench, bench_engines);
 criterion_main!(benches);
@@ -0,0 +1,5 @@
+Multiplication loops that change the counter more than once in the body
+++[>+<--+>+<]>.[-]<
++++[-->++<+]>.[-]<
++++++[++>+++<-]>.[-]<
+++++[+>+++<--]>.[-]<
@@ -0,0 +1,2 @@
+Reads one byte and then reads past the end of the input where the cell becomes zero
+,.,>++++++[<++++++++>-]<.
@@ -0,0 +1,2 @@
+Prints bytes of 128 and above which are written as they are and not as UTF8
+>++++++++[<++++++++++++++++>-]<.++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++.+++++++++++++++++++++++++++++++++++++++++++++++++++++++.
@@ -0,0 +1,2 @@
+Applies ROT13 to a line of input
+>+[<,[->>+>>>>+<<<<<<]>>>>>>[-<<<<<<+>>>>>>]<<<<---------->>>>>+<<<<<[->>>+>+<<<<]>>>>[-<<<<+>>>>]<[>>-<<[-]]>>[<<<<<<[-]>>>>>>[-]]<<<<<[-]<<[->>+>>>>+<<<<<<]>>>>>>[-<<<<<<+>>>>>>]>+<<<<<[>>>>>-<<<<<[-]]>>>>>[<<<<<<[-]>>>>>>[-]]<<<<<<<[->>+>>>>+<<<<<<]>>>>>>[-<<<<<<+>>>>>>]<<<<-----------------------------------------------------------------[->>+>>+<<<<]>>>>[-<<<<+>>>>]>+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]<<<[-]<[<[->>+>>+<<<<]>>>>[-<<<<+>>>>]>+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]<<<[-]<<<<------------->>>>>>>>>>>>>>>>>[<<<<<<<<<<<<<<<<<++++++++++++++++++++++++++>>>>>>>>>>>>>>>>>[-]]<<<<<<<<<<<<<<[-]]<[-]<<[->>+>>>>+<<<<<<]>>>>>>[-<<<<<<+>>>>>>]<<<<-------------------------------------------------------------------------------------------------[->>+>>+<<<<]>>>>[-<<<<+>>>>]>+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[<<<<[-]+>>>>[-]]<<<[-]<[<[->>+>>+<<<<]>>>>[-<<<<+>>>>]>+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]+<<<[->+>+<<]>>[-<<+>>]<[<->>>-<<[-]]>>[>>>>>>>>>>[-]+<<<<<<<<<<[-]]<<<[-]<<<<------------->>>>>>>>>>>>>>>>>[<<<<<<<<<<<<<<<<<++++++++++++++++++++++++++>>>>>>>>>>>>>>>>>[-]]<<<<<<<<<<<<<<[-]]<[-]<<.[-]>]
@@ -0,0 +1,2 @@
+Prints the squares of 0 to 100 in decimal
+>>>>>>>>+<<<<<<<<+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++[>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<+<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>[<<[-]+>>[-]]<<[-<+<+>>]<<[->>+<<]>[>>>>>>>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<+<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>++++++++++++++++++++++++++++++++++++++++++++++++.[-]<<[-]]>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<+<<<<+>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<]>>>>[<<[-]+>>[-]]<<[-<+<+>>]<<[->>+<<]>[>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<+<<<+>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<]>>>++++++++++++++++++++++++++++++++++++++++++++++++.[-]<<[-]]>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<+<<<<+>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<<<]>>>>[<<[-]+>>[-]]<<[-<+<+>>]<<[->>+<<]>[>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<+<<<+>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<<<]>>>++++++++++++++++++++++++++++++++++++++++++++++++.[-]<<[-]]>>>>>>>>>>[-<<<<<<<+<<<<+>>>>>>>>>>>]<<<<<<<<<<<[->>>>>>>>>>>+<<<<<<<<<<<]>>>>[<<[-]+>>[-]]<<[-<+<+>>]<<[->>+<<]>[>>>>>>>>>>[-<<<<<<<<+<<<+>>>>>>>>>>>]<<<<<<<<<<<[->>>>>>>>>>>+<<<<<<<<<<<]>>>++++++++++++++++++++++++++++++++++++++++++++++++.[-]<<[-]]>>>>[-<+<<<<+>>>>>]<<<<<[->>>>>+<<<<<]>>>>+[<<[-]+>>[-]]<<[-<+<+>>]<<[->>+<<]>[>>>>[-<<+<<<+>>>>>]<<<<<[->>>>>+<<<<<]>>>++++++++++++++++++++++++++++++++++++++++++++++++.[-]<<[-]]>[-]>++++++++++.[-]>>>[->+>>+<<<]>>>[-<<<+>>>]<[-<+>]<[-<<+[->>>>>+<+<<<<]>>>>[-<<<<+>>>>]>----------<+>[<->[-]]<[<<<<[-]>>>+>[-]]<<]>[->>>>>>+<<<<<<]>>>>[->+>>+<<<]>>>[-<<<+>>>]<[-<+>]<[-<<+[->>>>>+<+<<<<]>>>>[-<<<<+>>>>]>----------<+>[<->[-]]<[<<<<[-]>>>+>[-]]<<]>[->>>>>>+<<<<<<]>>>>[->+>>+<<<]>>>[-<<<+>>>]<[-<+>]<[-<<+[->>>>>+<+<<<<]>>>>[-<<<<+>>>>]>----------<+>[<->[-]]<[<<<<[-]>>>+>[-]]<<]>[->>>>>>+<<<<<<]>>>>[->+>>+<<<]>>>[-<<<+>>>]<[-<+>]<[-<<+[->>>>>+<+<<<<]>>>>[-<<<<+>>>>]>----------<+>[<->[-]]<[<<<<[-]>>>+>[-]]<<]>[->>>>>>+<<<<<<]>>>>[->+>>+<<<]>>>[-<<<+>>>]<[-<+>]<[-<<+[->>>>>+<+<<<<]>>>>[-<<<<+>>>>]>----------<+>[<->[-]]<[<<<<[-]>>>>[-]]<<]<<<<<<<<<<<<<<<<<<<<<<+[-<<+>>]<[-<+>]<[-<+[->>>>+<+<<<]>>>[-<<<+>>>]>----------<+>[<->[-]]<[<<<[-]>>+>[-]]<<]>[->>>>>>+<<<<<<]>>>>>>[-<+>]<[-<+[->>>>+<+<<<]>>>[-<<<+>>>]>----------<+>[<->[-]]<[<<<[-]>>+>[-]]<<]>[->>>>>>+<<<<<<]>>>>>>[-<+>]<[-<+[->>>>+<+<<<]>>>[-<<<+>>>]>----------<+>[<->[-]]<[<<<[-]>>+>[-]]<<]>[->>>>>>+<<<<<<]>>>>>>[-<+>]<[-<+[->>>>+<+<<<]>>>[-<<<+>>>]>----------<+>[<->[-]]<[<<<[-]>>+>[-]]<<]>[->>>>>>+<<<<<<]>>>>>>[-<+>]<[-<+[->>>>+<+<<<]>>>[-<<<+>>>]>----------<+>[<->[-]]<[<<<[-]>>>[-]]<<]<<<<<<<<<<<<<<<<<<<<<<+[-<<+>>]<[-<+>]<[-<+[->>>>+<+<<<]>>>[-<<<+>>>]>----------<+>[<->[-]]<[<<<[-]>>+>[-]]<<]>[->>>>>>+<<<<<<]>>>>>>[-<+>]<[-<+[->>>>+<+<<<]>>>[-<<<+>>>]>----------<+>[<->[-]]<[<<<[-]>>+>[-]]<<]>[->>>>>>+<<<<<<]>>>>>>[-<+>]<[-<+[->>>>+<+<<<]>>>[-<<<+>>>]>----------<+>[<->[-]]<[<<<[-]>>+>[-]]<<]>[->>>>>>+<<<<<<]>>>>>>[-<+>]<[-<+[->>>>+<+<<<]>>>[-<<<+>>>]>----------<+>[<->[-]]<[<<<[-]>>+>[-]]<<]>[->>>>>>+<<<<<<]>>>>>>[-<+>]<[-<+[->>>>+<+<<<]>>>[-<<<+>>>]>----------<+>[<->[-]]<[<<<[-]>>>[-]]<<]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<-]
@@ -0,0 +1,7 @@
+Deeply nested and empty loops
+[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[+-<>.,]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]][][[]]]
++++[>+++[>+++[>+++[>+++[>+++[>+++[>+++[>+<-]<-]<-]<-]<-]<-]<-]<-]
+>>>>>>>>------------------------------------------------------------------------------------------------.
+[-][-][-]>+++++[>++++[<+>-]<-]>[-]<+++++++++++++++++++++++++++++++++++++++++++++.
+>+++++++[>+++++++++[>+<-]<-]>>.
+>----------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------------+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++.[-]++++++++++.
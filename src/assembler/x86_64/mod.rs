@@ -171,6 +171,24 @@ impl Assembler {
         self.code.extend(rm64.displacement32().iter().flatten());
     }
 
+    /// - Opcode: E8 cd
+    /// - Instruction: CALL rel32
+    /// - Op/En: D (Offset)
+    /// - Description: Call near, relative, displacement relative to next instruction.
+    pub fn call_rel32(&mut self, rel32: i32) {
+        self.opcode(0xe8);
+        self.code.extend(rel32.to_le_bytes());
+    }
+
+    /// - Opcode: 0F 05
+    /// - Instruction: SYSCALL
+    /// - Op/En: ZO
+    /// - Description: Fast call to privilege level 0 system procedures.
+    pub fn syscall(&mut self) {
+        self.opcode(0x0f);
+        self.opcode(0x05);
+    }
+
     /// - Opcode: REX.W + 89 /r
     /// - Instruction: MOV r/m64, r64
     /// - Op/En: MR (ModRM:r/m (w), ModRM:reg (r))
@@ -186,6 +204,21 @@ impl Assembler {
         self.code.extend(dest.displacement32().iter().flatten());
     }
 
+    /// - Opcode: REX.W + 8B /r
+    /// - Instruction: MOV r64, r/m64
+    /// - Op/En: RM (ModRM:reg (w), ModRM:r/m (r))
+    /// - Description: Move r/m64 to r64.
+    pub fn mov_r64_rm64(&mut self, dest: QwordRegister, src: AddressingMode) {
+        let dest = dest as u8; // -> ModRM:reg
+
+        self.rex(true, dest & 0b1000 != 0, src.rex_x(), src.rex_b());
+        self.opcode(0x8b);
+        self.code.push(src.mod_r_m(dest));
+        self.code.extend(src.sib());
+        self.code.extend(src.displacement8());
+        self.code.extend(src.displacement32().iter().flatten());
+    }
+
     /// - Opcode: REX.W + B8 /0 + rd io
     /// - Instruction: MOV r64, imm64
     /// - Op/En: OI (opcode + rd(w), imm64)
@@ -198,6 +231,18 @@ impl Assembler {
         self.code.extend(src.to_le_bytes());
     }
 
+    /// - Opcode: B8+rd id
+    /// - Instruction: MOV r32, imm32
+    /// - Op/En: OI (opcode + rd(w), imm32)
+    /// - Description: Move imm32 to r32. The upper 32 bits of r64 are cleared.
+    pub fn mov_r32_imm32(&mut self, dest: QwordRegister, src: u32) {
+        let dest = dest as u8;
+
+        self.rex(false, false, false, dest & 0b1000 != 0);
+        self.opcode(0xb8 + (dest & 0b111));
+        self.code.extend(src.to_le_bytes());
+    }
+
     /// - Opcode: C6 /0 ib
     /// - Instruction: MOV r/m8, imm8
     /// - Op/En: MI (ModRM:r/m (w), imm8)
@@ -266,6 +311,58 @@ impl Assembler {
         self.code.push(imm8);
     }
 
+    /// - Opcode: 83 /7 ib
+    /// - Instruction: CMP r/m32, imm8
+    /// - Op/En: MI (ModRM:r/m (r), imm8)
+    /// - Description: Compare imm8 with r/m32.
+    pub fn cmp_rm32_imm8(&mut self, rm32: AddressingMode, imm8: u8) {
+        self.rex(false, false, rm32.rex_x(), rm32.rex_b());
+        self.opcode(0x83);
+        self.code.push(rm32.mod_r_m(7));
+        self.code.extend(rm32.sib());
+        self.code.extend(rm32.displacement8());
+        self.code.extend(rm32.displacement32().iter().flatten());
+        self.code.push(imm8);
+    }
+
+    /// - Opcode: REX.W + 83 /7 ib
+    /// - Instruction: CMP r/m64, imm8
+    /// - Op/En: MI (ModRM:r/m (r), imm8)
+    /// - Description: Compare imm8 with r/m64.
+    pub fn cmp_rm64_imm8(&mut self, rm64: AddressingMode, imm8: u8) {
+        self.rex(true, false, rm64.rex_x(), rm64.rex_b());
+        self.opcode(0x83);
+        self.code.push(rm64.mod_r_m(7));
+        self.code.extend(rm64.sib());
+        self.code.extend(rm64.displacement8());
+        self.code.extend(rm64.displacement32().iter().flatten());
+        self.code.push(imm8);
+    }
+
+    /// - Opcode: REX.W + 3B /r
+    /// - Instruction: CMP r64, r/m64
+    /// - Op/En: RM (ModRM:reg (r), ModRM:r/m (r))
+    /// - Description: Compare r/m64 with r64.
+    pub fn cmp_r64_rm64(&mut self, r64: QwordRegister, rm64: AddressingMode) {
+        let r64 = r64 as u8; // -> ModRM:reg
+
+        self.rex(true, r64 & 0b1000 != 0, rm64.rex_x(), rm64.rex_b());
+        self.opcode(0x3b);
+        self.code.push(rm64.mod_r_m(r64));
+        self.code.extend(rm64.sib());
+        self.code.extend(rm64.displacement8());
+        self.code.extend(rm64.displacement32().iter().flatten());
+    }
+
+    /// - Opcode: E9 cd
+    /// - Instruction: JMP rel32
+    /// - Op/En: D (Offset)
+    /// - Description: Jump near, relative, RIP = RIP + 32-bit displacement sign extended to 64-bits.
+    pub fn jmp_rel32(&mut self, rel32: i32) {
+        self.opcode(0xe9);
+        self.code.extend(rel32.to_le_bytes());
+    }
+
     /// - Opcode: 74 cb
     /// - Instruction: JE rel8
     /// - Op/En: D (Offset)
@@ -324,6 +421,36 @@ impl Assembler {
         self.code[addr + 2..addr + 6].copy_from_slice(&rel32.to_le_bytes());
     }
 
+    /// - Opcode: 0F 83 cd
+    /// - Instruction: JAE rel32
+    /// - Op/En: D (Offset)
+    /// - Description: Jump near if above or equal (CF=0).
+    pub fn jae_rel32(&mut self, rel32: i32) {
+        self.opcode(0x0f);
+        self.opcode(0x83);
+        self.code.extend(rel32.to_le_bytes());
+    }
+
+    /// 指定した位置にあるJAE命令のオペランドを変更する
+    pub fn set_jae_rel32(&mut self, addr: usize, rel32: i32) {
+        self.code[addr + 2..addr + 6].copy_from_slice(&rel32.to_le_bytes());
+    }
+
+    /// - Opcode: 0F 8C cd
+    /// - Instruction: JL rel32
+    /// - Op/En: D (Offset)
+    /// - Description: Jump near if less (SF≠ OF).
+    pub fn jl_rel32(&mut self, rel32: i32) {
+        self.opcode(0x0f);
+        self.opcode(0x8c);
+        self.code.extend(rel32.to_le_bytes());
+    }
+
+    /// 指定した位置にあるJL命令のオペランドを変更する
+    pub fn set_jl_rel32(&mut self, addr: usize, rel32: i32) {
+        self.code[addr + 2..addr + 6].copy_from_slice(&rel32.to_le_bytes());
+    }
+
     /// - Opcode: 80 /0 ib
     /// - Instruction: ADD r/m8, imm8
     /// - Op/En: MI (ModRM:r/m (r, w), imm8)
@@ -352,6 +479,20 @@ impl Assembler {
         self.code.push(imm8);
     }
 
+    /// - Opcode: REX.W + 81 /0 id
+    /// - Instruction: ADD r/m64, imm32
+    /// - Op/En: MI (ModRM:r/m (r, w), imm32)
+    /// - Description: Add sign-extended imm32 to r/m64.
+    pub fn add_rm64_imm32(&mut self, rm64: AddressingMode, imm32: u32) {
+        self.rex(true, false, rm64.rex_x(), rm64.rex_b());
+        self.opcode(0x81);
+        self.code.push(rm64.mod_r_m(0));
+        self.code.extend(rm64.sib());
+        self.code.extend(rm64.displacement8());
+        self.code.extend(rm64.displacement32().iter().flatten());
+        self.code.extend(imm32.to_le_bytes());
+    }
+
     /// - Opcode: 00 /r
     /// - Instruction: ADD r/m8, r8
     /// - Op/En: MR (ModRM:r/m (r, w), ModRM:reg (r))
@@ -393,6 +534,35 @@ impl Assembler {
         self.code.push(imm8);
     }
 
+    /// - Opcode: REX.W + 81 /5 id
+    /// - Instruction: SUB r/m64, imm32
+    /// - Op/En: MI (ModRM:r/m (r, w), imm32)
+    /// - Description: Subtract sign-extended imm32 from r/m64.
+    pub fn sub_rm64_imm32(&mut self, rm64: AddressingMode, imm32: u32) {
+        self.rex(true, false, rm64.rex_x(), rm64.rex_b());
+        self.opcode(0x81);
+        self.code.push(rm64.mod_r_m(5));
+        self.code.extend(rm64.sib());
+        self.code.extend(rm64.displacement8());
+        self.code.extend(rm64.displacement32().iter().flatten());
+        self.code.extend(imm32.to_le_bytes());
+    }
+
+    /// - Opcode: REX.W + 2B /r
+    /// - Instruction: SUB r64, r/m64
+    /// - Op/En: RM (ModRM:reg (r, w), ModRM:r/m (r))
+    /// - Description: Subtract r/m64 from r64.
+    pub fn sub_r64_rm64(&mut self, r64: QwordRegister, rm64: AddressingMode) {
+        let r64 = r64 as u8; // -> ModRM:reg
+
+        self.rex(true, r64 & 0b1000 != 0, rm64.rex_x(), rm64.rex_b());
+        self.opcode(0x2b);
+        self.code.push(rm64.mod_r_m(r64));
+        self.code.extend(rm64.sib());
+        self.code.extend(rm64.displacement8());
+        self.code.extend(rm64.displacement32().iter().flatten());
+    }
+
     /// - Opcode: 28 /r
     /// - Instruction: SUB r/m8, r8
     /// - Op/En: MR (ModRM:r/m (r, w), ModRM:reg (r))
@@ -0,0 +1,160 @@
+use anyhow::bail;
+
+use crate::instruction::Instruction;
+
+/// Brainfuckのソースコードを生成する。
+/// コメントを取り除き、打ち消し合う`+`と`-`や`>`と`<`をまとめ、最適化した命令はループに戻す。
+/// セルは8ビットとして、255回の`+`は1回の`-`にする
+pub fn compile(instructions: &[Instruction]) -> anyhow::Result<String> {
+    let mut writer = Writer::default();
+    do_compile(instructions, &mut writer)?;
+    writer.flush();
+    let mut out = writer.out;
+    out.push('\n');
+    Ok(out)
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    /// まだ書いていない、ポインタが指すセルへの加算
+    delta: u8,
+    /// まだ書いていないポインタの移動量
+    moves: isize,
+}
+
+impl Writer {
+    fn add(&mut self, value: u8) {
+        self.flush_moves();
+        self.delta = self.delta.wrapping_add(value);
+    }
+
+    fn shift(&mut self, value: isize) {
+        self.flush_delta();
+        self.moves += value;
+    }
+
+    /// 加算と移動以外の命令を書く
+    fn command(&mut self, command: &str) {
+        self.flush();
+        self.out.push_str(command);
+    }
+
+    fn flush(&mut self) {
+        self.flush_delta();
+        self.flush_moves();
+    }
+
+    fn flush_delta(&mut self) {
+        if self.delta <= 128 {
+            self.push_repeated('+', self.delta as usize);
+        } else {
+            self.push_repeated('-', 256 - self.delta as usize);
+        }
+        self.delta = 0;
+    }
+
+    fn flush_moves(&mut self) {
+        if self.moves >= 0 {
+            self.push_repeated('>', self.moves as usize);
+        } else {
+            self.push_repeated('<', self.moves.unsigned_abs());
+        }
+        self.moves = 0;
+    }
+
+    fn push_repeated(&mut self, c: char, count: usize) {
+        self.out.extend(std::iter::repeat_n(c, count));
+    }
+}
+
+fn do_compile(instructions: &[Instruction], writer: &mut Writer) -> anyhow::Result<()> {
+    for inst in instructions {
+        match inst {
+            Instruction::Increment => writer.add(1),
+            Instruction::Decrement => writer.add(255),
+            Instruction::PointerIncrement => writer.shift(1),
+            Instruction::PointerDecrement => writer.shift(-1),
+            Instruction::PutChar => writer.command("."),
+            Instruction::GetChar => writer.command(","),
+            Instruction::Loop(body) => {
+                writer.command("[");
+                do_compile(body, writer)?;
+                writer.command("]");
+            }
+            Instruction::Add(value) => writer.add(*value),
+            Instruction::Subtract(value) => writer.add(value.wrapping_neg()),
+            Instruction::SetZero => {
+                // 0にするセルへの加算は書かなくてよい
+                writer.flush_moves();
+                writer.delta = 0;
+                writer.command("[-]");
+            }
+            Instruction::PointerAdd(value) => writer.shift(*value as isize),
+            Instruction::PointerSubtract(value) => writer.shift(-(*value as isize)),
+            Instruction::IfNotZero(body) => match multiplication_loop(body) {
+                Some(loop_body) => {
+                    writer.command("[");
+                    do_compile(&loop_body, writer)?;
+                    writer.command("]");
+                }
+                None => bail!("cannot express {:?} in Brainf*ck", inst),
+            },
+            Instruction::AddValueAt(_)
+            | Instruction::SubtractValueAt(_)
+            | Instruction::AddValueMultipliedBy(_, _)
+            | Instruction::SubtractValueMultipliedBy(_, _)
+            | Instruction::Negate => {
+                bail!("cannot express {:?} outside a multiplication loop", inst)
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 乗算のループを最適化した`IfNotZero`の本体から、元のループの本体を組み立てる。
+///
+/// 本体は、必要なら`Negate`で始まり、ポインタの移動と、ループを始めた位置のセルを加算する命令が続き、
+/// 始めた位置に戻って`SetZero`で終わる。始めた位置のセルは`Negate`があれば`+`、なければ`-`で1ずつ0に近づける
+fn multiplication_loop(body: &[Instruction]) -> Option<Vec<Instruction>> {
+    let (first, body) = match body.first() {
+        Some(Instruction::Negate) => (Instruction::Increment, &body[1..]),
+        _ => (Instruction::Decrement, body),
+    };
+    let zeros = body
+        .iter()
+        .rev()
+        .take_while(|inst| **inst == Instruction::SetZero)
+        .count();
+    if zeros == 0 {
+        return None;
+    }
+
+    let mut loop_body = vec![first];
+    let mut offset = 0isize;
+    for inst in &body[..body.len() - zeros] {
+        let (value, at) = match *inst {
+            Instruction::PointerIncrement => (None, 1),
+            Instruction::PointerDecrement => (None, -1),
+            Instruction::PointerAdd(value) => (None, value as isize),
+            Instruction::PointerSubtract(value) => (None, -(value as isize)),
+            Instruction::AddValueAt(at) => (Some(1u8), at),
+            Instruction::SubtractValueAt(at) => (Some(255), at),
+            Instruction::AddValueMultipliedBy(value, at) => (Some(value), at),
+            Instruction::SubtractValueMultipliedBy(value, at) => (Some(value.wrapping_neg()), at),
+            _ => return None,
+        };
+        match value {
+            None => {
+                offset += at;
+                loop_body.push(inst.clone());
+            }
+            // 加算する値は始めた位置のセルから読むものに限る
+            Some(value) if offset != 0 && offset + at == 0 => {
+                loop_body.push(Instruction::Add(value));
+            }
+            Some(_) => return None,
+        }
+    }
+    (offset == 0).then_some(loop_body)
+}
@@ -0,0 +1,243 @@
+//! VMの命令列をファイルに保存するバイトコード形式
+//!
+//! 構文解析と最適化を一度だけ行い、結果を配布したり再利用したりできるようにする。
+//! 形式はすべてリトルエンディアンで、次の順に並べる
+//!
+//! - マジックナンバー`BFBC`
+//! - バージョン(u16)
+//! - セルのビット幅(u8、8/16/32)
+//! - 入力の終わりの扱い(u8、0: `zero`、1: `minus_one`、2: `unchanged`)
+//! - テープのセル数(u64)
+//! - 命令の数(u64)
+//! - 命令。種類(u8、`VMInstruction::opcode`を参照)に続けてオペランドを置く。
+//!   `u8`のオペランドは1バイト、`usize`と`isize`のオペランドは8バイト(i64)
+
+use anyhow::{bail, Context};
+
+use crate::{
+    runtime::vm::VMInstruction,
+    tape::{CellWidth, Eof, TapeOptions},
+};
+
+#[cfg(test)]
+mod tests;
+
+const MAGIC: &[u8; 4] = b"BFBC";
+const VERSION: u16 = 1;
+
+/// バイトコードのファイルの内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bytecode {
+    pub tape: TapeOptions,
+    pub instructions: Vec<VMInstruction>,
+}
+
+/// ファイルの先頭がバイトコードのマジックナンバーかどうか
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn encode(instructions: &[VMInstruction], tape: TapeOptions) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.push(tape.cell_width.bits() as u8);
+    out.push(match tape.eof {
+        Eof::Zero => 0,
+        Eof::MinusOne => 1,
+        Eof::Unchanged => 2,
+    });
+    out.extend_from_slice(&(tape.size as u64).to_le_bytes());
+    out.extend(encode_instructions(instructions));
+    out
+}
+
+/// 命令の数と命令を、ファイルと同じ形式で並べる
+pub fn encode_instructions(instructions: &[VMInstruction]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&(instructions.len() as u64).to_le_bytes());
+    for &inst in instructions {
+        out.push(inst.opcode());
+        let mut word = |value: i64| out.extend_from_slice(&value.to_le_bytes());
+        match inst {
+            VMInstruction::Increment
+            | VMInstruction::Decrement
+            | VMInstruction::PointerIncrement
+            | VMInstruction::PointerDecrement
+            | VMInstruction::PutChar
+            | VMInstruction::GetChar
+            | VMInstruction::SetZero
+            | VMInstruction::Negate => {}
+            VMInstruction::JumpIfZero(target) | VMInstruction::JumpIfNotZero(target) => {
+                word(target as i64)
+            }
+            VMInstruction::PointerAdd(value) | VMInstruction::PointerSubtract(value) => {
+                word(value as i64)
+            }
+            VMInstruction::AddValueAt(at)
+            | VMInstruction::SubtractValueAt(at)
+            | VMInstruction::Scan(at) => word(at as i64),
+            VMInstruction::AddValueMultipliedBy(value, at)
+            | VMInstruction::SubtractValueMultipliedBy(value, at) => {
+                out.push(value);
+                out.extend_from_slice(&(at as i64).to_le_bytes());
+            }
+            VMInstruction::MoveAdd(offset, value) => {
+                word(offset as i64);
+                out.push(value);
+            }
+            VMInstruction::Add(value)
+            | VMInstruction::Subtract(value)
+            | VMInstruction::SetValue(value) => out.push(value),
+        }
+    }
+    out
+}
+
+pub fn decode(bytes: &[u8]) -> anyhow::Result<Bytecode> {
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        bail!("not a bytecode file");
+    }
+    let version = u16::from_le_bytes(reader.take(2)?.try_into().unwrap());
+    if version != VERSION {
+        bail!("unsupported bytecode version {}", version);
+    }
+
+    let cell_width = match reader.u8()? {
+        8 => CellWidth::U8,
+        16 => CellWidth::U16,
+        32 => CellWidth::U32,
+        bits => bail!("invalid cell width {}", bits),
+    };
+    let eof = match reader.u8()? {
+        0 => Eof::Zero,
+        1 => Eof::MinusOne,
+        2 => Eof::Unchanged,
+        value => bail!("invalid eof mode {}", value),
+    };
+    let size = reader.usize()?;
+    if size == 0 {
+        bail!("the tape must have at least one cell");
+    }
+    let tape = TapeOptions {
+        size,
+        cell_width,
+        eof,
+    };
+
+    let len = reader.usize()?;
+    // 長さが壊れていても巨大な領域を確保しないように、残りのバイト数で抑える
+    let mut instructions = Vec::with_capacity(len.min(bytes.len()));
+    for _ in 0..len {
+        let inst = match reader.u8()? {
+            0 => VMInstruction::Increment,
+            1 => VMInstruction::Decrement,
+            2 => VMInstruction::PointerIncrement,
+            3 => VMInstruction::PointerDecrement,
+            4 => VMInstruction::PutChar,
+            5 => VMInstruction::GetChar,
+            6 => VMInstruction::JumpIfZero(reader.usize()?),
+            7 => VMInstruction::JumpIfNotZero(reader.usize()?),
+            8 => VMInstruction::Add(reader.u8()?),
+            9 => VMInstruction::Subtract(reader.u8()?),
+            10 => VMInstruction::SetZero,
+            11 => VMInstruction::PointerAdd(reader.usize()?),
+            12 => VMInstruction::PointerSubtract(reader.usize()?),
+            13 => VMInstruction::AddValueAt(reader.isize()?),
+            14 => VMInstruction::SubtractValueAt(reader.isize()?),
+            15 => VMInstruction::AddValueMultipliedBy(reader.u8()?, reader.isize()?),
+            16 => VMInstruction::SubtractValueMultipliedBy(reader.u8()?, reader.isize()?),
+            17 => VMInstruction::Negate,
+            18 => VMInstruction::MoveAdd(reader.isize()?, reader.u8()?),
+            19 => VMInstruction::SetValue(reader.u8()?),
+            20 => VMInstruction::Scan(reader.isize()?),
+            opcode => bail!(
+                "invalid opcode {} at instruction {}",
+                opcode,
+                instructions.len()
+            ),
+        };
+        instructions.push(inst);
+    }
+    if reader.position != bytes.len() {
+        bail!("unexpected data after the last instruction");
+    }
+
+    check_jumps(&instructions)?;
+    Ok(Bytecode { tape, instructions })
+}
+
+/// ジャンプが`compiler::vm::compile`の出力と同じ形で入れ子になっていることを確かめる。
+///
+/// ループは`JumpIfZero(end)`と、`end`にある`JumpIfNotZero(start)`の組で、
+/// ifは本体の最後の命令を指す`JumpIfZero`だけで表す。
+/// 形が崩れていると、ジャンプ先からループを復元するクロージャのVMが終わらなくなる
+fn check_jumps(instructions: &[VMInstruction]) -> anyhow::Result<()> {
+    // 入っているループとifの、開始位置と最後の命令の位置
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    for (i, &inst) in instructions.iter().enumerate() {
+        match inst {
+            VMInstruction::JumpIfZero(target) => {
+                let enclosing_end = blocks
+                    .last()
+                    .map_or(instructions.len() - 1, |&(_, end)| end);
+                if target < i || target > enclosing_end {
+                    bail!(
+                        "jump target {} of instruction {} is out of its block",
+                        target,
+                        i
+                    );
+                }
+                blocks.push((i, target));
+            }
+            VMInstruction::JumpIfNotZero(target)
+                if blocks.last() != Some(&(target, i))
+                    || instructions[target] != VMInstruction::JumpIfZero(i) =>
+            {
+                bail!(
+                    "jump target {} of instruction {} does not start its loop",
+                    target,
+                    i
+                );
+            }
+            _ => {}
+        }
+        while blocks.last().is_some_and(|&(_, end)| end == i) {
+            blocks.pop();
+        }
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + len)
+            .context("the bytecode is truncated")?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i64(&mut self) -> anyhow::Result<i64> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> anyhow::Result<usize> {
+        usize::try_from(self.i64()?).context("operand out of range")
+    }
+
+    fn isize(&mut self) -> anyhow::Result<isize> {
+        isize::try_from(self.i64()?).context("operand out of range")
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    compiler::vm::{self, Fusion},
+    optimizer::{self, Optimization},
+    parser,
+    runtime::vm::VMInstruction::*,
+    tape::{CellWidth, Eof, TapeOptions},
+};
+
+use super::*;
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+fn compile(source: &str, optimizations: &[Optimization], fusions: &[Fusion]) -> Vec<VMInstruction> {
+    let program = optimizer::optimize(parser::parse(source).unwrap(), optimizations);
+    vm::fuse(&vm::compile(&program), fusions)
+}
+
+/// 命令列だけを置き換えたファイルを作る
+fn with_instructions(instructions: &[VMInstruction]) -> Vec<u8> {
+    encode(instructions, TapeOptions::default())
+}
+
+#[test]
+fn roundtrip() {
+    let tape = TapeOptions {
+        size: 1234,
+        cell_width: CellWidth::U16,
+        eof: Eof::Unchanged,
+    };
+    for (optimizations, fusions) in [
+        (&[][..], &[][..]),
+        (&[Optimization::All][..], &[][..]),
+        (&[Optimization::All][..], &[Fusion::All][..]),
+    ] {
+        // 掛け算のループはifになるので、ifの形も確かめられる
+        let instructions = compile(HELLO_WORLD, optimizations, fusions);
+        let decoded = decode(&encode(&instructions, tape)).unwrap();
+        assert_eq!(decoded, Bytecode { tape, instructions });
+    }
+}
+
+#[test]
+fn roundtrip_ifs_ending_with_loops() {
+    // 本体の最後がループのifは、ifとループが同じ命令で終わる
+    let instructions = [
+        JumpIfZero(4),
+        PointerIncrement,
+        JumpIfZero(4),
+        Decrement,
+        JumpIfNotZero(2),
+        JumpIfZero(5),
+    ];
+    assert_eq!(
+        decode(&with_instructions(&instructions))
+            .unwrap()
+            .instructions,
+        instructions
+    );
+}
+
+#[test]
+fn reject_malformed_jumps() {
+    for instructions in [
+        // 後ろに戻るJumpIfZero
+        &[Increment, JumpIfZero(0)][..],
+        // 対応するJumpIfZeroがないJumpIfNotZero
+        &[Increment, JumpIfNotZero(0)],
+        &[JumpIfNotZero(0)],
+        // ループの外まで続くif
+        &[
+            JumpIfZero(2),
+            JumpIfZero(3),
+            JumpIfNotZero(0),
+            JumpIfNotZero(1),
+        ],
+        // ifの中で始まり、ifの外で終わるループ
+        &[JumpIfZero(2), JumpIfZero(3), Decrement, JumpIfNotZero(1)],
+        // 範囲外
+        &[JumpIfZero(5)],
+    ] {
+        let error = decode(&with_instructions(instructions)).unwrap_err();
+        assert!(
+            error.to_string().contains("jump target"),
+            "{:?}",
+            instructions
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt::Write;
+
+use crate::{
+    instruction::Instruction,
+    tape::{Eof, TapeOptions},
+};
+
+/// C言語のソースコードを生成する
+pub fn compile(instructions: &[Instruction], tape: TapeOptions) -> String {
+    let cell = format!("uint{}_t", tape.cell_width.bits());
+    let eof = match tape.eof {
+        Eof::Zero => "0".to_string(),
+        Eof::MinusOne => format!("({})-1", cell),
+        Eof::Unchanged => "current".to_string(),
+    };
+
+    let mut out = String::new();
+    writeln!(out, "#include <stdint.h>").unwrap();
+    writeln!(out, "#include <stdio.h>").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "static {} tape[{}];", cell, tape.size).unwrap();
+    writeln!(out).unwrap();
+    if contains_get_char(instructions) {
+        writeln!(out, "static {} input({} current) {{", cell, cell).unwrap();
+        writeln!(out, "    int c = getchar();").unwrap();
+        writeln!(out, "    (void)current;").unwrap();
+        writeln!(out, "    return c == EOF ? {} : ({})c;", eof, cell).unwrap();
+        writeln!(out, "}}").unwrap();
+        writeln!(out).unwrap();
+    }
+    writeln!(out, "int main(void) {{").unwrap();
+    writeln!(out, "    {} *p = tape;", cell).unwrap();
+
+    let mut writer = Writer {
+        out,
+        indent: 1,
+        offset: 0,
+    };
+    do_compile(instructions, &mut writer);
+
+    let mut out = writer.out;
+    writeln!(out, "    return 0;").unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+fn contains_get_char(instructions: &[Instruction]) -> bool {
+    instructions.iter().any(|inst| match inst {
+        Instruction::GetChar => true,
+        Instruction::Loop(instructions) | Instruction::IfNotZero(instructions) => {
+            contains_get_char(instructions)
+        }
+        _ => false,
+    })
+}
+
+struct Writer {
+    out: String,
+    indent: usize,
+    /// まだ`p`に反映していないポインタの移動量
+    offset: isize,
+}
+
+impl Writer {
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    /// ポインタから`at`だけ離れたセル
+    fn cell(&self, at: isize) -> String {
+        format!("p[{}]", self.offset + at)
+    }
+
+    /// ループの前後ではポインタの移動を`p`に反映する
+    fn flush(&mut self) {
+        match self.offset {
+            0 => {}
+            offset if offset > 0 => self.line(&format!("p += {};", offset)),
+            offset => self.line(&format!("p -= {};", -offset)),
+        }
+        self.offset = 0;
+    }
+}
+
+fn do_compile(instructions: &[Instruction], writer: &mut Writer) {
+    for inst in instructions {
+        let current = writer.cell(0);
+        match inst {
+            Instruction::Increment => writer.line(&format!("{} += 1;", current)),
+            Instruction::Decrement => writer.line(&format!("{} -= 1;", current)),
+            Instruction::PointerIncrement => writer.offset += 1,
+            Instruction::PointerDecrement => writer.offset -= 1,
+            Instruction::PutChar => writer.line(&format!("putchar({});", current)),
+            Instruction::GetChar => writer.line(&format!("{} = input({});", current, current)),
+            Instruction::Loop(loop_instructions) => {
+                writer.flush();
+                writer.line("while (*p) {");
+                writer.indent += 1;
+                do_compile(loop_instructions, writer);
+                writer.flush();
+                writer.indent -= 1;
+                writer.line("}");
+            }
+            Instruction::Add(value) => writer.line(&format!("{} += {};", current, value)),
+            Instruction::Subtract(value) => writer.line(&format!("{} -= {};", current, value)),
+            Instruction::SetZero => writer.line(&format!("{} = 0;", current)),
+            Instruction::PointerAdd(value) => writer.offset += *value as isize,
+            Instruction::PointerSubtract(value) => writer.offset -= *value as isize,
+            Instruction::AddValueAt(at) => {
+                writer.line(&format!("{} += {};", current, writer.cell(*at)))
+            }
+            Instruction::SubtractValueAt(at) => {
+                writer.line(&format!("{} -= {};", current, writer.cell(*at)))
+            }
+            Instruction::AddValueMultipliedBy(value, at) => {
+                writer.line(&format!("{} += {} * {};", current, value, writer.cell(*at)))
+            }
+            Instruction::SubtractValueMultipliedBy(value, at) => {
+                writer.line(&format!("{} -= {} * {};", current, value, writer.cell(*at)))
+            }
+            Instruction::Negate => writer.line(&format!("{} = -{};", current, current)),
+            Instruction::IfNotZero(if_instructions) => {
+                writer.flush();
+                writer.line("if (*p) {");
+                writer.indent += 1;
+                do_compile(if_instructions, writer);
+                writer.flush();
+                writer.indent -= 1;
+                writer.line("}");
+            }
+        }
+    }
+}
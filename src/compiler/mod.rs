@@ -1,2 +1,7 @@
+pub mod bf;
+pub mod bytecode;
+pub mod c;
+pub mod rust;
 pub mod vm;
+pub mod wasm;
 pub mod x86_64;
@@ -0,0 +1,201 @@
+use std::fmt::Write;
+
+use crate::{
+    instruction::Instruction,
+    runtime::vm::VMInstruction,
+    tape::{Eof, TapeOptions},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// 単体でビルドできるRustの`main.rs`を生成する
+///
+/// IRの走査は`compiler::vm::compile`に任せ、その出力のジャンプ先から`while`と`if`を復元する
+pub fn compile(instructions: &[Instruction], tape: TapeOptions) -> String {
+    let mut instructions = super::vm::compile(instructions);
+    // 最後のポインタの移動は読まれず、`p`への代入が使われないと警告されるので出力しない。
+    // ifの本体の最後の命令は閉じ括弧の位置なので残す
+    while let Some(&last) = instructions.last() {
+        let closes_if = instructions.contains(&VMInstruction::JumpIfZero(instructions.len() - 1));
+        if closes_if || !is_pointer_move(last) {
+            break;
+        }
+        instructions.pop();
+    }
+    let cell = format!("u{}", tape.cell_width.bits());
+    let uses_input = instructions.contains(&VMInstruction::GetChar);
+    let uses_output = instructions.contains(&VMInstruction::PutChar);
+    let moves_pointer = instructions.iter().any(|&inst| {
+        is_pointer_move(inst)
+            || matches!(inst, VMInstruction::MoveAdd(_, _) | VMInstruction::Scan(_))
+    });
+    let writes_tape = instructions.iter().any(|inst| {
+        !matches!(
+            inst,
+            VMInstruction::PointerIncrement
+                | VMInstruction::PointerDecrement
+                | VMInstruction::PointerAdd(_)
+                | VMInstruction::PointerSubtract(_)
+                | VMInstruction::PutChar
+                | VMInstruction::JumpIfZero(_)
+                | VMInstruction::JumpIfNotZero(_)
+                | VMInstruction::Scan(_)
+        )
+    });
+    let mutable = |used: bool| if used { "mut " } else { "" };
+
+    let mut out = String::new();
+    match (uses_input, uses_output) {
+        (true, _) => writeln!(out, "use std::io::{{Read, Write}};\n").unwrap(),
+        (false, true) => writeln!(out, "use std::io::Write;\n").unwrap(),
+        (false, false) => {}
+    }
+    writeln!(out, "fn main() {{").unwrap();
+    if !instructions.is_empty() {
+        writeln!(
+            out,
+            "    let {}tape = vec![0{}; {}];",
+            mutable(writes_tape),
+            cell,
+            tape.size
+        )
+        .unwrap();
+        writeln!(out, "    let {}p: usize = 0;", mutable(moves_pointer)).unwrap();
+    }
+    if uses_input {
+        writeln!(out, "    let mut input = std::io::stdin().lock().bytes();").unwrap();
+    }
+    if uses_output || uses_input {
+        writeln!(
+            out,
+            "    let mut output = std::io::BufWriter::new(std::io::stdout().lock());"
+        )
+        .unwrap();
+    }
+
+    // 閉じ括弧を出力する位置
+    let mut closes = Vec::new();
+    for (i, inst) in instructions.iter().enumerate() {
+        let indent = "    ".repeat(closes.len() + 1);
+        let mut line = |line: String| writeln!(out, "{}{}", indent, line).unwrap();
+        let current = "tape[p]";
+        match *inst {
+            VMInstruction::Increment => line(format!("{0} = {0}.wrapping_add(1);", current)),
+            VMInstruction::Decrement => line(format!("{0} = {0}.wrapping_sub(1);", current)),
+            VMInstruction::PointerIncrement => line("p += 1;".to_string()),
+            VMInstruction::PointerDecrement => line("p -= 1;".to_string()),
+            VMInstruction::PutChar => {
+                line(format!("output.write_all(&[{} as u8]).unwrap();", current))
+            }
+            VMInstruction::GetChar => {
+                line("output.flush().unwrap();".to_string());
+                match tape.eof {
+                    Eof::Zero => line(format!(
+                        "{} = input.next().map_or(0, |c| c.unwrap() as {});",
+                        current, cell
+                    )),
+                    Eof::MinusOne => line(format!(
+                        "{} = input.next().map_or({}::MAX, |c| c.unwrap() as {});",
+                        current, cell, cell
+                    )),
+                    Eof::Unchanged => line(format!(
+                        "if let Some(c) = input.next() {{ {} = c.unwrap() as {}; }}",
+                        current, cell
+                    )),
+                }
+            }
+            VMInstruction::JumpIfZero(target) => {
+                // ループの場合は対応するJumpIfNotZeroがこの命令に戻ってくる。
+                // そうでなければIfNotZeroで、targetは本体の最後の命令
+                if instructions.get(target) == Some(&VMInstruction::JumpIfNotZero(i)) {
+                    line(format!("while {} != 0 {{", current));
+                } else {
+                    line(format!("if {} != 0 {{", current));
+                }
+                closes.push(target);
+            }
+            VMInstruction::JumpIfNotZero(_) => {}
+            VMInstruction::Add(value) => {
+                line(format!("{0} = {0}.wrapping_add({1});", current, value))
+            }
+            VMInstruction::Subtract(value) => {
+                line(format!("{0} = {0}.wrapping_sub({1});", current, value))
+            }
+            VMInstruction::SetZero => line(format!("{} = 0;", current)),
+            VMInstruction::PointerAdd(value) => line(format!("p += {};", value)),
+            VMInstruction::PointerSubtract(value) => line(format!("p -= {};", value)),
+            VMInstruction::AddValueAt(at) => line(format!(
+                "{0} = {0}.wrapping_add({1});",
+                current,
+                cell_at(at)
+            )),
+            VMInstruction::SubtractValueAt(at) => line(format!(
+                "{0} = {0}.wrapping_sub({1});",
+                current,
+                cell_at(at)
+            )),
+            VMInstruction::AddValueMultipliedBy(value, at) => line(format!(
+                "{0} = {0}.wrapping_add({1}.wrapping_mul({2}));",
+                current,
+                cell_at(at),
+                value
+            )),
+            VMInstruction::SubtractValueMultipliedBy(value, at) => line(format!(
+                "{0} = {0}.wrapping_sub({1}.wrapping_mul({2}));",
+                current,
+                cell_at(at),
+                value
+            )),
+            VMInstruction::Negate => line(format!("{0} = {0}.wrapping_neg();", current)),
+            VMInstruction::MoveAdd(offset, value) => {
+                line(format!("{};", move_pointer(offset)));
+                line(format!("{0} = {0}.wrapping_add({1});", current, value));
+            }
+            VMInstruction::SetValue(value) => line(format!("{} = {};", current, value)),
+            VMInstruction::Scan(step) => line(format!(
+                "while {} != 0 {{ {}; }}",
+                current,
+                move_pointer(step)
+            )),
+        }
+
+        while closes.last() == Some(&i) {
+            closes.pop();
+            writeln!(out, "{}}}", "    ".repeat(closes.len() + 1)).unwrap();
+        }
+    }
+
+    if uses_output || uses_input {
+        writeln!(out, "    output.flush().unwrap();").unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+fn is_pointer_move(inst: VMInstruction) -> bool {
+    matches!(
+        inst,
+        VMInstruction::PointerIncrement
+            | VMInstruction::PointerDecrement
+            | VMInstruction::PointerAdd(_)
+            | VMInstruction::PointerSubtract(_)
+    )
+}
+
+fn move_pointer(offset: isize) -> String {
+    if offset >= 0 {
+        format!("p += {}", offset)
+    } else {
+        format!("p -= {}", -offset)
+    }
+}
+
+/// ポインタから`at`だけ離れたセル
+fn cell_at(at: isize) -> String {
+    if at >= 0 {
+        format!("tape[p + {}]", at)
+    } else {
+        format!("tape[p - {}]", -at)
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{
+    optimizer::{self, Optimization},
+    parser,
+    tape::{CellWidth, Eof},
+};
+
+use super::*;
+
+fn emit(source: &str, optimizations: &[Optimization], tape: TapeOptions) -> String {
+    compile(
+        &optimizer::optimize(parser::parse(source).unwrap(), optimizations),
+        tape,
+    )
+}
+
+#[test]
+fn empty_program() {
+    assert_eq!(emit("", &[], TapeOptions::default()), "fn main() {\n}\n");
+}
+
+#[test]
+fn mul_loop_as_if() {
+    assert_eq!(
+        emit("++[>+++<-]>.", &[Optimization::All], TapeOptions::default()),
+        r#"use std::io::Write;
+
+fn main() {
+    let mut tape = vec![0u8; 30000];
+    let mut p: usize = 0;
+    let mut output = std::io::BufWriter::new(std::io::stdout().lock());
+    tape[p] = tape[p].wrapping_add(2);
+    if tape[p] != 0 {
+        p += 1;
+        tape[p] = tape[p].wrapping_add(tape[p - 1].wrapping_mul(3));
+        p -= 1;
+        tape[p] = 0;
+        tape[p] = 0;
+    }
+    p += 1;
+    output.write_all(&[tape[p] as u8]).unwrap();
+    output.flush().unwrap();
+}
+"#
+    );
+}
+
+#[test]
+fn loops_with_input() {
+    let tape = TapeOptions {
+        size: 8,
+        cell_width: CellWidth::U16,
+        eof: Eof::Unchanged,
+    };
+    assert_eq!(
+        emit(",[>,]<[.<]", &[], tape),
+        r#"use std::io::{Read, Write};
+
+fn main() {
+    let mut tape = vec![0u16; 8];
+    let mut p: usize = 0;
+    let mut input = std::io::stdin().lock().bytes();
+    let mut output = std::io::BufWriter::new(std::io::stdout().lock());
+    output.flush().unwrap();
+    if let Some(c) = input.next() { tape[p] = c.unwrap() as u16; }
+    while tape[p] != 0 {
+        p += 1;
+        output.flush().unwrap();
+        if let Some(c) = input.next() { tape[p] = c.unwrap() as u16; }
+    }
+    p -= 1;
+    while tape[p] != 0 {
+        output.write_all(&[tape[p] as u8]).unwrap();
+        p -= 1;
+    }
+    output.flush().unwrap();
+}
+"#
+    );
+}
+
+#[test]
+fn eof_minus_one() {
+    let tape = TapeOptions {
+        eof: Eof::MinusOne,
+        ..Default::default()
+    };
+    assert!(emit(",", &[], tape)
+        .contains("    tape[p] = input.next().map_or(u8::MAX, |c| c.unwrap() as u8);\n"));
+}
+
+#[test]
+fn drop_trailing_pointer_moves() {
+    assert_eq!(
+        emit("+>>", &[], TapeOptions::default()),
+        "fn main() {\n    let mut tape = vec![0u8; 30000];\n    let p: usize = 0;\n    tape[p] = tape[p].wrapping_add(1);\n}\n"
+    );
+}
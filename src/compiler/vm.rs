@@ -1,9 +1,151 @@
 use crate::{instruction::Instruction, runtime::vm::VMInstruction};
 
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum, PartialEq, Eq)]
+#[clap(rename_all = "snake_case")]
+pub enum Fusion {
+    #[value(help = "Enable every fusion below")]
+    All,
+
+    /// ポインタの移動と加算(`PointerAdd(n); Add(m)`など)を`MoveAdd`にまとめる
+    #[value(help = "Fuse a pointer move and an addition into MoveAdd")]
+    MoveAdd,
+    /// `SetZero; Add(n)`を`SetValue`にまとめる
+    #[value(help = "Fuse clearing a cell and adding to it into SetValue")]
+    SetValue,
+    /// ポインタを動かすだけのループ(`[>]`など)を`Scan`にまとめる
+    #[value(help = "Turn loops that only move the pointer (like `[>]`) into Scan")]
+    Scan,
+}
+
+/// 実際にまとめる命令の種類。同じ位置で複数当てはまる場合は先にあるものを使う
+pub const FUSIONS: [Fusion; 3] = [Fusion::Scan, Fusion::SetValue, Fusion::MoveAdd];
+
 pub fn compile(instructions: &[Instruction]) -> Vec<VMInstruction> {
     do_compile(instructions, 0)
 }
 
+/// よく現れる命令の並びを1つの命令(スーパー命令)にまとめ、ジャンプ先を付け替える
+pub fn fuse(instructions: &[VMInstruction], fusions: &[Fusion]) -> Vec<VMInstruction> {
+    let all = fusions.contains(&Fusion::All);
+    let enabled = FUSIONS
+        .into_iter()
+        .filter(|fusion| all || fusions.contains(fusion))
+        .collect::<Vec<_>>();
+    let landings = landings(instructions);
+
+    let mut fused = Vec::new();
+    // 元の命令の位置から、まとめた後の位置への対応
+    let mut positions = vec![0; instructions.len() + 1];
+    let mut i = 0;
+    while i < instructions.len() {
+        let (len, inst) = enabled
+            .iter()
+            .find_map(|&fusion| match_fusion(instructions, &landings, i, fusion))
+            .unwrap_or((1, instructions[i]));
+        for position in &mut positions[i..i + len] {
+            *position = fused.len();
+        }
+        fused.push(inst);
+        i += len;
+    }
+    positions[instructions.len()] = fused.len();
+
+    // ジャンプした後は対象の次の命令から実行するので、その位置を基準に付け替える
+    for inst in &mut fused {
+        match inst {
+            VMInstruction::JumpIfZero(target) | VMInstruction::JumpIfNotZero(target) => {
+                *target = positions[*target + 1] - 1;
+            }
+            _ => {}
+        }
+    }
+    fused
+}
+
+/// 命令列の中で`fusion`を適用できる位置と、まとめられる命令の数
+pub fn fusion_sites(instructions: &[VMInstruction], fusion: Fusion) -> Vec<(usize, usize)> {
+    let landings = landings(instructions);
+    let mut sites = Vec::new();
+    let mut i = 0;
+    while i < instructions.len() {
+        match match_fusion(instructions, &landings, i, fusion) {
+            Some((len, _)) => {
+                sites.push((i, len));
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+    sites
+}
+
+/// ジャンプした後に実行が始まる位置
+fn landings(instructions: &[VMInstruction]) -> Vec<bool> {
+    let mut landings = vec![false; instructions.len() + 1];
+    for inst in instructions {
+        if let VMInstruction::JumpIfZero(target) | VMInstruction::JumpIfNotZero(target) = inst {
+            landings[target + 1] = true;
+        }
+    }
+    landings
+}
+
+/// `i`から始まる命令が`fusion`に当てはまれば、まとめる命令の数とまとめた命令を返す
+fn match_fusion(
+    instructions: &[VMInstruction],
+    landings: &[bool],
+    i: usize,
+    fusion: Fusion,
+) -> Option<(usize, VMInstruction)> {
+    let pattern = instructions.get(i..)?;
+    let fused = match (fusion, pattern) {
+        (Fusion::MoveAdd, [first, second, ..]) => {
+            let offset = pointer_offset(first)?;
+            let value = cell_delta(second)?;
+            (2, VMInstruction::MoveAdd(offset, value))
+        }
+        (Fusion::SetValue, [VMInstruction::SetZero, second, ..]) => {
+            (2, VMInstruction::SetValue(cell_delta(second)?))
+        }
+        (
+            Fusion::Scan,
+            [VMInstruction::JumpIfZero(end), step, VMInstruction::JumpIfNotZero(start), ..],
+        ) if *end == i + 2 && *start == i => {
+            // ループの中に入ってくるジャンプは自身のJumpIfNotZeroだけ
+            return Some((3, VMInstruction::Scan(pointer_offset(step)?)));
+        }
+        _ => return None,
+    };
+    // 途中の命令にジャンプしてくる場合はまとめられない
+    if landings[i + 1..i + fused.0].iter().any(|&landing| landing) {
+        return None;
+    }
+    Some(fused)
+}
+
+fn pointer_offset(inst: &VMInstruction) -> Option<isize> {
+    match *inst {
+        VMInstruction::PointerIncrement => Some(1),
+        VMInstruction::PointerDecrement => Some(-1),
+        VMInstruction::PointerAdd(value) => Some(value as isize),
+        VMInstruction::PointerSubtract(value) => Some(-(value as isize)),
+        _ => None,
+    }
+}
+
+fn cell_delta(inst: &VMInstruction) -> Option<u8> {
+    match *inst {
+        VMInstruction::Increment => Some(1),
+        VMInstruction::Decrement => Some(1u8.wrapping_neg()),
+        VMInstruction::Add(value) => Some(value),
+        VMInstruction::Subtract(value) => Some(value.wrapping_neg()),
+        _ => None,
+    }
+}
+
 fn do_compile(instructions: &[Instruction], offset: usize) -> Vec<VMInstruction> {
     let mut compiled = Vec::new();
     for inst in instructions {
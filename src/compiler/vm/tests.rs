@@ -0,0 +1,97 @@
+use crate::{
+    optimizer::{self, Optimization},
+    parser,
+    runtime::vm::VMInstruction::*,
+};
+
+use super::*;
+
+#[test]
+fn fuse_sequences() {
+    for (instructions, fused) in [
+        (&[PointerIncrement, Add(3)][..], &[MoveAdd(1, 3)][..]),
+        (&[PointerSubtract(2), Decrement], &[MoveAdd(-2, 255)]),
+        (&[SetZero, Add(5)], &[SetValue(5)]),
+        (&[SetZero, Subtract(1)], &[SetValue(255)]),
+        (
+            &[JumpIfZero(2), PointerDecrement, JumpIfNotZero(0)],
+            &[Scan(-1)],
+        ),
+        // ポインタを動かすだけでないループはまとめない
+        (
+            &[JumpIfZero(2), Decrement, JumpIfNotZero(0)],
+            &[JumpIfZero(2), Decrement, JumpIfNotZero(0)],
+        ),
+    ] {
+        assert_eq!(fuse(instructions, &[Fusion::All]), fused);
+    }
+}
+
+#[test]
+fn fuse_only_enabled_sequences() {
+    let instructions = [SetZero, Increment, PointerIncrement, Increment];
+    assert_eq!(
+        fuse(&instructions, &[Fusion::SetValue]),
+        [SetValue(1), PointerIncrement, Increment]
+    );
+    assert_eq!(
+        fuse(&instructions, &[Fusion::MoveAdd]),
+        [SetZero, Increment, MoveAdd(1, 1)]
+    );
+    assert_eq!(fuse(&instructions, &[]), instructions);
+}
+
+#[test]
+fn retarget_jumps() {
+    // `=2[>+]`
+    let instructions = [
+        SetZero,
+        Add(2),
+        JumpIfZero(5),
+        PointerIncrement,
+        Increment,
+        JumpIfNotZero(2),
+    ];
+    assert_eq!(
+        fuse(&instructions, &[Fusion::All]),
+        [SetValue(2), JumpIfZero(3), MoveAdd(1, 1), JumpIfNotZero(1)]
+    );
+}
+
+#[test]
+fn retarget_ifs_ending_with_fused_instructions() {
+    // ifは本体の最後の命令へジャンプするので、まとめた命令へジャンプする
+    let instructions = [JumpIfZero(2), PointerIncrement, Increment, PutChar];
+    assert_eq!(
+        fuse(&instructions, &[Fusion::All]),
+        [JumpIfZero(1), MoveAdd(1, 1), PutChar]
+    );
+}
+
+#[test]
+fn keep_landings_unfused() {
+    // ifの本体の最後の`>`と、ifを飛ばした後に実行する`+`はまとめられない
+    let instructions = [JumpIfZero(2), Decrement, PointerIncrement, Increment];
+    assert_eq!(fuse(&instructions, &[Fusion::All]), instructions);
+    assert_eq!(fusion_sites(&instructions, Fusion::MoveAdd), []);
+}
+
+#[test]
+fn fuse_compiled_loops() {
+    let program = optimizer::optimize(
+        parser::parse("+[>>]<[-]++").unwrap(),
+        &[Optimization::ConsecutiveIncDec],
+    );
+    assert_eq!(
+        fuse(&compile(&program), &[Fusion::All]),
+        [
+            Increment,
+            Scan(2),
+            PointerDecrement,
+            JumpIfZero(5),
+            Decrement,
+            JumpIfNotZero(3),
+            Add(2),
+        ]
+    );
+}
@@ -0,0 +1,466 @@
+//! WebAssemblyのモジュールを生成する
+//!
+//! 生成されるモジュールは次の関数をインポートし、`main`関数と`memory`をエクスポートする。
+//! - `env.putchar: (param i32)`: セルの値を出力する
+//! - `env.getchar: (result i32)`: 1バイト読み込む。入力の終わりでは-1を返す
+
+use std::fmt::Write;
+
+use crate::{
+    instruction::Instruction,
+    tape::{CellWidth, Eof, TapeOptions},
+};
+
+#[cfg(test)]
+mod tests;
+
+const PAGE_SIZE: usize = 65536;
+
+const PUTCHAR: u32 = 0;
+const GETCHAR: u32 = 1;
+const MAIN: u32 = 2;
+
+/// ポインタ(セルのアドレス)を保持するローカル変数
+const POINTER: u32 = 0;
+/// `getchar`の戻り値を一時的に保持するローカル変数
+const INPUT: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasmInstruction {
+    LocalGet(u32),
+    LocalSet(u32),
+    I32Const(i32),
+    I32Add,
+    I32Sub,
+    I32Mul,
+    I32Eqz,
+    I32Ne,
+    Select,
+    Block,
+    Loop,
+    If,
+    End,
+    Br(u32),
+    BrIf(u32),
+    Call(u32),
+    /// オペランドはメモリ上のオフセット
+    I32Load8U(u32),
+    I32Load16U(u32),
+    I32Load(u32),
+    I32Store8(u32),
+    I32Store16(u32),
+    I32Store(u32),
+}
+
+pub struct Module {
+    /// メモリのページ数
+    pub pages: u32,
+    /// `main`関数の本体
+    pub code: Vec<WasmInstruction>,
+}
+
+pub fn compile(instructions: &[Instruction], tape: TapeOptions) -> Module {
+    let cell_size = tape.cell_width.bits() as usize / 8;
+    let mut compiler = Compiler {
+        code: Vec::new(),
+        tape,
+        cell_size: cell_size as i32,
+    };
+    compiler.do_compile(instructions);
+
+    Module {
+        pages: (tape.size * cell_size).div_ceil(PAGE_SIZE).max(1) as u32,
+        code: compiler.code,
+    }
+}
+
+struct Compiler {
+    code: Vec<WasmInstruction>,
+    tape: TapeOptions,
+    cell_size: i32,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: WasmInstruction) {
+        self.code.push(inst);
+    }
+
+    /// ポインタから`at`だけ離れたセルのアドレスをスタックに積み、ロード・ストア命令に使うオフセットを返す
+    fn address(&mut self, at: isize) -> u32 {
+        self.push(WasmInstruction::LocalGet(POINTER));
+        let offset = at as i32 * self.cell_size;
+        if offset >= 0 {
+            offset as u32
+        } else {
+            self.push(WasmInstruction::I32Const(offset));
+            self.push(WasmInstruction::I32Add);
+            0
+        }
+    }
+
+    fn load(&mut self, at: isize) {
+        let offset = self.address(at);
+        self.push(match self.tape.cell_width {
+            CellWidth::U8 => WasmInstruction::I32Load8U(offset),
+            CellWidth::U16 => WasmInstruction::I32Load16U(offset),
+            CellWidth::U32 => WasmInstruction::I32Load(offset),
+        });
+    }
+
+    fn store(&mut self, offset: u32) {
+        self.push(match self.tape.cell_width {
+            CellWidth::U8 => WasmInstruction::I32Store8(offset),
+            CellWidth::U16 => WasmInstruction::I32Store16(offset),
+            CellWidth::U32 => WasmInstruction::I32Store(offset),
+        });
+    }
+
+    /// ポインタが指すセルに`value`で計算した値を書き込む
+    fn update(&mut self, value: impl FnOnce(&mut Self)) {
+        let offset = self.address(0);
+        value(self);
+        self.store(offset);
+    }
+
+    fn move_pointer(&mut self, value: isize) {
+        self.push(WasmInstruction::LocalGet(POINTER));
+        self.push(WasmInstruction::I32Const(value as i32 * self.cell_size));
+        self.push(WasmInstruction::I32Add);
+        self.push(WasmInstruction::LocalSet(POINTER));
+    }
+
+    fn do_compile(&mut self, instructions: &[Instruction]) {
+        for inst in instructions {
+            match inst {
+                Instruction::Increment => self.add(1),
+                Instruction::Decrement => self.subtract(1),
+                Instruction::PointerIncrement => self.move_pointer(1),
+                Instruction::PointerDecrement => self.move_pointer(-1),
+                Instruction::PutChar => {
+                    self.load(0);
+                    self.push(WasmInstruction::Call(PUTCHAR));
+                }
+                Instruction::GetChar => {
+                    self.push(WasmInstruction::Call(GETCHAR));
+                    self.push(WasmInstruction::LocalSet(INPUT));
+                    match self.tape.eof {
+                        Eof::Zero => self.update(|c| {
+                            c.push(WasmInstruction::LocalGet(INPUT));
+                            c.push(WasmInstruction::I32Const(0));
+                            c.push(WasmInstruction::LocalGet(INPUT));
+                            c.push(WasmInstruction::I32Const(-1));
+                            c.push(WasmInstruction::I32Ne);
+                            c.push(WasmInstruction::Select);
+                        }),
+                        // -1をストアすると全ビットが1になる
+                        Eof::MinusOne => self.update(|c| c.push(WasmInstruction::LocalGet(INPUT))),
+                        Eof::Unchanged => {
+                            self.push(WasmInstruction::LocalGet(INPUT));
+                            self.push(WasmInstruction::I32Const(-1));
+                            self.push(WasmInstruction::I32Ne);
+                            self.push(WasmInstruction::If);
+                            self.update(|c| c.push(WasmInstruction::LocalGet(INPUT)));
+                            self.push(WasmInstruction::End);
+                        }
+                    }
+                }
+                Instruction::Loop(loop_instructions) => {
+                    self.push(WasmInstruction::Block);
+                    self.push(WasmInstruction::Loop);
+                    self.load(0);
+                    self.push(WasmInstruction::I32Eqz);
+                    self.push(WasmInstruction::BrIf(1));
+                    self.do_compile(loop_instructions);
+                    self.push(WasmInstruction::Br(0));
+                    self.push(WasmInstruction::End);
+                    self.push(WasmInstruction::End);
+                }
+                Instruction::Add(value) => self.add(*value as i32),
+                Instruction::Subtract(value) => self.subtract(*value as i32),
+                Instruction::SetZero => self.update(|c| c.push(WasmInstruction::I32Const(0))),
+                Instruction::PointerAdd(value) => self.move_pointer(*value as isize),
+                Instruction::PointerSubtract(value) => self.move_pointer(-(*value as isize)),
+                Instruction::AddValueAt(at) => self.update(|c| {
+                    c.load(0);
+                    c.load(*at);
+                    c.push(WasmInstruction::I32Add);
+                }),
+                Instruction::SubtractValueAt(at) => self.update(|c| {
+                    c.load(0);
+                    c.load(*at);
+                    c.push(WasmInstruction::I32Sub);
+                }),
+                Instruction::AddValueMultipliedBy(value, at) => self.update(|c| {
+                    c.load(0);
+                    c.load(*at);
+                    c.push(WasmInstruction::I32Const(*value as i32));
+                    c.push(WasmInstruction::I32Mul);
+                    c.push(WasmInstruction::I32Add);
+                }),
+                Instruction::SubtractValueMultipliedBy(value, at) => self.update(|c| {
+                    c.load(0);
+                    c.load(*at);
+                    c.push(WasmInstruction::I32Const(*value as i32));
+                    c.push(WasmInstruction::I32Mul);
+                    c.push(WasmInstruction::I32Sub);
+                }),
+                Instruction::Negate => self.update(|c| {
+                    c.push(WasmInstruction::I32Const(0));
+                    c.load(0);
+                    c.push(WasmInstruction::I32Sub);
+                }),
+                Instruction::IfNotZero(if_instructions) => {
+                    self.load(0);
+                    self.push(WasmInstruction::If);
+                    self.do_compile(if_instructions);
+                    self.push(WasmInstruction::End);
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, value: i32) {
+        self.update(|c| {
+            c.load(0);
+            c.push(WasmInstruction::I32Const(value));
+            c.push(WasmInstruction::I32Add);
+        });
+    }
+
+    fn subtract(&mut self, value: i32) {
+        self.update(|c| {
+            c.load(0);
+            c.push(WasmInstruction::I32Const(value));
+            c.push(WasmInstruction::I32Sub);
+        });
+    }
+}
+
+impl Module {
+    /// テキスト形式(.wat)で出力する
+    pub fn wat(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "(module").unwrap();
+        writeln!(
+            out,
+            "  (import \"env\" \"putchar\" (func $putchar (param i32)))"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "  (import \"env\" \"getchar\" (func $getchar (result i32)))"
+        )
+        .unwrap();
+        writeln!(out, "  (memory (export \"memory\") {})", self.pages).unwrap();
+        writeln!(out, "  (func (export \"main\")").unwrap();
+        writeln!(out, "    (local $p i32)").unwrap();
+        writeln!(out, "    (local $c i32)").unwrap();
+
+        let local = |index: u32| if index == POINTER { "$p" } else { "$c" };
+        let memarg = |offset: u32| {
+            if offset == 0 {
+                String::new()
+            } else {
+                format!(" offset={}", offset)
+            }
+        };
+        let mut indent = 2;
+        for inst in &self.code {
+            if *inst == WasmInstruction::End {
+                indent -= 1;
+            }
+            let text = match *inst {
+                WasmInstruction::LocalGet(index) => format!("local.get {}", local(index)),
+                WasmInstruction::LocalSet(index) => format!("local.set {}", local(index)),
+                WasmInstruction::I32Const(value) => format!("i32.const {}", value),
+                WasmInstruction::I32Add => "i32.add".to_string(),
+                WasmInstruction::I32Sub => "i32.sub".to_string(),
+                WasmInstruction::I32Mul => "i32.mul".to_string(),
+                WasmInstruction::I32Eqz => "i32.eqz".to_string(),
+                WasmInstruction::I32Ne => "i32.ne".to_string(),
+                WasmInstruction::Select => "select".to_string(),
+                WasmInstruction::Block => "block".to_string(),
+                WasmInstruction::Loop => "loop".to_string(),
+                WasmInstruction::If => "if".to_string(),
+                WasmInstruction::End => "end".to_string(),
+                WasmInstruction::Br(depth) => format!("br {}", depth),
+                WasmInstruction::BrIf(depth) => format!("br_if {}", depth),
+                WasmInstruction::Call(PUTCHAR) => "call $putchar".to_string(),
+                WasmInstruction::Call(GETCHAR) => "call $getchar".to_string(),
+                WasmInstruction::Call(index) => format!("call {}", index),
+                WasmInstruction::I32Load8U(offset) => format!("i32.load8_u{}", memarg(offset)),
+                WasmInstruction::I32Load16U(offset) => {
+                    format!("i32.load16_u{}", memarg(offset))
+                }
+                WasmInstruction::I32Load(offset) => format!("i32.load{}", memarg(offset)),
+                WasmInstruction::I32Store8(offset) => format!("i32.store8{}", memarg(offset)),
+                WasmInstruction::I32Store16(offset) => {
+                    format!("i32.store16{}", memarg(offset))
+                }
+                WasmInstruction::I32Store(offset) => format!("i32.store{}", memarg(offset)),
+            };
+            writeln!(out, "{}{}", "  ".repeat(indent), text).unwrap();
+            if matches!(
+                inst,
+                WasmInstruction::Block | WasmInstruction::Loop | WasmInstruction::If
+            ) {
+                indent += 1;
+            }
+        }
+
+        writeln!(out, "  )").unwrap();
+        writeln!(out, ")").unwrap();
+        out
+    }
+
+    /// バイナリ形式(.wasm)で出力する
+    pub fn wasm(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(b"\0asm");
+        out.extend(1u32.to_le_bytes());
+
+        // Type: 0 = (i32) -> (), 1 = () -> (i32), 2 = () -> ()
+        let mut types = Vec::new();
+        unsigned(&mut types, 3);
+        types.extend([0x60, 1, 0x7f, 0]);
+        types.extend([0x60, 0, 1, 0x7f]);
+        types.extend([0x60, 0, 0]);
+        section(&mut out, 1, &types);
+
+        // Import
+        let mut imports = Vec::new();
+        unsigned(&mut imports, 2);
+        for (name, type_index) in [("putchar", 0), ("getchar", 1)] {
+            string(&mut imports, "env");
+            string(&mut imports, name);
+            imports.push(0x00); // func
+            unsigned(&mut imports, type_index);
+        }
+        section(&mut out, 2, &imports);
+
+        // Function
+        section(&mut out, 3, &[1, 2]);
+
+        // Memory
+        let mut memory = vec![1, 0x00];
+        unsigned(&mut memory, self.pages);
+        section(&mut out, 5, &memory);
+
+        // Export
+        let mut exports = Vec::new();
+        unsigned(&mut exports, 2);
+        string(&mut exports, "main");
+        exports.push(0x00); // func
+        unsigned(&mut exports, MAIN);
+        string(&mut exports, "memory");
+        exports.push(0x02); // memory
+        unsigned(&mut exports, 0);
+        section(&mut out, 7, &exports);
+
+        // Code
+        let mut body = Vec::new();
+        body.extend([1, 2, 0x7f]); // local i32 × 2
+        for inst in &self.code {
+            encode(&mut body, *inst);
+        }
+        body.push(0x0b);
+
+        let mut code = Vec::new();
+        unsigned(&mut code, 1);
+        unsigned(&mut code, body.len() as u32);
+        code.extend(body);
+        section(&mut out, 10, &code);
+
+        out
+    }
+}
+
+fn encode(out: &mut Vec<u8>, inst: WasmInstruction) {
+    match inst {
+        WasmInstruction::LocalGet(index) => {
+            out.push(0x20);
+            unsigned(out, index);
+        }
+        WasmInstruction::LocalSet(index) => {
+            out.push(0x21);
+            unsigned(out, index);
+        }
+        WasmInstruction::I32Const(value) => {
+            out.push(0x41);
+            signed(out, value);
+        }
+        WasmInstruction::I32Add => out.push(0x6a),
+        WasmInstruction::I32Sub => out.push(0x6b),
+        WasmInstruction::I32Mul => out.push(0x6c),
+        WasmInstruction::I32Eqz => out.push(0x45),
+        WasmInstruction::I32Ne => out.push(0x47),
+        WasmInstruction::Select => out.push(0x1b),
+        // ブロックの型は常に空(0x40)
+        WasmInstruction::Block => out.extend([0x02, 0x40]),
+        WasmInstruction::Loop => out.extend([0x03, 0x40]),
+        WasmInstruction::If => out.extend([0x04, 0x40]),
+        WasmInstruction::End => out.push(0x0b),
+        WasmInstruction::Br(depth) => {
+            out.push(0x0c);
+            unsigned(out, depth);
+        }
+        WasmInstruction::BrIf(depth) => {
+            out.push(0x0d);
+            unsigned(out, depth);
+        }
+        WasmInstruction::Call(index) => {
+            out.push(0x10);
+            unsigned(out, index);
+        }
+        WasmInstruction::I32Load(offset) => memarg(out, 0x28, offset),
+        WasmInstruction::I32Load8U(offset) => memarg(out, 0x2d, offset),
+        WasmInstruction::I32Load16U(offset) => memarg(out, 0x2f, offset),
+        WasmInstruction::I32Store(offset) => memarg(out, 0x36, offset),
+        WasmInstruction::I32Store8(offset) => memarg(out, 0x3a, offset),
+        WasmInstruction::I32Store16(offset) => memarg(out, 0x3b, offset),
+    }
+}
+
+/// メモリ命令。アラインメントはヒントなので、常に1バイト境界を指定する
+fn memarg(out: &mut Vec<u8>, opcode: u8, offset: u32) {
+    out.push(opcode);
+    unsigned(out, 0);
+    unsigned(out, offset);
+}
+
+fn section(out: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    out.push(id);
+    unsigned(out, contents.len() as u32);
+    out.extend(contents);
+}
+
+fn string(out: &mut Vec<u8>, s: &str) {
+    unsigned(out, s.len() as u32);
+    out.extend(s.as_bytes());
+}
+
+/// 符号なしLEB128
+fn unsigned(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// 符号付きLEB128
+fn signed(out: &mut Vec<u8>, mut value: i32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
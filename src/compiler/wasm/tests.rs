@@ -0,0 +1,428 @@
+//! 生成したバイナリを、生成される命令だけを実装した小さなインタプリタで検証する
+
+use crate::{
+    optimizer::{self, Optimization},
+    parser,
+    tape::{CellWidth, Eof, TapeOptions},
+};
+
+use super::*;
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> u8 {
+        let byte = self.bytes[self.pos];
+        self.pos += 1;
+        byte
+    }
+
+    fn bytes(&mut self, len: usize) -> &'a [u8] {
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        bytes
+    }
+
+    fn unsigned(&mut self) -> u32 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte();
+            value |= ((byte & 0x7f) as u32) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+        }
+    }
+
+    fn signed(&mut self) -> i32 {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte();
+            value |= ((byte & 0x7f) as i64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return value as i32;
+            }
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let len = self.unsigned() as usize;
+        String::from_utf8(self.bytes(len).to_vec()).unwrap()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+}
+
+#[derive(Debug, Default)]
+struct ParsedModule {
+    types: Vec<(Vec<u8>, Vec<u8>)>,
+    imports: Vec<(String, String, u32)>,
+    functions: Vec<u32>,
+    pages: u32,
+    exports: Vec<(String, u8, u32)>,
+    locals: u32,
+    body: Vec<WasmInstruction>,
+}
+
+fn parse(wasm: &[u8]) -> ParsedModule {
+    let mut reader = Reader {
+        bytes: wasm,
+        pos: 0,
+    };
+    assert_eq!(reader.bytes(4), b"\0asm");
+    assert_eq!(reader.bytes(4), [1, 0, 0, 0]);
+
+    let mut module = ParsedModule::default();
+    let mut last_id = 0;
+    while !reader.is_empty() {
+        let id = reader.byte();
+        assert!(id > last_id, "sections must be ordered");
+        last_id = id;
+        let len = reader.unsigned() as usize;
+        let mut section = Reader {
+            bytes: reader.bytes(len),
+            pos: 0,
+        };
+        let count = section.unsigned();
+        for _ in 0..count {
+            match id {
+                1 => {
+                    assert_eq!(section.byte(), 0x60);
+                    let params = section.unsigned() as usize;
+                    let params = section.bytes(params).to_vec();
+                    let results = section.unsigned() as usize;
+                    let results = section.bytes(results).to_vec();
+                    module.types.push((params, results));
+                }
+                2 => {
+                    let module_name = section.name();
+                    let name = section.name();
+                    assert_eq!(section.byte(), 0x00);
+                    module.imports.push((module_name, name, section.unsigned()));
+                }
+                3 => module.functions.push(section.unsigned()),
+                5 => {
+                    assert_eq!(section.byte(), 0x00);
+                    module.pages = section.unsigned();
+                }
+                7 => {
+                    let name = section.name();
+                    let kind = section.byte();
+                    module.exports.push((name, kind, section.unsigned()));
+                }
+                10 => {
+                    let len = section.unsigned() as usize;
+                    let mut body = Reader {
+                        bytes: section.bytes(len),
+                        pos: 0,
+                    };
+                    for _ in 0..body.unsigned() {
+                        module.locals += body.unsigned();
+                        assert_eq!(body.byte(), 0x7f);
+                    }
+                    module.body = decode(&mut body);
+                    assert!(body.is_empty());
+                }
+                _ => panic!("unexpected section {}", id),
+            }
+        }
+        assert!(section.is_empty(), "section {} has trailing bytes", id);
+    }
+    module
+}
+
+/// 関数の最後の`end`までを読み込む。最後の`end`は含まない
+fn decode(reader: &mut Reader) -> Vec<WasmInstruction> {
+    let mut code = Vec::new();
+    let mut depth = 0;
+    loop {
+        let opcode = reader.byte();
+        let inst = match opcode {
+            0x02..=0x04 => {
+                assert_eq!(reader.byte(), 0x40);
+                depth += 1;
+                match opcode {
+                    0x02 => WasmInstruction::Block,
+                    0x03 => WasmInstruction::Loop,
+                    _ => WasmInstruction::If,
+                }
+            }
+            0x0b => {
+                if depth == 0 {
+                    return code;
+                }
+                depth -= 1;
+                WasmInstruction::End
+            }
+            0x0c => WasmInstruction::Br(reader.unsigned()),
+            0x0d => WasmInstruction::BrIf(reader.unsigned()),
+            0x10 => WasmInstruction::Call(reader.unsigned()),
+            0x1b => WasmInstruction::Select,
+            0x20 => WasmInstruction::LocalGet(reader.unsigned()),
+            0x21 => WasmInstruction::LocalSet(reader.unsigned()),
+            0x28 | 0x2d | 0x2f | 0x36 | 0x3a | 0x3b => {
+                reader.unsigned();
+                let offset = reader.unsigned();
+                match opcode {
+                    0x28 => WasmInstruction::I32Load(offset),
+                    0x2d => WasmInstruction::I32Load8U(offset),
+                    0x2f => WasmInstruction::I32Load16U(offset),
+                    0x36 => WasmInstruction::I32Store(offset),
+                    0x3a => WasmInstruction::I32Store8(offset),
+                    _ => WasmInstruction::I32Store16(offset),
+                }
+            }
+            0x41 => WasmInstruction::I32Const(reader.signed()),
+            0x45 => WasmInstruction::I32Eqz,
+            0x47 => WasmInstruction::I32Ne,
+            0x6a => WasmInstruction::I32Add,
+            0x6b => WasmInstruction::I32Sub,
+            0x6c => WasmInstruction::I32Mul,
+            _ => panic!("unexpected opcode {:#x}", opcode),
+        };
+        code.push(inst);
+    }
+}
+
+/// `main`を実行し、`putchar`に渡された値を返す
+fn execute(module: &ParsedModule, input: &[u8]) -> Vec<i32> {
+    let code = &module.body;
+
+    // ブロックの開始位置から対応するendの位置
+    let mut ends = vec![0; code.len()];
+    let mut open = Vec::new();
+    for (pc, inst) in code.iter().enumerate() {
+        match inst {
+            WasmInstruction::Block | WasmInstruction::Loop | WasmInstruction::If => open.push(pc),
+            WasmInstruction::End => ends[open.pop().unwrap()] = pc,
+            _ => {}
+        }
+    }
+
+    let mut memory = vec![0u8; module.pages as usize * PAGE_SIZE];
+    let mut locals = vec![0i32; module.locals as usize];
+    let mut stack: Vec<i32> = Vec::new();
+    let mut labels: Vec<usize> = Vec::new();
+    let mut input = input.iter();
+    let mut output = Vec::new();
+
+    let address =
+        |stack: &mut Vec<i32>, offset: u32| stack.pop().unwrap() as u32 as usize + offset as usize;
+
+    let mut pc = 0;
+    let mut steps = 0;
+    while pc < code.len() {
+        steps += 1;
+        assert!(steps < 10_000_000, "too many steps");
+
+        let mut branch = None;
+        match code[pc] {
+            WasmInstruction::LocalGet(index) => stack.push(locals[index as usize]),
+            WasmInstruction::LocalSet(index) => locals[index as usize] = stack.pop().unwrap(),
+            WasmInstruction::I32Const(value) => stack.push(value),
+            WasmInstruction::I32Add
+            | WasmInstruction::I32Sub
+            | WasmInstruction::I32Mul
+            | WasmInstruction::I32Ne => {
+                let b = stack.pop().unwrap();
+                let a = stack.pop().unwrap();
+                stack.push(match code[pc] {
+                    WasmInstruction::I32Add => a.wrapping_add(b),
+                    WasmInstruction::I32Sub => a.wrapping_sub(b),
+                    WasmInstruction::I32Mul => a.wrapping_mul(b),
+                    _ => (a != b) as i32,
+                });
+            }
+            WasmInstruction::I32Eqz => {
+                let a = stack.pop().unwrap();
+                stack.push((a == 0) as i32);
+            }
+            WasmInstruction::Select => {
+                let condition = stack.pop().unwrap();
+                let b = stack.pop().unwrap();
+                let a = stack.pop().unwrap();
+                stack.push(if condition != 0 { a } else { b });
+            }
+            WasmInstruction::Block | WasmInstruction::Loop => labels.push(pc),
+            WasmInstruction::If => {
+                if stack.pop().unwrap() != 0 {
+                    labels.push(pc);
+                } else {
+                    pc = ends[pc];
+                }
+            }
+            WasmInstruction::End => {
+                labels.pop();
+            }
+            WasmInstruction::Br(depth) => branch = Some(depth),
+            WasmInstruction::BrIf(depth) => {
+                if stack.pop().unwrap() != 0 {
+                    branch = Some(depth);
+                }
+            }
+            WasmInstruction::Call(PUTCHAR) => output.push(stack.pop().unwrap()),
+            WasmInstruction::Call(GETCHAR) => {
+                stack.push(input.next().map(|&c| c as i32).unwrap_or(-1))
+            }
+            WasmInstruction::Call(index) => panic!("unknown function {}", index),
+            WasmInstruction::I32Load8U(offset) => {
+                let address = address(&mut stack, offset);
+                stack.push(memory[address] as i32);
+            }
+            WasmInstruction::I32Load16U(offset) => {
+                let address = address(&mut stack, offset);
+                stack.push(u16::from_le_bytes([memory[address], memory[address + 1]]) as i32);
+            }
+            WasmInstruction::I32Load(offset) => {
+                let address = address(&mut stack, offset);
+                let bytes = memory[address..address + 4].try_into().unwrap();
+                stack.push(i32::from_le_bytes(bytes));
+            }
+            WasmInstruction::I32Store8(offset)
+            | WasmInstruction::I32Store16(offset)
+            | WasmInstruction::I32Store(offset) => {
+                let value = stack.pop().unwrap().to_le_bytes();
+                let address = address(&mut stack, offset);
+                let len = match code[pc] {
+                    WasmInstruction::I32Store8(_) => 1,
+                    WasmInstruction::I32Store16(_) => 2,
+                    _ => 4,
+                };
+                memory[address..address + len].copy_from_slice(&value[..len]);
+            }
+        }
+
+        if let Some(depth) = branch {
+            let target = labels[labels.len() - 1 - depth as usize];
+            if code[target] == WasmInstruction::Loop {
+                labels.truncate(labels.len() - depth as usize);
+                pc = target;
+            } else {
+                labels.truncate(labels.len() - 1 - depth as usize);
+                pc = ends[target];
+            }
+        }
+        pc += 1;
+    }
+
+    assert!(stack.is_empty(), "stack is not empty: {:?}", stack);
+    output
+}
+
+fn run(source: &str, optimizations: &[Optimization], tape: TapeOptions, input: &[u8]) -> Vec<i32> {
+    let program = parser::parse(source).unwrap();
+    let optimized = optimizer::optimize(program, optimizations);
+    let wasm = compile(&optimized, tape).wasm();
+    execute(&parse(&wasm), input)
+}
+
+fn bytes(output: &[i32]) -> Vec<u8> {
+    output.iter().map(|&c| c as u8).collect()
+}
+
+#[test]
+fn binary_structure() {
+    let program = parser::parse(HELLO_WORLD).unwrap();
+    let module = parse(&compile(&program, TapeOptions::default()).wasm());
+
+    assert_eq!(
+        module.types,
+        [(vec![0x7f], vec![]), (vec![], vec![0x7f]), (vec![], vec![])]
+    );
+    assert_eq!(
+        module.imports,
+        [
+            ("env".to_string(), "putchar".to_string(), 0),
+            ("env".to_string(), "getchar".to_string(), 1)
+        ]
+    );
+    assert_eq!(module.functions, [2]);
+    assert_eq!(module.pages, 1);
+    assert_eq!(
+        module.exports,
+        [("main".to_string(), 0, MAIN), ("memory".to_string(), 2, 0)]
+    );
+    assert_eq!(module.locals, 2);
+}
+
+#[test]
+fn memory_pages() {
+    let tape = TapeOptions {
+        size: 30000,
+        cell_width: CellWidth::U32,
+        eof: Eof::Zero,
+    };
+    assert_eq!(compile(&[], tape).pages, 2);
+}
+
+#[test]
+fn hello_world() {
+    for optimizations in [&[][..], &[Optimization::All]] {
+        let output = run(HELLO_WORLD, optimizations, TapeOptions::default(), b"");
+        assert_eq!(bytes(&output), b"Hello World!\n");
+    }
+}
+
+#[test]
+fn cat() {
+    let output = run(
+        ",[.,]",
+        &[Optimization::All],
+        TapeOptions::default(),
+        b"abc",
+    );
+    assert_eq!(bytes(&output), b"abc");
+}
+
+#[test]
+fn multiplication_with_negative_offset() {
+    for optimizations in [&[][..], &[Optimization::All]] {
+        let output = run(
+            ">>++++[-<+++<++>>]<.<.",
+            optimizations,
+            TapeOptions::default(),
+            b"",
+        );
+        assert_eq!(output, [12, 8]);
+    }
+}
+
+#[test]
+fn cell_width() {
+    for (cell_width, expected) in [
+        (CellWidth::U8, 0xff),
+        (CellWidth::U16, 0xffff),
+        (CellWidth::U32, -1),
+    ] {
+        let tape = TapeOptions {
+            cell_width,
+            ..Default::default()
+        };
+        assert_eq!(run("-.", &[], tape, b""), [expected]);
+    }
+}
+
+#[test]
+fn eof() {
+    for (eof, expected) in [(Eof::Zero, 0), (Eof::MinusOne, 0xff), (Eof::Unchanged, 5)] {
+        let tape = TapeOptions {
+            eof,
+            ..Default::default()
+        };
+        assert_eq!(run("+++++,.", &[], tape, b""), [expected]);
+    }
+}
@@ -0,0 +1,639 @@
+use std::ffi::c_void;
+
+use crate::{
+    assembler::x86_64::{addressing_mode::AddressingMode, Assembler, ByteRegister, QwordRegister},
+    instruction::Instruction,
+};
+
+#[cfg(test)]
+mod tests;
+
+extern "C" {
+    fn putchar(c: i32) -> i32;
+    fn getchar() -> i32;
+}
+
+/// VMと同じく、入力の終わりでは`EOF`(-1)の代わりに0を返す。
+/// 読み込みのエラーも`EOF`になるので区別できない
+unsafe extern "C" fn getchar_or_zero() -> i32 {
+    match getchar() {
+        libc::EOF => 0,
+        c => c,
+    }
+}
+
+const POINTER_REGISTER: QwordRegister = QwordRegister::Rdx; // arg1
+
+/// `compile_relocatable`で生成した関数で、第2引数のコンテキストを保存しておく位置
+const CONTEXT: AddressingMode = AddressingMode::IndirectDisplacement8 {
+    base: QwordRegister::Rbp,
+    disp: -8,
+};
+
+/// 命令を実行する直前に呼び出される関数。
+/// 引数はコンテキスト、`compiler::vm::compile`の出力での命令の位置、ポインタ
+pub type TraceHook = unsafe extern "C" fn(context: *mut c_void, ip: u64, pointer: *mut u8);
+
+#[derive(Default)]
+pub struct CompileOptions {
+    /// 出力関数。libcの`putchar`と同じく、失敗したら負の値を返す。負の値が返ると実行を中断する
+    pub putchar: Option<unsafe extern "C" fn(i32) -> i32>,
+    /// 入力関数。読んだバイトを返す。負の値が返ると実行を中断する
+    pub getchar: Option<unsafe extern "C" fn() -> i32>,
+    /// 指定すると、すべての命令の前にトレース関数の呼び出しを埋め込む
+    pub trace: Option<(TraceHook, *mut c_void)>,
+    /// 指定すると、ループの終わりで繰り返すたびに燃料を1減らし、尽きたら実行を中断する。
+    /// 生成される関数は中断した場合に1、最後まで実行した場合に0を返す
+    pub fuel: Option<*mut Fuel>,
+    /// 指定すると、ポインタを動かすたびと離れたセルを読む前に、そのセルがテープの範囲内にあるかを確かめ、
+    /// 範囲外なら実行を中断する。生成される関数は中断した場合に2、最後まで実行した場合に0を返す
+    pub bounds: Option<*mut Bounds>,
+    /// 指定すると、生成される関数は終了したときのポインタを返す。`fuel`や`bounds`と同時には使えない
+    pub return_pointer: bool,
+}
+
+/// 生成したコードが読み書きする燃料
+#[repr(C)]
+pub struct Fuel {
+    /// 残りの繰り返し回数
+    pub remaining: u64,
+    /// 中断したときのポインタ
+    pub pointer: *mut u8,
+}
+
+/// 生成したコードが確かめるテープの範囲
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Bounds {
+    /// テープの先頭
+    pub start: *mut u8,
+    /// テープのセルの数
+    pub len: u64,
+    /// 範囲外に出ようとしたときの、テープの先頭からの位置
+    pub reach: i64,
+}
+
+/// 入出力関数への相対呼び出しを含む、配置先が決まっていないコード
+pub struct RelocatableCode {
+    pub code: Vec<u8>,
+    pub relocations: Vec<Relocation>,
+}
+
+/// `call rel32`のオペランドの位置と、呼び出し先の関数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Relocation {
+    pub offset: usize,
+    pub target: IoFunction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoFunction {
+    PutChar,
+    GetChar,
+}
+
+/// 生成するコードに埋め込む計測用の処理
+#[derive(Default)]
+struct Instrumentation {
+    /// トレース関数とそのコンテキストのアドレス
+    trace: Option<(u64, u64)>,
+    /// 次の命令の`compiler::vm::compile`の出力での位置
+    ip: usize,
+    /// 燃料のアドレス
+    fuel: Option<u64>,
+    /// 燃料が尽きたときの`je rel32`の位置
+    out_of_fuel: Vec<usize>,
+    /// テープの範囲のアドレス
+    bounds: Option<u64>,
+    /// テープの範囲外に出たときの`jae rel32`の位置
+    out_of_tape: Vec<usize>,
+    /// 入出力関数が失敗したときの`jl rel32`の位置
+    io_failed: Vec<usize>,
+    /// 終了したときのポインタを返すかどうか
+    return_pointer: bool,
+}
+
+/// 入出力関数の呼び出し方
+enum IoCall<'a> {
+    /// 絶対アドレスをレジスタに読み込んで呼び出す
+    Absolute { putchar: u64, getchar: u64 },
+    /// `call rel32`で呼び出し、オペランドの位置を記録する
+    Relative {
+        relocations: &'a mut Vec<Relocation>,
+    },
+}
+
+pub fn compile(instructions: &[Instruction], options: CompileOptions) -> Vec<u8> {
+    let mut io = IoCall::Absolute {
+        putchar: options.putchar.unwrap_or(putchar) as usize as u64,
+        getchar: options.getchar.unwrap_or(getchar_or_zero) as usize as u64,
+    };
+    let mut instrumentation = Instrumentation {
+        trace: options
+            .trace
+            .map(|(hook, context)| (hook as usize as u64, context as u64)),
+        fuel: options.fuel.map(|fuel| fuel as u64),
+        bounds: options.bounds.map(|bounds| bounds as u64),
+        return_pointer: options.return_pointer,
+        ..Default::default()
+    };
+    assert!(
+        !((instrumentation.fuel.is_some() || instrumentation.bounds.is_some())
+            && instrumentation.return_pointer),
+        "fuel and bounds cannot be used together with return_pointer"
+    );
+    compile_function(instructions, &mut io, &mut instrumentation)
+}
+
+/// 入出力関数の呼び出しを`call rel32`で出力する。
+/// 呼び出し先のアドレスは`relocations`に従って後から埋める必要がある。
+///
+/// 生成される関数は`void f(uint8_t *tape, void *ctx)`で、入出力関数は
+/// `void putchar(void *ctx, int c)`と`int getchar(void *ctx)`として呼び出される
+pub fn compile_relocatable(instructions: &[Instruction]) -> RelocatableCode {
+    let mut relocations = Vec::new();
+    let code = compile_function(
+        instructions,
+        &mut IoCall::Relative {
+            relocations: &mut relocations,
+        },
+        &mut Instrumentation::default(),
+    );
+    RelocatableCode { code, relocations }
+}
+
+fn compile_function(
+    instructions: &[Instruction],
+    io: &mut IoCall,
+    instrumentation: &mut Instrumentation,
+) -> Vec<u8> {
+    let mut assembler = Assembler::new();
+
+    assembler.push_r64(QwordRegister::Rbp);
+    assembler.mov_rm64_r64(
+        AddressingMode::Register {
+            reg: QwordRegister::Rbp,
+        },
+        QwordRegister::Rsp,
+    );
+
+    // メモリのアドレスは第1引数に渡される
+    assembler.mov_rm64_r64(
+        AddressingMode::Register {
+            reg: POINTER_REGISTER,
+        },
+        QwordRegister::Rdi,
+    );
+
+    // コンテキストは第2引数に渡される。呼び出し時のスタックのアラインメントもここで揃う
+    let has_context = matches!(io, IoCall::Relative { .. });
+    if has_context {
+        assembler.push_r64(QwordRegister::Rsi);
+    }
+
+    do_compile(instructions, &mut assembler, io, instrumentation);
+
+    // 入出力関数が失敗したときは、最後まで実行したときと同じように終了する
+    let finish = assembler.code.len();
+    for &jump in &instrumentation.io_failed {
+        assembler.set_jl_rel32(jump, (finish - jump - 6) as i32);
+    }
+
+    if instrumentation.fuel.is_none() && instrumentation.bounds.is_none() {
+        if instrumentation.return_pointer {
+            assembler.mov_rm64_r64(
+                AddressingMode::Register {
+                    reg: QwordRegister::Rax,
+                },
+                POINTER_REGISTER,
+            );
+        }
+        epilogue(&mut assembler, has_context);
+        return assembler.code;
+    }
+
+    assembler.mov_r32_imm32(QwordRegister::Rax, 0);
+    epilogue(&mut assembler, has_context);
+
+    if instrumentation.fuel.is_some() {
+        // 燃料が尽きたときはraxに燃料のアドレスが入っている
+        let out_of_fuel = assembler.code.len();
+        for &jump in &instrumentation.out_of_fuel {
+            assembler.set_je_rel32(jump, (out_of_fuel - jump - 6) as i32);
+        }
+        assembler.mov_rm64_r64(
+            AddressingMode::IndirectDisplacement8 {
+                base: QwordRegister::Rax,
+                disp: 8,
+            },
+            POINTER_REGISTER,
+        );
+        assembler.mov_r32_imm32(QwordRegister::Rax, 1);
+        epilogue(&mut assembler, has_context);
+    }
+
+    if instrumentation.bounds.is_some() {
+        // 範囲外に出たときはrcxにテープの範囲のアドレス、raxにセルの位置が入っている
+        let out_of_tape = assembler.code.len();
+        for &jump in &instrumentation.out_of_tape {
+            assembler.set_jae_rel32(jump, (out_of_tape - jump - 6) as i32);
+        }
+        assembler.mov_rm64_r64(
+            AddressingMode::IndirectDisplacement8 {
+                base: QwordRegister::Rcx,
+                disp: 16,
+            },
+            QwordRegister::Rax,
+        );
+        assembler.mov_r32_imm32(QwordRegister::Rax, 2);
+        epilogue(&mut assembler, has_context);
+    }
+
+    assembler.code
+}
+
+fn epilogue(assembler: &mut Assembler, has_context: bool) {
+    if has_context {
+        assembler.pop_r64(QwordRegister::Rsi);
+    }
+    assembler.pop_r64(QwordRegister::Rbp);
+    assembler.ret();
+}
+
+fn do_compile(
+    instructions: &[Instruction],
+    assembler: &mut Assembler,
+    io: &mut IoCall,
+    instrumentation: &mut Instrumentation,
+) {
+    for inst in instructions {
+        call_trace(assembler, instrumentation);
+        match inst {
+            Instruction::Increment => {
+                assembler.inc_rm8(AddressingMode::Indirect {
+                    reg: POINTER_REGISTER,
+                });
+            }
+            Instruction::Decrement => {
+                assembler.dec_rm8(AddressingMode::Indirect {
+                    reg: POINTER_REGISTER,
+                });
+            }
+            Instruction::PointerIncrement => {
+                assembler.add_rm64_imm8(
+                    AddressingMode::Register {
+                        reg: POINTER_REGISTER,
+                    },
+                    1,
+                );
+                check_bounds(assembler, instrumentation, 0);
+            }
+            Instruction::PointerDecrement => {
+                assembler.sub_rm64_imm8(
+                    AddressingMode::Register {
+                        reg: POINTER_REGISTER,
+                    },
+                    1,
+                );
+                check_bounds(assembler, instrumentation, 0);
+            }
+            Instruction::PutChar => {
+                assembler.push_r64(POINTER_REGISTER);
+                call_io(assembler, io, IoFunction::PutChar);
+                assembler.pop_r64(POINTER_REGISTER);
+                check_io(assembler, io, instrumentation);
+            }
+            Instruction::GetChar => {
+                assembler.push_r64(POINTER_REGISTER);
+                call_io(assembler, io, IoFunction::GetChar);
+                assembler.pop_r64(POINTER_REGISTER);
+                check_io(assembler, io, instrumentation);
+
+                assembler.mov_rm8_r8(
+                    AddressingMode::Indirect {
+                        reg: POINTER_REGISTER,
+                    },
+                    ByteRegister::Al,
+                );
+            }
+            Instruction::Loop(loop_instructions) => {
+                assembler.cmp_rm8_imm8(
+                    AddressingMode::Indirect {
+                        reg: POINTER_REGISTER,
+                    },
+                    0,
+                );
+
+                let jump = assembler.code.len();
+                assembler.je_rel32(0);
+
+                let start = assembler.code.len();
+                do_compile(loop_instructions, assembler, io, instrumentation);
+
+                // VMのJumpIfNotZeroに対応する
+                call_trace(assembler, instrumentation);
+                assembler.cmp_rm8_imm8(
+                    AddressingMode::Indirect {
+                        reg: POINTER_REGISTER,
+                    },
+                    0,
+                );
+                if instrumentation.fuel.is_some() {
+                    // 繰り返すときだけ燃料を消費する
+                    let exit = assembler.code.len();
+                    assembler.je_rel32(0);
+                    consume_fuel(assembler, instrumentation);
+                    assembler
+                        .jmp_rel32((start as isize - assembler.code.len() as isize - 5) as i32);
+                    let end = assembler.code.len();
+                    assembler.set_je_rel32(exit, (end - exit - 6) as i32);
+                } else {
+                    assembler
+                        .jne_rel32((start as isize - assembler.code.len() as isize - 6) as i32);
+                }
+
+                let end = assembler.code.len();
+
+                assembler.set_je_rel32(jump, (end - jump - 6) as i32);
+            }
+            Instruction::Add(value) => {
+                assembler.add_rm8_imm8(
+                    AddressingMode::Indirect {
+                        reg: POINTER_REGISTER,
+                    },
+                    *value,
+                );
+            }
+            Instruction::Subtract(value) => {
+                assembler.sub_rm8_imm8(
+                    AddressingMode::Indirect {
+                        reg: POINTER_REGISTER,
+                    },
+                    *value,
+                );
+            }
+            Instruction::SetZero => {
+                assembler.mov_rm8_imm8(
+                    AddressingMode::Indirect {
+                        reg: POINTER_REGISTER,
+                    },
+                    0,
+                );
+            }
+            Instruction::PointerAdd(value) => {
+                let rm64 = AddressingMode::Register {
+                    reg: POINTER_REGISTER,
+                };
+                // imm8は符号拡張されるので、128以上は4バイトの即値にする
+                if *value < 0x80 {
+                    assembler.add_rm64_imm8(rm64, *value as u8);
+                } else {
+                    let value = i32::try_from(*value).expect("value is too large");
+                    assembler.add_rm64_imm32(rm64, value as u32);
+                }
+                check_bounds(assembler, instrumentation, 0);
+            }
+            Instruction::PointerSubtract(value) => {
+                let rm64 = AddressingMode::Register {
+                    reg: POINTER_REGISTER,
+                };
+                // imm8は符号拡張されるので、128以上は4バイトの即値にする
+                if *value < 0x80 {
+                    assembler.sub_rm64_imm8(rm64, *value as u8);
+                } else {
+                    let value = i32::try_from(*value).expect("value is too large");
+                    assembler.sub_rm64_imm32(rm64, value as u32);
+                }
+                check_bounds(assembler, instrumentation, 0);
+            }
+            Instruction::AddValueAt(at) => {
+                check_bounds(assembler, instrumentation, *at);
+                assembler.mov_r8_rm8(
+                    ByteRegister::Al,
+                    AddressingMode::IndirectDisplacement32 {
+                        base: POINTER_REGISTER,
+                        disp: *at as i32,
+                    },
+                );
+                assembler.add_rm8_r8(
+                    AddressingMode::Indirect {
+                        reg: POINTER_REGISTER,
+                    },
+                    ByteRegister::Al,
+                );
+            }
+            Instruction::SubtractValueAt(at) => {
+                check_bounds(assembler, instrumentation, *at);
+                assembler.mov_r8_rm8(
+                    ByteRegister::Al,
+                    AddressingMode::IndirectDisplacement32 {
+                        base: POINTER_REGISTER,
+                        disp: *at as i32,
+                    },
+                );
+                assembler.sub_rm8_r8(
+                    AddressingMode::Indirect {
+                        reg: POINTER_REGISTER,
+                    },
+                    ByteRegister::Al,
+                );
+            }
+            Instruction::AddValueMultipliedBy(mul, at) => {
+                check_bounds(assembler, instrumentation, *at);
+                assembler.mov_r8_rm8(
+                    ByteRegister::Al,
+                    AddressingMode::IndirectDisplacement32 {
+                        base: POINTER_REGISTER,
+                        disp: *at as i32,
+                    },
+                );
+
+                assembler.mov_rm8_imm8(
+                    AddressingMode::Register {
+                        reg: QwordRegister::Rcx,
+                    },
+                    *mul,
+                );
+
+                assembler.mul_rm8(AddressingMode::Register {
+                    reg: QwordRegister::Rcx,
+                });
+
+                assembler.add_rm8_r8(
+                    AddressingMode::Indirect {
+                        reg: POINTER_REGISTER,
+                    },
+                    ByteRegister::Al,
+                );
+            }
+            Instruction::SubtractValueMultipliedBy(mul, at) => {
+                check_bounds(assembler, instrumentation, *at);
+                assembler.mov_r8_rm8(
+                    ByteRegister::Al,
+                    AddressingMode::IndirectDisplacement32 {
+                        base: POINTER_REGISTER,
+                        disp: *at as i32,
+                    },
+                );
+
+                assembler.mov_rm8_imm8(
+                    AddressingMode::Register {
+                        reg: QwordRegister::Rcx,
+                    },
+                    *mul,
+                );
+
+                assembler.mul_rm8(AddressingMode::Register {
+                    reg: QwordRegister::Rcx,
+                });
+
+                assembler.sub_rm8_r8(
+                    AddressingMode::Indirect {
+                        reg: POINTER_REGISTER,
+                    },
+                    ByteRegister::Al,
+                );
+            }
+            Instruction::Negate => {
+                assembler.neg_rm8(AddressingMode::Indirect {
+                    reg: POINTER_REGISTER,
+                });
+            }
+            Instruction::IfNotZero(if_instructions) => {
+                assembler.cmp_rm8_imm8(
+                    AddressingMode::Indirect {
+                        reg: POINTER_REGISTER,
+                    },
+                    0,
+                );
+
+                let jump = assembler.code.len();
+                assembler.je_rel32(0);
+
+                do_compile(if_instructions, assembler, io, instrumentation);
+
+                let end = assembler.code.len();
+
+                assembler.set_je_rel32(jump, (end - jump - 6) as i32);
+            }
+        }
+    }
+}
+
+/// 入出力関数の引数を設定して呼び出す
+fn call_io(assembler: &mut Assembler, io: &mut IoCall, function: IoFunction) {
+    let value = AddressingMode::Indirect {
+        reg: POINTER_REGISTER,
+    };
+    match io {
+        IoCall::Absolute { putchar, getchar } => {
+            if function == IoFunction::PutChar {
+                assembler.movzx_r32_rm8(QwordRegister::Rdi, value);
+            }
+
+            let address = match function {
+                IoFunction::PutChar => *putchar,
+                IoFunction::GetChar => *getchar,
+            };
+            // 呼び出し元で積んだポインタと合わせて2回積み、呼び出し時のスタックのアラインメントを揃える
+            assembler.push_r64(POINTER_REGISTER);
+            assembler.mov_r64_imm64(QwordRegister::Rax, address);
+            assembler.call_rm64(AddressingMode::Register {
+                reg: QwordRegister::Rax,
+            });
+            assembler.pop_r64(POINTER_REGISTER);
+        }
+        IoCall::Relative { relocations } => {
+            assembler.mov_r64_rm64(QwordRegister::Rdi, CONTEXT);
+            if function == IoFunction::PutChar {
+                assembler.movzx_r32_rm8(QwordRegister::Rsi, value);
+            }
+            relocations.push(Relocation {
+                offset: assembler.code.len() + 1,
+                target: function,
+            });
+            assembler.call_rel32(0);
+        }
+    }
+}
+
+/// 絶対アドレスで呼び出した入出力関数が負の値を返したら中断する
+fn check_io(assembler: &mut Assembler, io: &IoCall, instrumentation: &mut Instrumentation) {
+    if !matches!(io, IoCall::Absolute { .. }) {
+        return;
+    }
+    assembler.cmp_rm32_imm8(
+        AddressingMode::Register {
+            reg: QwordRegister::Rax,
+        },
+        0,
+    );
+    instrumentation.io_failed.push(assembler.code.len());
+    assembler.jl_rel32(0);
+}
+
+/// 燃料を1減らす。尽きていたら中断する
+fn consume_fuel(assembler: &mut Assembler, instrumentation: &mut Instrumentation) {
+    let Some(fuel) = instrumentation.fuel else {
+        return;
+    };
+    let remaining = AddressingMode::Indirect {
+        reg: QwordRegister::Rax,
+    };
+    assembler.mov_r64_imm64(QwordRegister::Rax, fuel);
+    assembler.cmp_rm64_imm8(remaining, 0);
+    instrumentation.out_of_fuel.push(assembler.code.len());
+    assembler.je_rel32(0);
+    assembler.dec_rm64(remaining);
+}
+
+/// ポインタから`offset`離れたセルがテープの範囲外なら中断する。
+/// 位置を符号なしで比べるので、テープの先頭より前も1回の比較で範囲外になる
+fn check_bounds(assembler: &mut Assembler, instrumentation: &mut Instrumentation, offset: isize) {
+    let Some(bounds) = instrumentation.bounds else {
+        return;
+    };
+    let rax = AddressingMode::Register {
+        reg: QwordRegister::Rax,
+    };
+    assembler.mov_r64_imm64(QwordRegister::Rcx, bounds);
+    assembler.mov_rm64_r64(rax, POINTER_REGISTER);
+    if offset != 0 {
+        let offset = i32::try_from(offset).expect("offset is too large");
+        assembler.add_rm64_imm32(rax, offset as u32);
+    }
+    assembler.sub_r64_rm64(
+        QwordRegister::Rax,
+        AddressingMode::Indirect {
+            reg: QwordRegister::Rcx,
+        },
+    );
+    assembler.cmp_r64_rm64(
+        QwordRegister::Rax,
+        AddressingMode::IndirectDisplacement8 {
+            base: QwordRegister::Rcx,
+            disp: 8,
+        },
+    );
+    instrumentation.out_of_tape.push(assembler.code.len());
+    assembler.jae_rel32(0);
+}
+
+/// トレース関数を呼び出す。ポインタはそのまま第3引数として渡す
+fn call_trace(assembler: &mut Assembler, instrumentation: &mut Instrumentation) {
+    let Some((hook, context)) = instrumentation.trace else {
+        return;
+    };
+    // 2回積んで呼び出し時のスタックのアラインメントを揃える
+    assembler.push_r64(POINTER_REGISTER);
+    assembler.push_r64(POINTER_REGISTER);
+    assembler.mov_r64_imm64(QwordRegister::Rdi, context);
+    assembler.mov_r32_imm32(QwordRegister::Rsi, instrumentation.ip as u32);
+    assembler.mov_r64_imm64(QwordRegister::Rax, hook);
+    assembler.call_rm64(AddressingMode::Register {
+        reg: QwordRegister::Rax,
+    });
+    assembler.pop_r64(POINTER_REGISTER);
+    assembler.pop_r64(POINTER_REGISTER);
+    instrumentation.ip += 1;
+}
@@ -0,0 +1,169 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::*;
+use crate::{
+    optimizer::{self, Optimization},
+    parser,
+    runtime::native::NativeFunction,
+};
+
+/// 終了したときのポインタを返すようにコンパイルして実行し、ポインタとテープを返す
+fn run(instructions: &[Instruction]) -> (usize, Vec<u8>) {
+    let code = compile(
+        instructions,
+        CompileOptions {
+            return_pointer: true,
+            ..Default::default()
+        },
+    );
+    let mut memory = vec![0u8; 1000];
+    let pointer = unsafe { NativeFunction::new(&code).call(memory.as_mut_ptr()) };
+    (pointer as usize - memory.as_ptr() as usize, memory)
+}
+
+#[test]
+fn small_pointer_moves() {
+    let (pointer, memory) = run(&[
+        Instruction::PointerAdd(127),
+        Instruction::Increment,
+        Instruction::PointerSubtract(100),
+    ]);
+    assert_eq!(pointer, 27);
+    assert_eq!(memory[127], 1);
+}
+
+#[test]
+fn pointer_moves_of_128_or_more() {
+    // imm8は符号拡張されるので、200を1バイトで書くと-56になる
+    let (pointer, memory) = run(&[
+        Instruction::PointerAdd(200),
+        Instruction::Increment,
+        Instruction::PointerAdd(500),
+        Instruction::Increment,
+        Instruction::PointerSubtract(300),
+    ]);
+    assert_eq!(pointer, 400);
+    assert_eq!(memory[200], 1);
+    assert_eq!(memory[700], 1);
+}
+
+/// 入出力関数の中で、16バイトに揃えたはずのローカル変数が揃っていなかった回数
+static MISALIGNED: AtomicUsize = AtomicUsize::new(0);
+
+/// 呼び出し時のスタックが揃っていないと、ローカル変数のアドレスもずれる
+fn check_alignment() {
+    // u128は16バイトに揃えて配置される
+    let local = 0u128;
+    let address = std::hint::black_box(&local as *const u128 as usize);
+    if !address.is_multiple_of(16) {
+        MISALIGNED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+unsafe extern "C" fn aligned_putchar(c: i32) -> i32 {
+    check_alignment();
+    c
+}
+
+unsafe extern "C" fn aligned_getchar() -> i32 {
+    check_alignment();
+    0
+}
+
+#[test]
+fn io_functions_are_called_on_an_aligned_stack() {
+    let code = compile(
+        &[
+            Instruction::PutChar,
+            Instruction::GetChar,
+            Instruction::Loop(vec![Instruction::PutChar]),
+        ],
+        CompileOptions {
+            putchar: Some(aligned_putchar),
+            getchar: Some(aligned_getchar),
+            ..Default::default()
+        },
+    );
+    let mut memory = vec![0u8; 1];
+    unsafe {
+        NativeFunction::new(&code).call(memory.as_mut_ptr());
+    }
+    assert_eq!(MISALIGNED.load(Ordering::Relaxed), 0);
+}
+
+static PUT_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// 3回目の呼び出しで失敗する出力関数
+unsafe extern "C" fn failing_putchar(c: i32) -> i32 {
+    if PUT_CALLS.fetch_add(1, Ordering::Relaxed) == 2 {
+        return -1;
+    }
+    c
+}
+
+#[test]
+fn stop_when_an_io_function_fails() {
+    // 出力が失敗しなければ終わらない
+    let code = compile(
+        &[
+            Instruction::Add(1),
+            Instruction::Loop(vec![Instruction::PutChar]),
+        ],
+        CompileOptions {
+            putchar: Some(failing_putchar),
+            return_pointer: true,
+            ..Default::default()
+        },
+    );
+    let mut memory = vec![0u8; 1];
+    let pointer = unsafe { NativeFunction::new(&code).call(memory.as_mut_ptr()) };
+    assert_eq!(pointer, memory.as_ptr() as u64);
+    assert_eq!(PUT_CALLS.load(Ordering::Relaxed), 3);
+}
+
+/// テープの範囲を確かめるようにコンパイルして長さ`len`のテープで実行し、戻り値と範囲外の位置を返す
+fn run_bounded(instructions: &[Instruction], len: usize) -> (u64, i64) {
+    let mut bounds = Bounds {
+        start: std::ptr::null_mut(),
+        len: len as u64,
+        reach: 0,
+    };
+    let code = compile(
+        instructions,
+        CompileOptions {
+            bounds: Some(&mut bounds),
+            ..Default::default()
+        },
+    );
+    let mut memory = vec![0u8; len];
+    bounds.start = memory.as_mut_ptr();
+    let result = unsafe { NativeFunction::new(&code).call(memory.as_mut_ptr()) };
+    (result, bounds.reach)
+}
+
+#[test]
+fn stay_within_bounds() {
+    let program = parser::parse("+[>+++<-]>>>>[-]<<<<").unwrap();
+    assert_eq!(run_bounded(&program, 5).0, 0);
+}
+
+#[test]
+fn move_out_of_bounds() {
+    // `+[>+]`はテープの右端を越えるまで進む
+    let program = parser::parse("+[>+]").unwrap();
+    assert_eq!(run_bounded(&program, 10), (2, 10));
+    let program = optimizer::optimize(parser::parse(">>><<<<").unwrap(), &[Optimization::All]);
+    assert_eq!(run_bounded(&program, 10), (2, -1));
+    assert_eq!(
+        run_bounded(&[Instruction::PointerAdd(1000), Instruction::Increment], 10),
+        (2, 1000)
+    );
+}
+
+#[test]
+fn read_out_of_bounds() {
+    let program = [Instruction::Increment, Instruction::AddValueAt(-3)];
+    assert_eq!(run_bounded(&program, 10), (2, -3));
+    let program = [Instruction::AddValueMultipliedBy(2, 10)];
+    assert_eq!(run_bounded(&program, 10), (2, 10));
+}
@@ -0,0 +1,481 @@
+//! VMの上で動く対話的なデバッガ
+//!
+//! 命令と入力の位置を対応させるため、プログラムは最適化せずに実行する。
+//! 実行履歴を記録しているので、逆方向にも実行できる。
+//! 標準入力からはコマンドを読むので、プログラムの入力は起動時にまとめて受け取る
+
+use std::io::{BufRead, Cursor, Write};
+
+use anyhow::{bail, Context};
+
+use crate::{
+    compiler, parser,
+    runtime::{
+        vm::{snapshot::Snapshot, VMInstruction, VM},
+        Input,
+    },
+};
+
+const HELP: &str = "\
+Commands:
+  step [n]              (s) execute n instructions (default 1)
+  next                  (n) execute one instruction, running a whole loop or if at once
+  continue              (c) run until a breakpoint, a watchpoint or the end of the program
+  reverse-step [n]      (rs) undo n instructions (default 1)
+  reverse-continue      (rc) run backward until a breakpoint, a watchpoint or the beginning
+  last-change <cell>    show the instruction that last changed the cell
+  break [line[:column]] (b) set a breakpoint at a source position, or list breakpoints
+  delete <n>            (d) delete the n-th breakpoint
+  markers on|off        stop at `#` characters in the source (default: on)
+  watch [cell]          (w) stop when the value of the cell changes, or list watchpoints
+  unwatch <cell>        delete a watchpoint
+  tape [radius]         (t) show cells around the pointer (default radius: 8)
+  set <cell> <value>    change the value of a cell
+  ptr <cell>            move the pointer
+  where                 (l) show the current position
+  save <file>           save the current state to a file
+  load <file>           restore a state saved with `save` (the history is discarded)
+  restart               run the program from the beginning
+  help                  (h) show this message
+  quit                  (q) exit the debugger";
+
+/// `input`はプログラムが`,`で読む入力
+pub fn run(source: &str, input: Vec<u8>) -> anyhow::Result<()> {
+    let program = parser::parse(source)?;
+    let instructions = compiler::vm::compile(&program);
+    let mut debugger = Debugger::new(source, &instructions, input);
+
+    println!(
+        "{} instructions. Type `help` for a list of commands.",
+        instructions.len()
+    );
+    debugger.show_position();
+
+    let stdin = std::io::stdin();
+    let mut line = String::new();
+    loop {
+        print!("(bf) ");
+        std::io::stdout().flush()?;
+
+        line.clear();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let args = line.split_whitespace().collect::<Vec<_>>();
+        if args.is_empty() {
+            continue;
+        }
+        match debugger.command(&args) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => println!("error: {}", e),
+        }
+    }
+}
+
+/// 実行履歴を記録し、`input`を先頭から読むVMを作る
+fn start<'a>(instructions: &'a [VMInstruction], input: &[u8]) -> VM<'a> {
+    let mut vm = VM::with_history(instructions);
+    vm.input = Input::bytes(input.to_vec());
+    vm
+}
+
+/// 実行を止めた理由
+enum Stop {
+    Finished,
+    /// 逆実行で記録の先頭に到達した
+    Beginning,
+    Steps,
+    Breakpoint(usize),
+    Marker,
+    Watchpoint {
+        cell: usize,
+        old: u8,
+        new: u8,
+    },
+    /// 次の命令がテープの外のセルに移動するか読み書きする
+    OutOfTape(isize),
+}
+
+struct Debugger<'a> {
+    source: &'a str,
+    /// 各命令に対応する文字のバイト位置
+    positions: Vec<usize>,
+    vm: VM<'a>,
+    /// プログラムの入力。最初から実行し直すときに先頭から読み直す
+    input: Vec<u8>,
+    /// ブレークポイントを設定した命令の位置
+    breakpoints: Vec<usize>,
+    /// `#`の直後にある命令の位置
+    markers: Vec<usize>,
+    use_markers: bool,
+    /// 監視しているセルと、最後に確認したときの値
+    watchpoints: Vec<(usize, u8)>,
+}
+
+impl<'a> Debugger<'a> {
+    fn new(source: &'a str, instructions: &'a [VMInstruction], input: Vec<u8>) -> Self {
+        let positions = parser::command_positions(source);
+        let markers = source
+            .char_indices()
+            .filter(|&(_, c)| c == '#')
+            .map(|(i, _)| positions.partition_point(|&position| position < i))
+            .filter(|&ip| ip < instructions.len())
+            .collect();
+        Self {
+            source,
+            positions,
+            vm: start(instructions, &input),
+            input,
+            breakpoints: Vec::new(),
+            markers,
+            use_markers: true,
+            watchpoints: Vec::new(),
+        }
+    }
+
+    /// コマンドを実行する。終了する場合は`false`を返す
+    fn command(&mut self, args: &[&str]) -> anyhow::Result<bool> {
+        match args[0] {
+            "step" | "s" => {
+                let mut remaining: usize = parse_arg(args.get(1), 1)?.max(1);
+                let stop = self.resume(|_| {
+                    remaining -= 1;
+                    remaining == 0
+                })?;
+                self.report(stop);
+            }
+            "next" | "n" => {
+                let stop = match self.vm.instructions().get(self.vm.instruction_pointer) {
+                    // ループやifの終わりまで実行する
+                    Some(&VMInstruction::JumpIfZero(end)) => {
+                        self.resume(|vm| vm.instruction_pointer == end + 1)?
+                    }
+                    _ => self.resume(|_| true)?,
+                };
+                self.report(stop);
+            }
+            "continue" | "c" => {
+                let stop = self.resume(|_| false)?;
+                self.report(stop);
+            }
+            "reverse-step" | "rs" => {
+                let mut remaining: usize = parse_arg(args.get(1), 1)?.max(1);
+                let stop = self.reverse(|_| {
+                    remaining -= 1;
+                    remaining == 0
+                })?;
+                self.report(stop);
+            }
+            "reverse-continue" | "rc" => {
+                let stop = self.reverse(|_| false)?;
+                self.report(stop);
+            }
+            "last-change" => {
+                let cell = self.cell(args.get(1))?;
+                match self.vm.last_change(cell) {
+                    Some((time, ip)) => println!(
+                        "cell {} last changed at step {} by {:?} at {}",
+                        cell,
+                        time,
+                        self.vm.instructions()[ip],
+                        self.location(ip)
+                    ),
+                    None => println!("cell {} has not changed", cell),
+                }
+            }
+            "break" | "b" => match args.get(1) {
+                Some(position) => {
+                    let ip = self.instruction_at(position)?;
+                    if !self.breakpoints.contains(&ip) {
+                        self.breakpoints.push(ip);
+                    }
+                    println!(
+                        "Breakpoint {} at {}",
+                        self.breakpoints.len(),
+                        self.location(ip)
+                    );
+                }
+                None => {
+                    for (i, &ip) in self.breakpoints.iter().enumerate() {
+                        println!("{}: {}", i + 1, self.location(ip));
+                    }
+                }
+            },
+            "delete" | "d" => {
+                let n: usize = parse_arg(args.get(1), 0)?;
+                if n == 0 || n > self.breakpoints.len() {
+                    bail!("no breakpoint number {}", n);
+                }
+                self.breakpoints.remove(n - 1);
+            }
+            "markers" => match args.get(1) {
+                Some(&"on") => self.use_markers = true,
+                Some(&"off") => self.use_markers = false,
+                _ => bail!("usage: markers on|off"),
+            },
+            "watch" | "w" => match args.get(1) {
+                Some(_) => {
+                    let cell = self.cell(args.get(1))?;
+                    if !self.watchpoints.iter().any(|&(watched, _)| watched == cell) {
+                        self.watchpoints.push((cell, self.vm.memory[cell]));
+                    }
+                }
+                None => {
+                    for &(cell, _) in &self.watchpoints {
+                        println!("cell {} = {}", cell, self.vm.memory[cell]);
+                    }
+                }
+            },
+            "unwatch" => {
+                let cell = self.cell(args.get(1))?;
+                self.watchpoints.retain(|&(watched, _)| watched != cell);
+            }
+            "tape" | "t" => self.show_tape(parse_arg(args.get(1), 8)?),
+            "set" => {
+                let cell = self.cell(args.get(1))?;
+                let value = parse_arg(args.get(2), 0)?;
+                self.vm.memory[cell] = value;
+                self.vm.reset_history();
+                self.update_watchpoints();
+            }
+            "ptr" => {
+                self.vm.pointer = self.cell(args.get(1))?;
+                self.vm.reset_history();
+            }
+            "where" | "l" => self.show_position(),
+            "save" => {
+                let path = args.get(1).context("file name is required")?;
+                let mut file = std::fs::File::create(path)
+                    .with_context(|| format!("failed to create {}", path))?;
+                Snapshot::capture(&self.vm).write(&mut file)?;
+                println!("Saved to {}", path);
+            }
+            "load" => {
+                let path = args.get(1).context("file name is required")?;
+                let file = std::fs::File::open(path)
+                    .with_context(|| format!("failed to open {}", path))?;
+                Snapshot::read(&mut std::io::BufReader::new(file))?.restore(&mut self.vm)?;
+                // 保存したときまでに読んだ分の入力を読み飛ばす
+                let mut input = Cursor::new(self.input.clone());
+                input.set_position(self.vm.input_position);
+                self.vm.input = Input::Bytes(input);
+                self.update_watchpoints();
+                self.show_position();
+            }
+            "restart" => {
+                self.vm = start(self.vm.instructions(), &self.input);
+                self.update_watchpoints();
+                self.show_position();
+            }
+            "help" | "h" => println!("{}", HELP),
+            "quit" | "q" => return Ok(false),
+            command => bail!(
+                "unknown command `{}`. Type `help` for a list of commands",
+                command
+            ),
+        }
+        Ok(true)
+    }
+
+    /// 1命令以上実行し、`done`が`true`を返すか、ブレークポイントなどに到達したら止まる
+    fn resume(&mut self, mut done: impl FnMut(&VM) -> bool) -> anyhow::Result<Stop> {
+        if self.vm.is_finished() {
+            bail!("the program is not running. Use `restart` to run it again");
+        }
+        loop {
+            let reach = self.vm.reach();
+            if reach < 0 || reach as usize >= self.vm.memory.len() {
+                return Ok(Stop::OutOfTape(reach));
+            }
+            self.vm.step();
+            if let Some(stop) = self.check_stop() {
+                return Ok(stop);
+            }
+            if done(&self.vm) {
+                return Ok(Stop::Steps);
+            }
+        }
+    }
+
+    /// 1命令以上巻き戻し、`done`が`true`を返すか、ブレークポイントなどに到達したら止まる
+    fn reverse(&mut self, mut done: impl FnMut(&VM) -> bool) -> anyhow::Result<Stop> {
+        if !self.vm.step_back() {
+            bail!("already at the beginning of the recorded history");
+        }
+        loop {
+            if let Some(stop) = self.check_stop() {
+                return Ok(stop);
+            }
+            if done(&self.vm) {
+                return Ok(Stop::Steps);
+            }
+            if !self.vm.step_back() {
+                return Ok(Stop::Beginning);
+            }
+        }
+    }
+
+    /// 現在の状態で止まるべきかを調べる
+    fn check_stop(&mut self) -> Option<Stop> {
+        for (cell, last) in &mut self.watchpoints {
+            let value = self.vm.memory[*cell];
+            if value != *last {
+                let stop = Stop::Watchpoint {
+                    cell: *cell,
+                    old: *last,
+                    new: value,
+                };
+                *last = value;
+                return Some(stop);
+            }
+        }
+
+        let ip = self.vm.instruction_pointer;
+        if self.vm.is_finished() {
+            return Some(Stop::Finished);
+        }
+        if let Some(i) = self.breakpoints.iter().position(|&bp| bp == ip) {
+            return Some(Stop::Breakpoint(i + 1));
+        }
+        if self.use_markers && self.markers.contains(&ip) {
+            return Some(Stop::Marker);
+        }
+        None
+    }
+
+    fn report(&self, stop: Stop) {
+        match stop {
+            Stop::Finished => {
+                println!();
+                println!("Program finished.");
+                return;
+            }
+            Stop::Beginning => println!("Reached the beginning of the recorded history."),
+            Stop::Steps => {}
+            Stop::Breakpoint(n) => println!("Breakpoint {}", n),
+            Stop::Marker => println!("Marker"),
+            Stop::Watchpoint { cell, old, new } => {
+                println!("Watchpoint: cell {} changed from {} to {}", cell, old, new)
+            }
+            Stop::OutOfTape(cell) => println!(
+                "error: the next instruction would access cell {}, which is out of the tape (0..{})",
+                cell,
+                self.vm.memory.len()
+            ),
+        }
+        self.show_position();
+    }
+
+    fn update_watchpoints(&mut self) {
+        for (cell, last) in &mut self.watchpoints {
+            *last = self.vm.memory[*cell];
+        }
+    }
+
+    fn show_position(&self) {
+        let ip = self.vm.instruction_pointer;
+        if self.vm.is_finished() {
+            println!("Program finished.");
+            return;
+        }
+        // `ptr`で動かした直後などは、ポインタがテープの外を指していることがある
+        let value = match self.vm.memory.get(self.vm.pointer) {
+            Some(value) => value.to_string(),
+            None => "out of the tape".to_string(),
+        };
+        println!(
+            "step {}, ip {} at {}: {:?}, ptr: {}, value: {}",
+            self.vm.time(),
+            ip,
+            self.location(ip),
+            self.vm.instructions()[ip],
+            self.vm.pointer,
+            value
+        );
+        if let Some(&position) = self.positions.get(ip) {
+            let line_start = self.source[..position].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = self.source[position..]
+                .find('\n')
+                .map_or(self.source.len(), |i| position + i);
+            let line = &self.source[line_start..line_end];
+            let column = self.source[line_start..position].chars().count();
+            println!("  {}", line);
+            println!("  {}^", " ".repeat(column));
+        }
+    }
+
+    fn show_tape(&self, radius: usize) {
+        let end =
+            (self.vm.pointer.saturating_add(radius).saturating_add(1)).min(self.vm.memory.len());
+        let start = self.vm.pointer.saturating_sub(radius).min(end);
+        let mut cells = String::new();
+        let mut values = String::new();
+        for cell in start..end {
+            let (open, close) = if cell == self.vm.pointer {
+                ('[', ']')
+            } else {
+                (' ', ' ')
+            };
+            cells.push_str(&format!("{}{:>5}{}", open, cell, close));
+            values.push_str(&format!("{}{:>5}{}", open, self.vm.memory[cell], close));
+        }
+        println!("cell {}", cells);
+        println!("value{}", values);
+    }
+
+    /// 命令の位置を`行:列`で表す
+    fn location(&self, ip: usize) -> String {
+        match self.positions.get(ip) {
+            Some(&position) => {
+                let (line, column) = parser::line_column(self.source, position);
+                format!("{}:{}", line, column)
+            }
+            None => "end of program".to_string(),
+        }
+    }
+
+    /// `行[:列]`の位置以降にある最初の命令
+    fn instruction_at(&self, position: &str) -> anyhow::Result<usize> {
+        let (line, column) = match position.split_once(':') {
+            Some((line, column)) => (line.parse::<usize>()?, column.parse::<usize>()?),
+            None => (position.parse::<usize>()?, 1),
+        };
+        let line_start = if line <= 1 {
+            0
+        } else {
+            self.source
+                .match_indices('\n')
+                .nth(line - 2)
+                .map(|(i, _)| i + 1)
+                .with_context(|| format!("line {} does not exist", line))?
+        };
+        let offset = self.source[line_start..]
+            .char_indices()
+            .nth(column.saturating_sub(1))
+            .map_or(self.source.len(), |(i, _)| line_start + i);
+        let ip = self.positions.partition_point(|&p| p < offset);
+        if ip >= self.vm.instructions().len() {
+            bail!("no instruction at or after {}", position);
+        }
+        Ok(ip)
+    }
+
+    fn cell(&self, arg: Option<&&str>) -> anyhow::Result<usize> {
+        let cell = arg.context("cell number is required")?.parse::<usize>()?;
+        if cell >= self.vm.memory.len() {
+            bail!("cell {} is out of the tape", cell);
+        }
+        Ok(cell)
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: Option<&&str>, default: T) -> anyhow::Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match arg {
+        Some(arg) => Ok(arg.parse()?),
+        None => Ok(default),
+    }
+}
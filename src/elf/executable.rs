@@ -0,0 +1,206 @@
+//! libcに依存しない静的リンクされた実行ファイルの出力
+
+use crate::{
+    assembler::x86_64::{
+        addressing_mode::{AddressingMode, AddressingScale},
+        Assembler, QwordRegister,
+    },
+    compiler::x86_64::{IoFunction, RelocatableCode},
+};
+
+use super::*;
+
+const BASE_ADDRESS: u64 = 0x400000;
+const PAGE_SIZE: usize = 0x1000;
+const TAPE_SIZE: usize = 30000;
+
+const SYS_READ: u32 = 0;
+const SYS_WRITE: u32 = 1;
+const SYS_EXIT: u32 = 60;
+
+/// `_start`と入出力関数
+struct Runtime {
+    code: Vec<u8>,
+    /// `_start`の中にある、テープのアドレスを設定する`mov r64, imm64`のオペランドの位置
+    tape_address: usize,
+    /// `_start`の中にある、Brainf*ckのコードを呼び出す`call rel32`の位置
+    call_main: usize,
+    putchar: usize,
+    getchar: usize,
+}
+
+/// `compiler::x86_64::compile_relocatable`の出力を実行ファイルにする
+pub fn build(compiled: &RelocatableCode) -> Vec<u8> {
+    let text_offset = ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE * 2;
+    let text_address = BASE_ADDRESS + text_offset as u64;
+
+    let Runtime {
+        code: runtime,
+        tape_address,
+        call_main,
+        putchar,
+        getchar,
+    } = assemble_runtime();
+
+    let main = runtime.len();
+    let mut text = runtime;
+    text.extend(&compiled.code);
+
+    let mut set_rel32 = |addr: usize, target: usize| {
+        let rel32 = target as i32 - (addr + 4) as i32;
+        text[addr..addr + 4].copy_from_slice(&rel32.to_le_bytes());
+    };
+    set_rel32(call_main + 1, main);
+    for relocation in &compiled.relocations {
+        let target = match relocation.target {
+            IoFunction::PutChar => putchar,
+            IoFunction::GetChar => getchar,
+        };
+        set_rel32(main + relocation.offset, target);
+    }
+
+    let text_end = text_offset + text.len();
+    let bss_address = BASE_ADDRESS + align_up(text_end, PAGE_SIZE) as u64;
+    text[tape_address..tape_address + 8].copy_from_slice(&bss_address.to_le_bytes());
+
+    let mut section_names = StringTable::new();
+    let text_name = section_names.add(".text");
+    let bss_name = section_names.add(".bss");
+    let shstrtab_name = section_names.add(".shstrtab");
+
+    let shstrtab_offset = text_end;
+    let section_header_offset = align_up(shstrtab_offset + section_names.bytes.len(), 8);
+
+    let mut out = Vec::new();
+    ElfHeader {
+        e_type: ET_EXEC,
+        entry: text_address,
+        program_header_offset: ELF_HEADER_SIZE as u64,
+        program_header_count: 2,
+        section_header_offset: section_header_offset as u64,
+        section_header_count: 4,
+        section_name_index: 3,
+    }
+    .write(&mut out);
+
+    ProgramHeader {
+        p_type: PT_LOAD,
+        flags: PF_R | PF_X,
+        offset: 0,
+        vaddr: BASE_ADDRESS,
+        file_size: text_end as u64,
+        memory_size: text_end as u64,
+        align: PAGE_SIZE as u64,
+    }
+    .write(&mut out);
+    ProgramHeader {
+        p_type: PT_LOAD,
+        flags: PF_R | PF_W,
+        offset: 0,
+        vaddr: bss_address,
+        file_size: 0,
+        memory_size: TAPE_SIZE as u64,
+        align: PAGE_SIZE as u64,
+    }
+    .write(&mut out);
+
+    out.extend(&text);
+    out.extend(&section_names.bytes);
+    pad_to(&mut out, section_header_offset);
+
+    SectionHeader::default().write(&mut out);
+    SectionHeader {
+        name: text_name,
+        sh_type: SHT_PROGBITS,
+        flags: SHF_ALLOC | SHF_EXECINSTR,
+        addr: text_address,
+        offset: text_offset as u64,
+        size: text.len() as u64,
+        align: 1,
+        ..Default::default()
+    }
+    .write(&mut out);
+    SectionHeader {
+        name: bss_name,
+        sh_type: SHT_NOBITS,
+        flags: SHF_ALLOC | SHF_WRITE,
+        addr: bss_address,
+        offset: text_end as u64,
+        size: TAPE_SIZE as u64,
+        align: PAGE_SIZE as u64,
+        ..Default::default()
+    }
+    .write(&mut out);
+    SectionHeader {
+        name: shstrtab_name,
+        sh_type: SHT_STRTAB,
+        offset: shstrtab_offset as u64,
+        size: section_names.bytes.len() as u64,
+        align: 1,
+        ..Default::default()
+    }
+    .write(&mut out);
+
+    out
+}
+
+fn assemble_runtime() -> Runtime {
+    let mut assembler = Assembler::new();
+
+    // テープのアドレスはテキストの長さが決まるまでわからないので後で書き換える
+    let tape_address = assembler.code.len() + 2;
+    assembler.mov_r64_imm64(QwordRegister::Rdi, 0);
+    assembler.mov_r32_imm32(QwordRegister::Rsi, 0);
+    let call_main = assembler.code.len();
+    assembler.call_rel32(0);
+    assembler.mov_r32_imm32(QwordRegister::Rax, SYS_EXIT);
+    assembler.mov_r32_imm32(QwordRegister::Rdi, 0);
+    assembler.syscall();
+
+    // putchar: 第2引数の下位1バイトを標準出力に書き込む。第1引数のコンテキストは使わない
+    let putchar = assembler.code.len();
+    assembler.push_r64(QwordRegister::Rsi);
+    assembler.mov_r32_imm32(QwordRegister::Rax, SYS_WRITE);
+    assembler.mov_r32_imm32(QwordRegister::Rdi, 1);
+    assembler.mov_rm64_r64(
+        AddressingMode::Register {
+            reg: QwordRegister::Rsi,
+        },
+        QwordRegister::Rsp,
+    );
+    assembler.mov_r32_imm32(QwordRegister::Rdx, 1);
+    assembler.syscall();
+    assembler.pop_r64(QwordRegister::Rsi);
+    assembler.ret();
+
+    // getchar: 標準入力から1バイト読み込む。EOFの場合はVMと同じく0になる
+    let getchar = assembler.code.len();
+    let top_of_stack = AddressingMode::IndirectScaled {
+        base: QwordRegister::Rsp,
+        index: None,
+        scale: AddressingScale::Scale1,
+    };
+    assembler.push_r64(QwordRegister::Rax);
+    assembler.mov_rm8_imm8(top_of_stack, 0);
+    assembler.mov_r32_imm32(QwordRegister::Rax, SYS_READ);
+    assembler.mov_r32_imm32(QwordRegister::Rdi, 0);
+    assembler.mov_rm64_r64(
+        AddressingMode::Register {
+            reg: QwordRegister::Rsi,
+        },
+        QwordRegister::Rsp,
+    );
+    assembler.mov_r32_imm32(QwordRegister::Rdx, 1);
+    assembler.syscall();
+    assembler.movzx_r32_rm8(QwordRegister::Rax, top_of_stack);
+    assembler.pop_r64(QwordRegister::Rcx);
+    assembler.ret();
+
+    Runtime {
+        code: assembler.code,
+        tape_address,
+        call_main,
+        putchar,
+        getchar,
+    }
+}
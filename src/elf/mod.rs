@@ -0,0 +1,198 @@
+//! ELF64ファイルの出力
+
+pub mod executable;
+pub mod object;
+
+pub const ET_REL: u16 = 1;
+pub const ET_EXEC: u16 = 2;
+pub const EM_X86_64: u16 = 62;
+
+pub const PT_LOAD: u32 = 1;
+
+pub const PF_X: u32 = 1;
+pub const PF_W: u32 = 2;
+pub const PF_R: u32 = 4;
+
+pub const SHT_PROGBITS: u32 = 1;
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_STRTAB: u32 = 3;
+pub const SHT_RELA: u32 = 4;
+pub const SHT_NOBITS: u32 = 8;
+
+pub const SHF_WRITE: u64 = 1;
+pub const SHF_ALLOC: u64 = 2;
+pub const SHF_EXECINSTR: u64 = 4;
+pub const SHF_INFO_LINK: u64 = 0x40;
+
+pub const STB_LOCAL: u8 = 0;
+pub const STB_GLOBAL: u8 = 1;
+
+pub const STT_NOTYPE: u8 = 0;
+pub const STT_FUNC: u8 = 2;
+pub const STT_SECTION: u8 = 3;
+
+pub const SHN_UNDEF: u16 = 0;
+
+pub const R_X86_64_PLT32: u32 = 4;
+
+pub const ELF_HEADER_SIZE: usize = 64;
+pub const PROGRAM_HEADER_SIZE: usize = 56;
+pub const SECTION_HEADER_SIZE: usize = 64;
+pub const SYMBOL_SIZE: usize = 24;
+pub const RELA_SIZE: usize = 24;
+
+pub struct ElfHeader {
+    pub e_type: u16,
+    pub entry: u64,
+    pub program_header_offset: u64,
+    pub program_header_count: u16,
+    pub section_header_offset: u64,
+    pub section_header_count: u16,
+    pub section_name_index: u16,
+}
+
+impl ElfHeader {
+    pub fn write(&self, out: &mut Vec<u8>) {
+        // e_ident: マジックナンバー, ELFCLASS64, ELFDATA2LSB, EV_CURRENT, ELFOSABI_NONE
+        out.extend([0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+        out.extend([0; 8]);
+        out.extend(self.e_type.to_le_bytes());
+        out.extend(EM_X86_64.to_le_bytes());
+        out.extend(1u32.to_le_bytes()); // e_version
+        out.extend(self.entry.to_le_bytes());
+        out.extend(self.program_header_offset.to_le_bytes());
+        out.extend(self.section_header_offset.to_le_bytes());
+        out.extend(0u32.to_le_bytes()); // e_flags
+        out.extend((ELF_HEADER_SIZE as u16).to_le_bytes());
+        let program_header_size = if self.program_header_count == 0 {
+            0
+        } else {
+            PROGRAM_HEADER_SIZE as u16
+        };
+        out.extend(program_header_size.to_le_bytes());
+        out.extend(self.program_header_count.to_le_bytes());
+        out.extend((SECTION_HEADER_SIZE as u16).to_le_bytes());
+        out.extend(self.section_header_count.to_le_bytes());
+        out.extend(self.section_name_index.to_le_bytes());
+    }
+}
+
+pub struct ProgramHeader {
+    pub p_type: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub file_size: u64,
+    pub memory_size: u64,
+    pub align: u64,
+}
+
+impl ProgramHeader {
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.p_type.to_le_bytes());
+        out.extend(self.flags.to_le_bytes());
+        out.extend(self.offset.to_le_bytes());
+        out.extend(self.vaddr.to_le_bytes());
+        out.extend(self.vaddr.to_le_bytes()); // p_paddr
+        out.extend(self.file_size.to_le_bytes());
+        out.extend(self.memory_size.to_le_bytes());
+        out.extend(self.align.to_le_bytes());
+    }
+}
+
+#[derive(Default)]
+pub struct SectionHeader {
+    pub name: u32,
+    pub sh_type: u32,
+    pub flags: u64,
+    pub addr: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+    pub info: u32,
+    pub align: u64,
+    pub entry_size: u64,
+}
+
+impl SectionHeader {
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.name.to_le_bytes());
+        out.extend(self.sh_type.to_le_bytes());
+        out.extend(self.flags.to_le_bytes());
+        out.extend(self.addr.to_le_bytes());
+        out.extend(self.offset.to_le_bytes());
+        out.extend(self.size.to_le_bytes());
+        out.extend(self.link.to_le_bytes());
+        out.extend(self.info.to_le_bytes());
+        out.extend(self.align.to_le_bytes());
+        out.extend(self.entry_size.to_le_bytes());
+    }
+}
+
+pub struct Symbol {
+    pub name: u32,
+    pub bind: u8,
+    pub symbol_type: u8,
+    pub section_index: u16,
+    pub value: u64,
+    pub size: u64,
+}
+
+impl Symbol {
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.name.to_le_bytes());
+        out.push((self.bind << 4) | self.symbol_type);
+        out.push(0); // st_other: STV_DEFAULT
+        out.extend(self.section_index.to_le_bytes());
+        out.extend(self.value.to_le_bytes());
+        out.extend(self.size.to_le_bytes());
+    }
+}
+
+pub struct Rela {
+    pub offset: u64,
+    pub symbol: u32,
+    pub r_type: u32,
+    pub addend: i64,
+}
+
+impl Rela {
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.offset.to_le_bytes());
+        out.extend(((self.symbol as u64) << 32 | self.r_type as u64).to_le_bytes());
+        out.extend(self.addend.to_le_bytes());
+    }
+}
+
+/// `.shstrtab`や`.strtab`の中身を組み立てる
+pub struct StringTable {
+    pub bytes: Vec<u8>,
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        Self { bytes: vec![0] }
+    }
+
+    /// 文字列を追加し、そのオフセットを返す
+    pub fn add(&mut self, s: &str) -> u32 {
+        let offset = self.bytes.len() as u32;
+        self.bytes.extend(s.as_bytes());
+        self.bytes.push(0);
+        offset
+    }
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn align_up(value: usize, align: usize) -> usize {
+    value.div_ceil(align) * align
+}
+
+pub fn pad_to(out: &mut Vec<u8>, len: usize) {
+    out.resize(len, 0);
+}
@@ -0,0 +1,178 @@
+//! C言語などのプログラムとリンクできる再配置可能オブジェクトファイルの出力
+
+use crate::compiler::x86_64::{IoFunction, RelocatableCode};
+
+use super::*;
+
+/// 入出力関数のシンボル名。`void bf_putchar(void *ctx, int c)`と`int bf_getchar(void *ctx)`
+pub const PUTCHAR_SYMBOL: &str = "bf_putchar";
+pub const GETCHAR_SYMBOL: &str = "bf_getchar";
+
+/// `compiler::x86_64::compile_relocatable`の出力を、`void <symbol>(uint8_t *tape, void *ctx)`を
+/// エクスポートするオブジェクトファイルにする
+pub fn build(compiled: &RelocatableCode, symbol: &str) -> Vec<u8> {
+    let mut section_names = StringTable::new();
+    let text_name = section_names.add(".text");
+    let rela_text_name = section_names.add(".rela.text");
+    let symtab_name = section_names.add(".symtab");
+    let strtab_name = section_names.add(".strtab");
+    let note_gnu_stack_name = section_names.add(".note.GNU-stack");
+    let shstrtab_name = section_names.add(".shstrtab");
+
+    // シンボルテーブル: null, .text, <symbol>, bf_putchar, bf_getchar
+    let mut symbol_names = StringTable::new();
+    let mut symtab = Vec::new();
+    for symbol in [
+        Symbol {
+            name: 0,
+            bind: STB_LOCAL,
+            symbol_type: STT_NOTYPE,
+            section_index: SHN_UNDEF,
+            value: 0,
+            size: 0,
+        },
+        Symbol {
+            name: 0,
+            bind: STB_LOCAL,
+            symbol_type: STT_SECTION,
+            section_index: 1,
+            value: 0,
+            size: 0,
+        },
+        Symbol {
+            name: symbol_names.add(symbol),
+            bind: STB_GLOBAL,
+            symbol_type: STT_FUNC,
+            section_index: 1,
+            value: 0,
+            size: compiled.code.len() as u64,
+        },
+        Symbol {
+            name: symbol_names.add(PUTCHAR_SYMBOL),
+            bind: STB_GLOBAL,
+            symbol_type: STT_NOTYPE,
+            section_index: SHN_UNDEF,
+            value: 0,
+            size: 0,
+        },
+        Symbol {
+            name: symbol_names.add(GETCHAR_SYMBOL),
+            bind: STB_GLOBAL,
+            symbol_type: STT_NOTYPE,
+            section_index: SHN_UNDEF,
+            value: 0,
+            size: 0,
+        },
+    ] {
+        symbol.write(&mut symtab);
+    }
+    let first_global_symbol = 2;
+
+    let mut rela_text = Vec::new();
+    for relocation in &compiled.relocations {
+        Rela {
+            offset: relocation.offset as u64,
+            symbol: match relocation.target {
+                IoFunction::PutChar => 3,
+                IoFunction::GetChar => 4,
+            },
+            r_type: R_X86_64_PLT32,
+            // rel32は次の命令のアドレスからの相対位置
+            addend: -4,
+        }
+        .write(&mut rela_text);
+    }
+
+    let text_offset = ELF_HEADER_SIZE;
+    let rela_text_offset = align_up(text_offset + compiled.code.len(), 8);
+    let symtab_offset = rela_text_offset + rela_text.len();
+    let strtab_offset = symtab_offset + symtab.len();
+    let shstrtab_offset = strtab_offset + symbol_names.bytes.len();
+    let section_header_offset = align_up(shstrtab_offset + section_names.bytes.len(), 8);
+
+    let mut out = Vec::new();
+    ElfHeader {
+        e_type: ET_REL,
+        entry: 0,
+        program_header_offset: 0,
+        program_header_count: 0,
+        section_header_offset: section_header_offset as u64,
+        section_header_count: 7,
+        section_name_index: 6,
+    }
+    .write(&mut out);
+
+    out.extend(&compiled.code);
+    pad_to(&mut out, rela_text_offset);
+    out.extend(&rela_text);
+    out.extend(&symtab);
+    out.extend(&symbol_names.bytes);
+    out.extend(&section_names.bytes);
+    pad_to(&mut out, section_header_offset);
+
+    SectionHeader::default().write(&mut out);
+    SectionHeader {
+        name: text_name,
+        sh_type: SHT_PROGBITS,
+        flags: SHF_ALLOC | SHF_EXECINSTR,
+        offset: text_offset as u64,
+        size: compiled.code.len() as u64,
+        align: 16,
+        ..Default::default()
+    }
+    .write(&mut out);
+    SectionHeader {
+        name: rela_text_name,
+        sh_type: SHT_RELA,
+        flags: SHF_INFO_LINK,
+        offset: rela_text_offset as u64,
+        size: rela_text.len() as u64,
+        link: 3,
+        info: 1,
+        align: 8,
+        entry_size: RELA_SIZE as u64,
+        ..Default::default()
+    }
+    .write(&mut out);
+    SectionHeader {
+        name: symtab_name,
+        sh_type: SHT_SYMTAB,
+        offset: symtab_offset as u64,
+        size: symtab.len() as u64,
+        link: 4,
+        info: first_global_symbol,
+        align: 8,
+        entry_size: SYMBOL_SIZE as u64,
+        ..Default::default()
+    }
+    .write(&mut out);
+    SectionHeader {
+        name: strtab_name,
+        sh_type: SHT_STRTAB,
+        offset: strtab_offset as u64,
+        size: symbol_names.bytes.len() as u64,
+        align: 1,
+        ..Default::default()
+    }
+    .write(&mut out);
+    // スタックを実行可能にする必要がないことをリンカに伝える
+    SectionHeader {
+        name: note_gnu_stack_name,
+        sh_type: SHT_PROGBITS,
+        offset: shstrtab_offset as u64,
+        align: 1,
+        ..Default::default()
+    }
+    .write(&mut out);
+    SectionHeader {
+        name: shstrtab_name,
+        sh_type: SHT_STRTAB,
+        offset: shstrtab_offset as u64,
+        size: section_names.bytes.len() as u64,
+        align: 1,
+        ..Default::default()
+    }
+    .write(&mut out);
+
+    out
+}
@@ -0,0 +1,317 @@
+//! ライブラリとして使うための高水準のAPI
+//!
+//! `Engine::builder()`で最適化とバックエンド、テープの設定を決め、`Engine::compile`でプログラムを
+//! `CompiledProgram`にする。`CompiledProgram::run`は与えた入出力で何度でも実行でき、
+//! パニックする代わりにエラーを返す
+
+use std::{
+    cell::Cell,
+    fmt,
+    io::{Read, Write},
+};
+
+use anyhow::bail;
+
+use crate::{
+    compiler::{
+        self,
+        x86_64::{Bounds, CompileOptions},
+    },
+    optimizer::{self, Optimization},
+    parser,
+    runtime::{
+        native::{io_getchar, io_putchar, read_byte, NativeFunction, NativeIo},
+        vm::{VMInstruction, VM},
+    },
+    tape::{CellWidth, Eof, TapeOptions},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// プログラムを実行する方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// ポインタがテープの範囲内にあるかを命令ごとに確かめる
+    #[default]
+    Vm,
+    /// x86_64のネイティブコード。ポインタを動かすたびにテープの範囲内にあるかを確かめるコードを埋め込む
+    Native,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EngineBuilder {
+    optimizations: Vec<Optimization>,
+    backend: Backend,
+    tape: TapeOptions,
+}
+
+impl EngineBuilder {
+    pub fn optimizations(mut self, optimizations: &[Optimization]) -> Self {
+        self.optimizations = optimizations.to_vec();
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// テープのセルの数
+    pub fn tape(mut self, size: usize) -> Self {
+        self.tape.size = size;
+        self
+    }
+
+    pub fn cell_width(mut self, cell_width: CellWidth) -> Self {
+        self.tape.cell_width = cell_width;
+        self
+    }
+
+    pub fn eof(mut self, eof: Eof) -> Self {
+        self.tape.eof = eof;
+        self
+    }
+
+    /// バックエンドが対応していない設定はここでエラーになる
+    pub fn build(self) -> anyhow::Result<Engine> {
+        if self.tape.size == 0 {
+            bail!("the tape must have at least one cell");
+        }
+        if self.tape.cell_width != CellWidth::U8 {
+            bail!(
+                "{}-bit cells are not supported, only 8-bit cells can be run",
+                self.tape.cell_width.bits()
+            );
+        }
+        // 入出力関数には現在のセルが渡らないので、値を変えないことはできない
+        if self.backend == Backend::Native && self.tape.eof == Eof::Unchanged {
+            bail!("the native backend cannot leave the cell unchanged at the end of input");
+        }
+        Ok(Engine {
+            optimizations: self.optimizations,
+            backend: self.backend,
+            tape: self.tape,
+        })
+    }
+}
+
+/// プログラムをコンパイルする設定
+#[derive(Debug, Clone)]
+pub struct Engine {
+    optimizations: Vec<Optimization>,
+    backend: Backend,
+    tape: TapeOptions,
+}
+
+impl Engine {
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+
+    /// ソースコードを解析し、最適化してバックエンドの形式にする
+    pub fn compile(&self, source: &str) -> anyhow::Result<CompiledProgram> {
+        let instructions = optimizer::optimize(parser::parse(source)?, &self.optimizations);
+        let code = match self.backend {
+            Backend::Vm => Code::Vm(compiler::vm::compile(&instructions)),
+            Backend::Native => {
+                // 生成したコードがアドレスを埋め込むので、実行するたびに中身だけを書き換える
+                let bounds = Box::new(Cell::new(Bounds {
+                    start: std::ptr::null_mut(),
+                    len: 0,
+                    reach: 0,
+                }));
+                let code = compiler::x86_64::compile(
+                    &instructions,
+                    CompileOptions {
+                        putchar: Some(io_putchar),
+                        getchar: Some(io_getchar),
+                        bounds: Some(bounds.as_ptr()),
+                        ..Default::default()
+                    },
+                );
+                Code::Native {
+                    function: NativeFunction::new(&code),
+                    bounds,
+                }
+            }
+        };
+        Ok(CompiledProgram {
+            tape: self.tape,
+            code,
+        })
+    }
+}
+
+enum Code {
+    Vm(Vec<VMInstruction>),
+    Native {
+        function: NativeFunction,
+        bounds: Box<Cell<Bounds>>,
+    },
+}
+
+/// コンパイルしたプログラム。実行するたびに新しいテープを使う
+pub struct CompiledProgram {
+    tape: TapeOptions,
+    code: Code,
+}
+
+/// 1回の実行の統計
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunStats {
+    /// 実行したVMの命令の数。ネイティブコードでは数えないので`None`
+    pub steps: Option<u64>,
+    /// 読んだ入力のバイト数。入力の終わりに達した後の`,`は数えない
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    /// ポインタがテープの範囲外に出ようとした
+    OutOfTape {
+        /// 移動しようとした先か、読み書きしようとしたセル
+        pointer: isize,
+        /// VMの命令の位置。ネイティブコードでは不明
+        instruction_pointer: Option<usize>,
+        stats: RunStats,
+    },
+    /// 入力の読み込みか出力の書き込みに失敗した
+    Io(std::io::Error),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::OutOfTape {
+                pointer,
+                instruction_pointer,
+                ..
+            } => {
+                write!(f, "pointer moved out of the tape to cell {}", pointer)?;
+                if let Some(ip) = instruction_pointer {
+                    write!(f, " (ip: {})", ip)?;
+                }
+                Ok(())
+            }
+            RunError::Io(error) => write!(f, "I/O error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for RunError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RunError::OutOfTape { .. } => None,
+            RunError::Io(error) => Some(error),
+        }
+    }
+}
+
+impl From<std::io::Error> for RunError {
+    fn from(error: std::io::Error) -> Self {
+        RunError::Io(error)
+    }
+}
+
+impl CompiledProgram {
+    /// `input`から読み、`output`に書いて実行する。終わったら`output`をフラッシュする
+    pub fn run(&self, mut input: impl Read, mut output: impl Write) -> Result<RunStats, RunError> {
+        let stats = match &self.code {
+            Code::Vm(instructions) => self.run_vm(instructions, &mut input, &mut output)?,
+            Code::Native { function, bounds } => {
+                self.run_native(function, bounds, &mut input, &mut output)?
+            }
+        };
+        output.flush()?;
+        Ok(stats)
+    }
+
+    fn run_vm(
+        &self,
+        instructions: &[VMInstruction],
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> Result<RunStats, RunError> {
+        let mut vm = VM::new(instructions);
+        vm.memory = vec![0; self.tape.size];
+        let mut stats = RunStats::default();
+        let mut steps = 0;
+        while !vm.is_finished() {
+            let reach = vm.reach();
+            if reach < 0 || reach as usize >= vm.memory.len() {
+                stats.steps = Some(steps);
+                return Err(RunError::OutOfTape {
+                    pointer: reach,
+                    instruction_pointer: Some(vm.instruction_pointer),
+                    stats,
+                });
+            }
+            // VMは標準入出力を使うので、入出力の命令はここで実行する
+            match instructions[vm.instruction_pointer] {
+                VMInstruction::PutChar => {
+                    output.write_all(&[vm.memory[vm.pointer]])?;
+                    stats.output_bytes += 1;
+                    vm.output_position += 1;
+                    vm.instruction_pointer += 1;
+                }
+                VMInstruction::GetChar => {
+                    match read_byte(input)? {
+                        Some(value) => {
+                            vm.memory[vm.pointer] = value;
+                            stats.input_bytes += 1;
+                        }
+                        None => match self.tape.eof {
+                            Eof::Zero => vm.memory[vm.pointer] = 0,
+                            Eof::MinusOne => vm.memory[vm.pointer] = u8::MAX,
+                            Eof::Unchanged => {}
+                        },
+                    }
+                    vm.input_position += 1;
+                    vm.instruction_pointer += 1;
+                }
+                _ => vm.step(),
+            }
+            steps += 1;
+        }
+        stats.steps = Some(steps);
+        Ok(stats)
+    }
+
+    fn run_native(
+        &self,
+        function: &NativeFunction,
+        bounds: &Cell<Bounds>,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> Result<RunStats, RunError> {
+        let mut memory = vec![0u8; self.tape.size];
+        let mut io = NativeIo::new(input, output, self.tape.eof);
+        // 入出力関数の中で同じプログラムを実行しても戻せるように、前の値を残しておく
+        let previous_bounds = bounds.replace(Bounds {
+            start: memory.as_mut_ptr(),
+            len: memory.len() as u64,
+            reach: 0,
+        });
+        // 生成したコードはポインタがテープの範囲外に出る前と、入出力が失敗したときに中断する
+        let result = io.attach(|| unsafe { function.call(memory.as_mut_ptr()) });
+        let reach = bounds.replace(previous_bounds).reach;
+        if let Some(error) = io.error {
+            return Err(RunError::Io(error));
+        }
+        let stats = RunStats {
+            steps: None,
+            input_bytes: io.input_bytes,
+            output_bytes: io.output_bytes,
+        };
+        if result == 2 {
+            return Err(RunError::OutOfTape {
+                pointer: reach as isize,
+                instruction_pointer: None,
+                stats,
+            });
+        }
+        Ok(stats)
+    }
+}
@@ -0,0 +1,142 @@
+use super::*;
+
+const CAT: &str = ",[.,]";
+
+fn run(engine: &Engine, source: &str, input: &[u8]) -> (Vec<u8>, RunStats) {
+    let program = engine.compile(source).unwrap();
+    let mut output = Vec::new();
+    let stats = program.run(input, &mut output).unwrap();
+    (output, stats)
+}
+
+#[test]
+fn run_on_every_backend() {
+    for backend in [Backend::Vm, Backend::Native] {
+        for optimizations in [&[][..], &[Optimization::All]] {
+            let engine = Engine::builder()
+                .backend(backend)
+                .optimizations(optimizations)
+                .build()
+                .unwrap();
+            let (output, stats) = run(&engine, CAT, b"hello");
+            assert_eq!(output, b"hello", "{:?}", backend);
+            assert_eq!(stats.input_bytes, 5);
+            assert_eq!(stats.output_bytes, 5);
+            assert_eq!(stats.steps.is_some(), backend == Backend::Vm);
+        }
+    }
+}
+
+#[test]
+fn run_repeatedly() {
+    for backend in [Backend::Vm, Backend::Native] {
+        let program = Engine::builder()
+            .backend(backend)
+            .build()
+            .unwrap()
+            .compile("+++[>+<-]>.,.")
+            .unwrap();
+        for input in [&b"a"[..], b"b"] {
+            let mut output = Vec::new();
+            program.run(input, &mut output).unwrap();
+            // テープは実行ごとに新しくなる
+            assert_eq!(output, [3, input[0]]);
+        }
+    }
+}
+
+#[test]
+fn end_of_input() {
+    for (backend, eof, expected) in [
+        (Backend::Vm, Eof::Zero, 0),
+        (Backend::Vm, Eof::MinusOne, 255),
+        (Backend::Vm, Eof::Unchanged, 5),
+        (Backend::Native, Eof::Zero, 0),
+        (Backend::Native, Eof::MinusOne, 255),
+    ] {
+        let engine = Engine::builder().backend(backend).eof(eof).build().unwrap();
+        let (output, stats) = run(&engine, "+++++,.", b"");
+        assert_eq!(output, [expected], "{:?} {:?}", backend, eof);
+        assert_eq!(stats.input_bytes, 0);
+    }
+}
+
+#[test]
+fn pointer_out_of_tape() {
+    let engine = Engine::builder().tape(4).build().unwrap();
+    let program = engine.compile("+>>>>.").unwrap();
+    match program.run(&b""[..], Vec::new()) {
+        Err(RunError::OutOfTape {
+            pointer,
+            instruction_pointer,
+            stats,
+        }) => {
+            assert_eq!(pointer, 4);
+            assert_eq!(instruction_pointer, Some(4));
+            assert_eq!(stats.steps, Some(4));
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    for backend in [Backend::Vm, Backend::Native] {
+        for optimizations in [&[][..], &[Optimization::All]] {
+            let engine = Engine::builder()
+                .backend(backend)
+                .optimizations(optimizations)
+                .tape(100)
+                .build()
+                .unwrap();
+            for (source, expected) in [("+[>+]", 100), ("<", -1), (">>[-]<<<", -1)] {
+                let program = engine.compile(source).unwrap();
+                match program.run(&b""[..], Vec::new()) {
+                    Err(RunError::OutOfTape { pointer, .. }) => {
+                        assert_eq!(pointer, expected, "{:?} {}", backend, source)
+                    }
+                    result => panic!("unexpected result: {:?}", result),
+                }
+            }
+        }
+    }
+}
+
+struct FailingWriter;
+
+impl Write for FailingWriter {
+    fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("broken"))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn io_errors() {
+    for backend in [Backend::Vm, Backend::Native] {
+        let program = Engine::builder()
+            .backend(backend)
+            .build()
+            .unwrap()
+            .compile("+.")
+            .unwrap();
+        let error = program.run(&b""[..], FailingWriter).unwrap_err();
+        assert!(matches!(error, RunError::Io(_)), "{:?}", backend);
+        assert_eq!(error.to_string(), "I/O error: broken");
+    }
+}
+
+#[test]
+fn unsupported_options() {
+    assert!(Engine::builder()
+        .cell_width(CellWidth::U16)
+        .build()
+        .is_err());
+    assert!(Engine::builder().tape(0).build().is_err());
+    assert!(Engine::builder()
+        .backend(Backend::Native)
+        .eof(Eof::Unchanged)
+        .build()
+        .is_err());
+    assert!(Engine::builder().build().unwrap().compile("[").is_err());
+}
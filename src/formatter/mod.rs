@@ -0,0 +1,147 @@
+//! Brainfuckのソースコードを整形する
+
+use crate::{
+    instruction::Instruction,
+    parser::{self, Comment, Node},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// 整形の設定
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// 1行の幅。コメントはこの幅を超えても折り返さない
+    pub width: usize,
+    /// ループのネスト1段あたりのインデントの幅
+    pub indent: usize,
+    /// 同じ命令の並びを空白で区切ってまとめる
+    pub group: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            width: 80,
+            indent: 2,
+            group: true,
+        }
+    }
+}
+
+/// ループのネストごとにインデントし、ループ以外の命令の並びを幅に収まるように行にまとめる。
+/// コメントは元の行に命令があればその行の後ろに、なければ独立した行に書く
+pub fn format(source: &str, options: &FormatOptions) -> anyhow::Result<String> {
+    let program = parser::parse_with_comments(source)?;
+    let mut formatter = Formatter {
+        source,
+        options,
+        lines: Vec::new(),
+        runs: Vec::new(),
+    };
+    formatter.write_block(&program, 0);
+    let mut out = String::new();
+    for line in formatter.lines {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    options: &'a FormatOptions,
+    /// 書き終えた行。インデントを含む
+    lines: Vec<String>,
+    /// まだ行にしていない命令の並び。`group`が`false`なら1つにつなげる
+    runs: Vec<String>,
+}
+
+impl Formatter<'_> {
+    fn write_block(&mut self, nodes: &[Node], depth: usize) {
+        for node in nodes {
+            match node {
+                Node::Command(inst) => self.push_command(command_char(inst)),
+                Node::Loop(body) => {
+                    self.flush_runs(depth);
+                    self.push_line("[", depth);
+                    self.write_block(body, depth + 1);
+                    self.push_line("]", depth);
+                }
+                Node::Comment(comment) => self.write_comment(comment, depth),
+            }
+        }
+        self.flush_runs(depth);
+    }
+
+    fn push_command(&mut self, command: char) {
+        match self.runs.last_mut() {
+            Some(run) if !self.options.group || run.ends_with(command) => run.push(command),
+            _ => self.runs.push(command.to_string()),
+        }
+    }
+
+    fn write_comment(&mut self, comment: &Comment, depth: usize) {
+        self.flush_runs(depth);
+        let line_start = self.source[..comment.position]
+            .rfind('\n')
+            .map_or(0, |i| i + 1);
+        let own_line = self.source[line_start..comment.position].trim().is_empty();
+        match self.lines.last_mut() {
+            Some(line) if !own_line => {
+                line.push(' ');
+                line.push_str(&comment.text);
+            }
+            _ => self.push_line(&comment.text, depth),
+        }
+    }
+
+    /// 命令の並びを幅に収まるように行に分ける。1つの並びが幅を超える場合は途中で折り返す
+    fn flush_runs(&mut self, depth: usize) {
+        let width = self
+            .options
+            .width
+            .saturating_sub(depth * self.options.indent)
+            .max(1);
+        let separator = if self.options.group { " " } else { "" };
+        let mut line = String::new();
+        for run in std::mem::take(&mut self.runs) {
+            // 命令はすべてASCII文字なので、バイト単位で分けてよい
+            for chunk in run.as_bytes().chunks(width) {
+                let chunk = std::str::from_utf8(chunk).unwrap();
+                if line.is_empty() {
+                    line.push_str(chunk);
+                } else if line.len() + separator.len() + chunk.len() <= width {
+                    line.push_str(separator);
+                    line.push_str(chunk);
+                } else {
+                    self.push_line(&line, depth);
+                    line = chunk.to_string();
+                }
+            }
+        }
+        if !line.is_empty() {
+            self.push_line(&line, depth);
+        }
+    }
+
+    fn push_line(&mut self, line: &str, depth: usize) {
+        self.lines.push(format!(
+            "{}{}",
+            " ".repeat(depth * self.options.indent),
+            line
+        ));
+    }
+}
+
+fn command_char(inst: &Instruction) -> char {
+    match inst {
+        Instruction::Increment => '+',
+        Instruction::Decrement => '-',
+        Instruction::PointerIncrement => '>',
+        Instruction::PointerDecrement => '<',
+        Instruction::PutChar => '.',
+        Instruction::GetChar => ',',
+        _ => unreachable!("the parser only produces unoptimized instructions"),
+    }
+}
@@ -0,0 +1,57 @@
+use crate::parser;
+
+use super::*;
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+#[test]
+fn indent_and_group() {
+    let text = format("+++[->>+<<]>>.", &FormatOptions::default()).unwrap();
+    assert_eq!(text, "+++\n[\n  - >> + <<\n]\n>> .\n");
+
+    let options = FormatOptions {
+        indent: 4,
+        group: false,
+        ..Default::default()
+    };
+    let text = format("+++[->>+<<]>>.", &options).unwrap();
+    assert_eq!(text, "+++\n[\n    ->>+<<\n]\n>>.\n");
+}
+
+#[test]
+fn wrap_lines() {
+    let options = FormatOptions {
+        width: 8,
+        ..Default::default()
+    };
+    let text = format("+++>>>---<<<[++++++++++]", &options).unwrap();
+    assert_eq!(text, "+++ >>>\n--- <<<\n[\n  ++++++\n  ++++\n]\n");
+}
+
+#[test]
+fn preserve_comments() {
+    let source = "set counter\n+++ [ loop three times\n  - > + < decrement\n]\nprint it: >.\n";
+    let text = format(source, &FormatOptions::default()).unwrap();
+    assert_eq!(
+        text,
+        "set counter\n+++\n[ loop three times\n  - > + < decrement\n]\nprint it:\n> .\n"
+    );
+}
+
+#[test]
+fn keep_program() {
+    let source = format!("Hello World program\n{}\nend", HELLO_WORLD);
+    for width in [1, 10, 80] {
+        let options = FormatOptions {
+            width,
+            ..Default::default()
+        };
+        let text = format(&source, &options).unwrap();
+        assert_eq!(
+            parser::parse(&text).unwrap(),
+            parser::parse(&source).unwrap()
+        );
+        // 整形した結果をもう一度整形しても変わらない
+        assert_eq!(format(&text, &options).unwrap(), text);
+    }
+}
@@ -0,0 +1,204 @@
+//! 最適化やバックエンドによって結果が変わるプログラムを探す
+//!
+//! `generator::random`で生成したプログラムを、最適化なしのVM、最適化したVM、最適化したネイティブコードで
+//! `bf run`として別のプロセスで実行し、標準出力と終了の仕方を比べる。
+//! 異なるものが見つかれば、異なるままのできるだけ小さいプログラムと入力に縮める
+
+use std::{
+    path::Path,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use clap::ValueEnum;
+
+use crate::{
+    generator::random::{self, RandomOptions, Rng},
+    golden::Preset,
+    parser, process,
+    runtime::Backend,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// 比べる実行の方法。最初のものを基準にする
+pub const CONFIGS: [(Backend, Preset); 3] = [
+    (Backend::Vm, Preset::None),
+    (Backend::Vm, Preset::All),
+    (Backend::Native, Preset::All),
+];
+
+/// 基準の実行で、これより多くの命令を実行するプログラムは終わらないとみなす
+const MAX_STEPS: u64 = 100_000_000;
+/// 基準の実行のテープの長さ。他のバックエンドで範囲外になるプログラムを除く
+const MAX_TAPE: usize = 30000;
+
+/// プログラムと、その入力
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub code: String,
+    pub input: Vec<u8>,
+}
+
+impl Case {
+    /// `seed`からプログラムと、0から4バイトの入力を生成する
+    pub fn random(seed: u64, options: &RandomOptions) -> Self {
+        let mut rng = Rng::new(seed);
+        let code = random::program(&mut rng, options);
+        let input = (0..rng.below(5)).map(|_| rng.next_u64() as u8).collect();
+        Case { code, input }
+    }
+}
+
+/// 1回の実行の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Behavior {
+    /// 正常に終了した。値は標準出力
+    Finished(Vec<u8>),
+    /// エラーで終了したかシグナルで止まった。値は終了した状態と標準エラー出力
+    Failed(String),
+    TimedOut,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// すべての実行の結果が同じだった
+    Same,
+    /// 基準の実行が正常に終わらなかったので比べられない
+    Invalid(Behavior),
+    /// 結果が異なった。値は`CONFIGS`の順の結果
+    Diverged(Vec<Behavior>),
+}
+
+/// 実行ファイル`bf`で`case`を`CONFIGS`のそれぞれの方法で実行して比べる。
+///
+/// 基準以外の実行は基準より速いはずなので、基準の20倍(最低1秒)か`timeout`の短い方を過ぎたら止める。
+/// 縮める途中で止まらなくなったプログラムを、毎回`timeout`まで待たないようにするため
+pub fn check(bf: &Path, case: &Case, mut timeout: Duration) -> anyhow::Result<Verdict> {
+    let mut behaviors = Vec::new();
+    for (i, &(backend, preset)) in CONFIGS.iter().enumerate() {
+        let mut command = Command::new(bf);
+        command
+            .arg("run")
+            .arg("--backend")
+            .arg(backend.to_possible_value().unwrap().get_name());
+        if preset != Preset::None {
+            command.arg("-O").arg(preset.name());
+        }
+        if i == 0 {
+            command
+                .arg(format!("--max-steps={}", MAX_STEPS))
+                .arg(format!("--max-tape={}", MAX_TAPE));
+        }
+        // `-`で始まるプログラムをオプションと区別させないため、`=`でつなげる
+        command.arg(format!("--expression={}", case.code));
+        let started = Instant::now();
+        let output = process::run(&mut command, &case.input, timeout)
+            .with_context(|| format!("failed to run {}", bf.display()))?;
+        if i == 0 {
+            timeout = timeout.min((started.elapsed() * 20).max(Duration::from_secs(1)));
+        }
+        let behavior = match output.status {
+            Some(status) if status.success() => Behavior::Finished(output.stdout),
+            Some(status) => Behavior::Failed(format!(
+                "{}: {}",
+                status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            None => Behavior::TimedOut,
+        };
+        if i == 0 && !matches!(behavior, Behavior::Finished(_)) {
+            return Ok(Verdict::Invalid(behavior));
+        }
+        behaviors.push(behavior);
+    }
+    if behaviors.iter().all(|behavior| *behavior == behaviors[0]) {
+        Ok(Verdict::Same)
+    } else {
+        Ok(Verdict::Diverged(behaviors))
+    }
+}
+
+/// `diverges`が`true`を返すまま、プログラムと入力をできるだけ小さくする。
+///
+/// 大きな範囲から順に、命令の範囲、対応する括弧の組、入力の範囲を取り除けるか試し、
+/// 何も取り除けなくなるまで繰り返す。括弧の対応が崩れるものは試さない
+pub fn shrink(
+    mut case: Case,
+    mut diverges: impl FnMut(&Case) -> anyhow::Result<bool>,
+) -> anyhow::Result<Case> {
+    loop {
+        let before = case.clone();
+
+        let mut size = case.code.len().div_ceil(2);
+        while size > 0 {
+            let mut start = 0;
+            while start < case.code.len() {
+                let mut candidate = case.clone();
+                let end = (start + size).min(case.code.len());
+                candidate.code.replace_range(start..end, "");
+                if parser::parse(&candidate.code).is_ok() && diverges(&candidate)? {
+                    case = candidate;
+                } else {
+                    start += size;
+                }
+            }
+            size /= 2;
+        }
+
+        // ループを取り除き、本体だけを残す
+        let mut start = 0;
+        while let Some(offset) = case.code[start..].find('[') {
+            let open = start + offset;
+            let close = matching_bracket(&case.code, open);
+            let mut candidate = case.clone();
+            candidate.code.remove(close);
+            candidate.code.remove(open);
+            if diverges(&candidate)? {
+                case = candidate;
+            } else {
+                start = open + 1;
+            }
+        }
+
+        let mut size = case.input.len().div_ceil(2);
+        while size > 0 {
+            let mut start = 0;
+            while start < case.input.len() {
+                let mut candidate = case.clone();
+                let end = (start + size).min(case.input.len());
+                candidate.input.drain(start..end);
+                if diverges(&candidate)? {
+                    case = candidate;
+                } else {
+                    start += size;
+                }
+            }
+            size /= 2;
+        }
+
+        if case == before {
+            return Ok(case);
+        }
+    }
+}
+
+/// `open`の位置の`[`に対応する`]`の位置
+fn matching_bracket(code: &str, open: usize) -> usize {
+    let mut depth = 0;
+    for (i, c) in code.bytes().enumerate().skip(open) {
+        match c {
+            b'[' => depth += 1,
+            b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    unreachable!("the brackets are balanced")
+}
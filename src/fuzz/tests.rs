@@ -0,0 +1,41 @@
+use super::*;
+
+fn case(code: &str, input: &[u8]) -> Case {
+    Case {
+        code: code.to_string(),
+        input: input.to_vec(),
+    }
+}
+
+#[test]
+fn shrink_code_and_input() {
+    let shrunk = shrink(case("++>-[->+<]<+.>>", b"abxcd"), |case| {
+        Ok(case.code.contains("+.") && case.input.contains(&b'x'))
+    })
+    .unwrap();
+    assert_eq!(shrunk, case("+.", b"x"));
+}
+
+#[test]
+fn shrink_unwraps_loops() {
+    // `[`と`]`を別々に取り除くと括弧の対応が崩れる
+    let shrunk = shrink(case(">[+.]", b""), |case| Ok(case.code.contains("+."))).unwrap();
+    assert_eq!(shrunk, case("+.", b""));
+}
+
+#[test]
+fn shrink_keeps_brackets_balanced() {
+    let shrunk = shrink(case("+[>[-]<-]", b""), |case| {
+        assert!(parser::parse(&case.code).is_ok());
+        Ok(case.code.contains("[-]"))
+    })
+    .unwrap();
+    assert_eq!(shrunk, case("[-]", b""));
+}
+
+#[test]
+fn random_cases_are_reproducible() {
+    let options = RandomOptions::default();
+    assert_eq!(Case::random(7, &options), Case::random(7, &options));
+    assert_ne!(Case::random(7, &options), Case::random(8, &options));
+}
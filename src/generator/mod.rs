@@ -0,0 +1,91 @@
+//! Brainfuckのプログラムを生成する
+
+pub mod random;
+#[cfg(test)]
+mod tests;
+
+/// 初期化のループで値を設定するセルの数の上限
+const MAX_CELLS: usize = 5;
+/// 初期化のループの繰り返し回数の上限
+const MAX_ITERATIONS: u8 = 20;
+
+/// `text`を出力するプログラムを生成する。
+///
+/// 乗算のループでいくつかのセルを出力する文字に近い値にしてから、文字ごとに移動と加減算の少ないセルを選んで使い回す。
+/// セルの数とループの繰り返し回数の組み合わせを試し、最も短いプログラムを返す
+pub fn text(text: &[u8]) -> String {
+    let mut values = text.to_vec();
+    values.sort_unstable();
+    values.dedup();
+
+    // 初期化のループを使わず、0のセル1つから始める場合
+    let mut best = emit(text, &[], 0);
+    for cells in 1..=MAX_CELLS.min(values.len()) {
+        let targets = values
+            .chunks(values.len().div_ceil(cells))
+            .map(|chunk| (chunk[0] as u32 + chunk[chunk.len() - 1] as u32) / 2)
+            .collect::<Vec<_>>();
+        for iterations in 1..=MAX_ITERATIONS {
+            let factors = targets
+                .iter()
+                .map(|&target| ((target + iterations as u32 / 2) / iterations as u32) as u8)
+                .collect::<Vec<_>>();
+            let code = emit(text, &factors, iterations);
+            if code.len() < best.len() {
+                best = code;
+            }
+        }
+    }
+    best
+}
+
+/// セル0を`iterations`回繰り返すループで、セル1以降をそれぞれ`factors`倍した値にしてから`text`を出力する
+fn emit(text: &[u8], factors: &[u8], iterations: u8) -> String {
+    let mut code = String::new();
+    let mut cells = vec![0u8];
+    if !factors.is_empty() {
+        push_repeated(&mut code, '+', iterations as usize);
+        code.push('[');
+        for &factor in factors {
+            code.push('>');
+            push_repeated(&mut code, '+', factor as usize);
+        }
+        push_repeated(&mut code, '<', factors.len());
+        code.push_str("-]");
+        cells.extend(factors.iter().map(|factor| factor.wrapping_mul(iterations)));
+    }
+
+    let mut pointer = 0usize;
+    for &c in text {
+        let (cell, _) = cells
+            .iter()
+            .enumerate()
+            .min_by_key(|&(i, &value)| pointer.abs_diff(i) + distance(value, c))
+            .unwrap();
+        if cell > pointer {
+            push_repeated(&mut code, '>', cell - pointer);
+        } else {
+            push_repeated(&mut code, '<', pointer - cell);
+        }
+        pointer = cell;
+        let delta = c.wrapping_sub(cells[cell]);
+        if delta <= 128 {
+            push_repeated(&mut code, '+', delta as usize);
+        } else {
+            push_repeated(&mut code, '-', 256 - delta as usize);
+        }
+        cells[cell] = c;
+        code.push('.');
+    }
+    code
+}
+
+/// `from`を`to`にするのに必要な`+`か`-`の数
+fn distance(from: u8, to: u8) -> usize {
+    let delta = to.wrapping_sub(from);
+    delta.min(delta.wrapping_neg()) as usize
+}
+
+fn push_repeated(code: &mut String, c: char, count: usize) {
+    code.extend(std::iter::repeat_n(c, count));
+}
@@ -0,0 +1,228 @@
+//! 必ず終了し、テープの範囲を出ないランダムなプログラムを生成する
+//!
+//! ポインタの位置は常にわかるように、ループの本体は必ず開始したセルに戻る。
+//! ループの開始位置のセル(カウンタ)は、本体の最初と最後で合わせて奇数だけ増減し、それ以外では書き換えない。
+//! 増減には逆向きのものを混ぜることもある。
+//! 256回以内に0になるので、ループの中のループも含めて必ず終了する
+
+use super::push_repeated;
+
+/// シードから決まる疑似乱数(SplitMix64)
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// `0..n`の値を返す
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// `percent`%の確率で`true`を返す
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+/// 生成するプログラムの設定
+#[derive(Debug, Clone)]
+pub struct RandomOptions {
+    /// 加算や移動、ループなどの操作の数。ループの本体の操作は含まない
+    pub length: usize,
+    /// 使うセルの数。ポインタは`0..cells`に収まる
+    pub cells: usize,
+    /// ループのネストの深さの上限
+    pub depth: usize,
+}
+
+impl Default for RandomOptions {
+    fn default() -> Self {
+        RandomOptions {
+            length: 50,
+            cells: 300,
+            depth: 2,
+        }
+    }
+}
+
+pub fn program(rng: &mut Rng, options: &RandomOptions) -> String {
+    let mut generator = Generator {
+        rng,
+        cells: options.cells.max(1),
+        depth: options.depth,
+        code: String::new(),
+        pointer: 0,
+        counters: Vec::new(),
+    };
+    generator.block(options.length);
+    generator.code
+}
+
+struct Generator<'a> {
+    rng: &'a mut Rng,
+    cells: usize,
+    depth: usize,
+    code: String,
+    pointer: usize,
+    /// 実行中のループのカウンタ。ループの本体では書き換えない
+    counters: Vec<usize>,
+}
+
+impl Generator<'_> {
+    /// 加算や移動、ループなどの操作を`length`回生成する
+    fn block(&mut self, length: usize) {
+        for done in 0..length {
+            match self.rng.below(20) {
+                0..=5 => self.add(),
+                6..=10 => self.move_near(),
+                // 遠くへの移動は繰り返すと実行する命令の数が増えすぎるので、ループの外でだけ行う
+                11 if self.counters.is_empty() => {
+                    let target = self.rng.below(self.cells);
+                    self.move_to(target);
+                }
+                11 => self.move_near(),
+                12..=13 => self.code.push('.'),
+                14 => {
+                    if self.writable(self.pointer) {
+                        self.code.push(',');
+                    }
+                }
+                15 => self.clear(),
+                16..=17 => self.multiplication(),
+                _ => self.loop_((length - done) / 2),
+            }
+        }
+    }
+
+    fn writable(&self, cell: usize) -> bool {
+        !self.counters.contains(&cell)
+    }
+
+    /// 現在のセルに加算する。ループの外ではときどき256を超える回数にする
+    fn add(&mut self) {
+        if !self.writable(self.pointer) {
+            return;
+        }
+        let count = if self.counters.is_empty() && self.rng.chance(3) {
+            250 + self.rng.below(20)
+        } else {
+            1 + self.rng.below(12)
+        };
+        let c = if self.rng.chance(50) { '+' } else { '-' };
+        push_repeated(&mut self.code, c, count);
+    }
+
+    fn move_near(&mut self) {
+        let distance = 1 + self.rng.below(4);
+        let target = if self.rng.chance(50) {
+            self.pointer.saturating_sub(distance)
+        } else {
+            (self.pointer + distance).min(self.cells - 1)
+        };
+        self.move_to(target);
+    }
+
+    fn move_to(&mut self, target: usize) {
+        if target > self.pointer {
+            push_repeated(&mut self.code, '>', target - self.pointer);
+        } else {
+            push_repeated(&mut self.code, '<', self.pointer - target);
+        }
+        self.pointer = target;
+    }
+
+    /// `[-]`か`[+]`
+    fn clear(&mut self) {
+        if self.writable(self.pointer) {
+            self.code
+                .push_str(if self.rng.chance(80) { "[-]" } else { "[+]" });
+        }
+    }
+
+    /// 値を他のセルに定数倍して加算するループ。`optimizer::mul_loop`で展開される形にする
+    fn multiplication(&mut self) {
+        let counter = self.pointer;
+        if self.counters.len() >= self.depth || !self.writable(counter) {
+            return;
+        }
+        let c = if self.rng.chance(80) { '-' } else { '+' };
+        let (before, after) = self.counter_steps(c, 1, 50);
+        self.code.push('[');
+        self.code.push_str(&before);
+        self.counters.push(counter);
+        for _ in 0..1 + self.rng.below(3) {
+            self.move_near();
+            if self.writable(self.pointer) {
+                let count = 1 + self.rng.below(5);
+                let c = if self.rng.chance(70) { '+' } else { '-' };
+                push_repeated(&mut self.code, c, count);
+            }
+        }
+        self.counters.pop();
+        self.move_to(counter);
+        self.code.push_str(&after);
+        self.code.push(']');
+    }
+
+    /// 本体に何でも含むループ。カウンタは1か3ずつ増減する
+    fn loop_(&mut self, length: usize) {
+        let counter = self.pointer;
+        if self.counters.len() >= self.depth || !self.writable(counter) {
+            return;
+        }
+        let count = if self.rng.chance(80) { 1 } else { 3 };
+        let c = if self.rng.chance(80) { '-' } else { '+' };
+        let (before, after) = self.counter_steps(c, count, 30);
+        self.code.push('[');
+        self.code.push_str(&before);
+        self.counters.push(counter);
+        self.block(length.min(10));
+        self.counters.pop();
+        self.move_to(counter);
+        self.code.push_str(&after);
+        self.code.push(']');
+    }
+
+    /// ループの本体の最初と最後に置く、カウンタを増減する命令。合わせると`c`の向きに`count`だけ変わる。
+    ///
+    /// ときどき逆向きの増減を混ぜ、最初と最後に分けて置く。
+    /// そうでなければ`first_percent`%の確率ですべて最初に、それ以外はすべて最後に置く
+    fn counter_steps(&mut self, c: char, count: usize, first_percent: usize) -> (String, String) {
+        let opposite = if c == '-' { '+' } else { '-' };
+        let extra = if self.rng.chance(30) {
+            1 + self.rng.below(2)
+        } else {
+            0
+        };
+        if extra == 0 {
+            let steps = c.to_string().repeat(count);
+            return if self.rng.chance(first_percent) {
+                (steps, String::new())
+            } else {
+                (String::new(), steps)
+            };
+        }
+        let mut steps = vec![c; count + extra];
+        steps.extend(std::iter::repeat_n(opposite, extra));
+        // Fisher-Yatesで並べ替える
+        for i in (1..steps.len()).rev() {
+            steps.swap(i, self.rng.below(i + 1));
+        }
+        let split = self.rng.below(steps.len() + 1);
+        (
+            steps[..split].iter().collect(),
+            steps[split..].iter().collect(),
+        )
+    }
+}
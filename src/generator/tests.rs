@@ -0,0 +1,55 @@
+use crate::{compiler, parser, runtime};
+
+use self::random::{RandomOptions, Rng};
+
+use super::*;
+
+fn run(code: &str) -> Vec<u8> {
+    let program = parser::parse(code).unwrap();
+    runtime::vm::run_captured(&compiler::vm::compile(&program))
+}
+
+#[test]
+fn prints_text() {
+    for text in [
+        "",
+        "A",
+        "Hello, World!\n",
+        "aaaaaaaaaa",
+        "The quick brown fox jumps over the lazy dog.",
+        "\x00\x7f\u{3042}",
+    ] {
+        assert_eq!(run(&super::text(text.as_bytes())), text.as_bytes());
+    }
+}
+
+#[test]
+fn uses_multiplication_loop() {
+    let text = b"Hello, World!\n";
+    let code = super::text(text);
+    assert!(code.starts_with('+') && code.contains('['));
+    assert!(code.len() < emit(text, &[], 0).len());
+}
+
+#[test]
+fn random_programs_terminate_in_bounds() {
+    let options = RandomOptions {
+        cells: 50,
+        ..Default::default()
+    };
+    for seed in 0..200 {
+        let code = random::program(&mut Rng::new(seed), &options);
+        // 入力を読まないように`,`を除く。`,`はカウンタのセルには書き込まないので、終了するかどうかは変わらない
+        let program = parser::parse(&code.replace(',', "")).unwrap();
+        let instructions = compiler::vm::compile(&program);
+        let mut vm = runtime::vm::VM::new(&instructions);
+        vm.output = Some(Vec::new());
+        let mut steps = 0;
+        while !vm.is_finished() {
+            vm.step();
+            assert!(vm.pointer < options.cells, "seed {}: {}", seed, code);
+            steps += 1;
+            assert!(steps < 10_000_000, "seed {}: {}", seed, code);
+        }
+    }
+}
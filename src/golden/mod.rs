@@ -0,0 +1,160 @@
+//! 期待する出力と比べてプログラムを実行するテスト
+//!
+//! ディレクトリの`NAME.bf`ごとに、`NAME.out`を期待する標準出力、`NAME.in`があればそれを標準入力にして、
+//! `bf run`をバックエンドと最適化の組み合わせごとに別のプロセスで実行する。
+//! ネイティブコードがクラッシュしたり止まらなかったりしても、その組み合わせの失敗として扱える
+//!
+//! ここで実行するのは`bf run`のバックエンドだけで、`bf build`の出力は外部のツールが要るので
+//! 結合テストで同じプログラムを実行して確かめる。実行ファイルは`tests/executable.rs`、
+//! オブジェクトファイルは`tests/object.rs`、Rustは`tests/rust.rs`、C、WebAssembly、
+//! バイトコードは`tests/emitters.rs`で扱う
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use anyhow::Context;
+use clap::ValueEnum;
+
+use crate::{process, runtime::Backend};
+
+#[cfg(test)]
+mod tests;
+
+/// 1つのプログラムと、その入力と期待する出力
+#[derive(Debug, Clone)]
+pub struct Case {
+    /// 拡張子を除いたファイル名
+    pub name: String,
+    pub source: PathBuf,
+    pub input: Option<PathBuf>,
+    pub expected: Vec<u8>,
+}
+
+/// `bf run -O`に渡す最適化の組み合わせ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[clap(rename_all = "snake_case")]
+pub enum Preset {
+    /// No optimizations
+    None,
+    /// -O consecutive_inc_dec
+    ConsecutiveIncDec,
+    /// -O mul_loop
+    MulLoop,
+    /// -O all
+    All,
+    /// -O all --fuse all. Only for the VM and closure backends
+    Fused,
+}
+
+impl Preset {
+    pub fn name(self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+
+    /// `backend`でこの組み合わせを実行できるか。命令の融合はVMの命令列にしか適用できない
+    pub fn supports(self, backend: Backend) -> bool {
+        self != Preset::Fused || matches!(backend, Backend::Vm | Backend::Closure)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    /// 失敗の理由
+    Failed(String),
+}
+
+/// `dir`の`.bf`ファイルを名前の順に読み込む。期待する出力のファイルがなければエラーを返す
+pub fn load(dir: &Path) -> anyhow::Result<Vec<Case>> {
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    let mut cases = Vec::new();
+    for entry in entries {
+        let source = entry?.path();
+        if source.extension().is_none_or(|extension| extension != "bf") {
+            continue;
+        }
+        let name = source.file_stem().unwrap().to_string_lossy().to_string();
+        let output = source.with_extension("out");
+        let expected = std::fs::read(&output)
+            .with_context(|| format!("failed to read the expected output {}", output.display()))?;
+        let input = Some(source.with_extension("in")).filter(|input| input.exists());
+        cases.push(Case {
+            name,
+            source,
+            input,
+            expected,
+        });
+    }
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(cases)
+}
+
+/// 実行ファイル`bf`で`case`を実行し、出力を比べる。`timeout`を過ぎたらプロセスを止めて失敗にする
+pub fn run(
+    bf: &Path,
+    case: &Case,
+    backend: Backend,
+    preset: Preset,
+    timeout: Duration,
+) -> anyhow::Result<Outcome> {
+    let mut command = Command::new(bf);
+    command
+        .arg("run")
+        .arg("--backend")
+        .arg(backend.to_possible_value().unwrap().get_name());
+    match preset {
+        Preset::None => {}
+        Preset::Fused => {
+            command.arg("-O").arg("all").arg("--fuse").arg("all");
+        }
+        _ => {
+            command.arg("-O").arg(preset.name());
+        }
+    }
+    if let Some(input) = &case.input {
+        command.arg("--input-file").arg(input);
+    }
+    let output = process::run(command.arg(&case.source), &[], timeout)
+        .with_context(|| format!("failed to run {}", bf.display()))?;
+    let Some(status) = output.status else {
+        return Ok(Outcome::Failed(format!("timed out after {:?}", timeout)));
+    };
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Ok(Outcome::Failed(format!("{}: {}", status, stderr.trim())));
+    }
+    Ok(match difference(&case.expected, &output.stdout) {
+        Some(message) => Outcome::Failed(message),
+        None => Outcome::Passed,
+    })
+}
+
+/// 出力が異なれば、最初に異なる行を説明する
+pub fn difference(expected: &[u8], actual: &[u8]) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+    let mut expected_lines = expected.split_inclusive(|&c| c == b'\n');
+    let mut actual_lines = actual.split_inclusive(|&c| c == b'\n');
+    // 出力が異なるので、どちらかの行が先に異なるか尽きる
+    (1..).find_map(|line| match (expected_lines.next(), actual_lines.next()) {
+        (Some(expected), Some(actual)) if expected == actual => None,
+        (expected, actual) => Some(format!(
+            "line {}: expected {}, got {}",
+            line,
+            describe(expected),
+            describe(actual)
+        )),
+    })
+}
+
+fn describe(line: Option<&[u8]>) -> String {
+    match line {
+        Some(line) => format!("{:?}", String::from_utf8_lossy(line)),
+        None => "the end of the output".to_string(),
+    }
+}
@@ -0,0 +1,49 @@
+use super::*;
+
+#[test]
+fn same_output() {
+    assert_eq!(difference(b"a\nb\n", b"a\nb\n"), None);
+    assert_eq!(difference(b"", b""), None);
+}
+
+#[test]
+fn first_different_line() {
+    assert_eq!(
+        difference(b"a\nb\nc\n", b"a\nx\nc\n").unwrap(),
+        r#"line 2: expected "b\n", got "x\n""#
+    );
+    assert_eq!(
+        difference(b"a\nb", b"a\nb\n").unwrap(),
+        r#"line 2: expected "b", got "b\n""#
+    );
+}
+
+#[test]
+fn missing_and_extra_lines() {
+    assert_eq!(
+        difference(b"a\nb\n", b"a\n").unwrap(),
+        r#"line 2: expected "b\n", got the end of the output"#
+    );
+    assert_eq!(
+        difference(b"", b"a").unwrap(),
+        r#"line 1: expected the end of the output, got "a""#
+    );
+}
+
+#[test]
+fn load_cases() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs");
+    let cases = load(&dir).unwrap();
+    let names = cases
+        .iter()
+        .map(|case| case.name.as_str())
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"hello") && names.is_sorted());
+    let rot13 = cases.iter().find(|case| case.name == "rot13").unwrap();
+    assert_eq!(rot13.input, Some(dir.join("rot13.in")));
+    let hello = cases.iter().find(|case| case.name == "hello").unwrap();
+    assert_eq!(
+        (hello.input.as_ref(), &hello.expected[..]),
+        (None, &b"Hello World!\n"[..])
+    );
+}
@@ -0,0 +1,371 @@
+//! 中間表現をテキストで読み書きする
+//!
+//! 1行に1つの命令を書き、ループと`IfNotZero`の本体は`loop`/`if`から`end`までの行に
+//! インデントして書く。`;`から行末まではコメントで、読み込むときは無視する。
+//!
+//! | 命令 | テキスト |
+//! |---|---|
+//! | `Increment` / `Decrement` | `inc` / `dec` |
+//! | `PointerIncrement` / `PointerDecrement` | `right` / `left` |
+//! | `PutChar` / `GetChar` | `out` / `in` |
+//! | `Add(n)` / `Subtract(n)` | `add n` / `sub n` |
+//! | `PointerAdd(n)` / `PointerSubtract(n)` | `right n` / `left n` |
+//! | `SetZero` / `Negate` | `zero` / `neg` |
+//! | `AddValueAt(at)` / `SubtractValueAt(at)` | `add_at +1` / `sub_at -1` |
+//! | `AddValueMultipliedBy(n, at)` / `SubtractValueMultipliedBy(n, at)` | `add_mul n +1` / `sub_mul n -1` |
+//! | `Loop(..)` / `IfNotZero(..)` | `loop` … `end` / `if` … `end` |
+
+use std::{fmt::Write, ops::Range};
+
+use anyhow::{bail, Context};
+
+use crate::instruction::Instruction;
+
+#[cfg(test)]
+mod tests;
+
+/// 注釈を揃えて表示する列
+const ANNOTATION_COLUMN: usize = 32;
+/// インデントの幅
+const INDENT: usize = 2;
+
+/// 注釈を付けずにテキストにする
+pub fn print(program: &[Instruction]) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        annotate: false,
+        ip: 0,
+        source: None,
+    };
+    printer.block(program, 0, true);
+    printer.out
+}
+
+/// 各命令に注釈を付けてテキストにする
+///
+/// 注釈は`compiler::vm::compile`での命令の位置(`ip`)、VMの命令の数(`cost`)、
+/// 囲んでいるブロックの先頭からのポインタの移動量(`ptr`、ループの後などで分からなければ`?`)。
+/// `source`を渡した場合は、命令に対応するソースコードのバイト位置(`src`)も付ける。
+/// `source`は`program`の元になったソースコードでなければならず、対応が取れなくなった以降の命令には付けない
+pub fn print_annotated(program: &[Instruction], source: Option<&str>) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        annotate: true,
+        ip: 0,
+        source: source.map(SourceCursor::new),
+    };
+    printer.block(program, 0, true);
+    printer.out
+}
+
+struct Printer {
+    out: String,
+    annotate: bool,
+    /// 次の命令の`compiler::vm::compile`での位置
+    ip: usize,
+    source: Option<SourceCursor>,
+}
+
+impl Printer {
+    /// ブロックを書き出す。`in_source`が偽の場合は、最適化で作られた命令なのでソースコードと対応しない
+    fn block(&mut self, instructions: &[Instruction], depth: usize, in_source: bool) {
+        // ブロックの先頭からのポインタの移動量
+        let mut offset = Some(0);
+        for inst in instructions {
+            let span = if in_source {
+                self.source.as_mut().and_then(|source| source.take(inst))
+            } else {
+                None
+            };
+            self.line(depth, &text(inst), offset, Some(cost(inst)), span);
+            match inst {
+                Instruction::Loop(body) => {
+                    self.ip += 1;
+                    self.block(body, depth + 1, in_source);
+                    self.ip += 1;
+                    if in_source {
+                        if let Some(source) = &mut self.source {
+                            source.close();
+                        }
+                    }
+                    self.line(depth, "end", None, None, None);
+                }
+                Instruction::IfNotZero(body) => {
+                    self.ip += 1;
+                    self.block(body, depth + 1, false);
+                    self.line(depth, "end", None, None, None);
+                }
+                _ => self.ip += 1,
+            }
+            offset = offset
+                .zip(shift(inst))
+                .map(|(offset, shift)| offset + shift);
+        }
+    }
+
+    fn line(
+        &mut self,
+        depth: usize,
+        text: &str,
+        offset: Option<isize>,
+        cost: Option<usize>,
+        span: Option<Range<usize>>,
+    ) {
+        let start = self.out.len();
+        self.out.push_str(&" ".repeat(depth * INDENT));
+        self.out.push_str(text);
+        if self.annotate {
+            if let Some(cost) = cost {
+                let width = self.out.len() - start;
+                let padding = ANNOTATION_COLUMN.saturating_sub(width).max(1);
+                self.out.push_str(&" ".repeat(padding));
+                write!(self.out, "; ip={} cost={} ptr=", self.ip, cost).unwrap();
+                match offset {
+                    Some(offset) => write!(self.out, "{:+}", offset).unwrap(),
+                    None => self.out.push('?'),
+                }
+                if let Some(span) = span {
+                    write!(self.out, " src={}..{}", span.start, span.end).unwrap();
+                }
+            }
+        }
+        self.out.push('\n');
+    }
+}
+
+fn text(inst: &Instruction) -> String {
+    match inst {
+        Instruction::Increment => "inc".to_string(),
+        Instruction::Decrement => "dec".to_string(),
+        Instruction::PointerIncrement => "right".to_string(),
+        Instruction::PointerDecrement => "left".to_string(),
+        Instruction::PutChar => "out".to_string(),
+        Instruction::GetChar => "in".to_string(),
+        Instruction::Loop(_) => "loop".to_string(),
+        Instruction::Add(value) => format!("add {}", value),
+        Instruction::Subtract(value) => format!("sub {}", value),
+        Instruction::SetZero => "zero".to_string(),
+        Instruction::PointerAdd(value) => format!("right {}", value),
+        Instruction::PointerSubtract(value) => format!("left {}", value),
+        Instruction::AddValueAt(at) => format!("add_at {:+}", at),
+        Instruction::SubtractValueAt(at) => format!("sub_at {:+}", at),
+        Instruction::AddValueMultipliedBy(value, at) => format!("add_mul {} {:+}", value, at),
+        Instruction::SubtractValueMultipliedBy(value, at) => format!("sub_mul {} {:+}", value, at),
+        Instruction::Negate => "neg".to_string(),
+        Instruction::IfNotZero(_) => "if".to_string(),
+    }
+}
+
+/// `compiler::vm::compile`で変換したときの命令の数
+fn cost(inst: &Instruction) -> usize {
+    match inst {
+        Instruction::Loop(body) => body.iter().map(cost).sum::<usize>() + 2,
+        Instruction::IfNotZero(body) => body.iter().map(cost).sum::<usize>() + 1,
+        _ => 1,
+    }
+}
+
+/// 命令を実行した後のポインタの移動量。実行するたびに変わる場合は`None`
+fn shift(inst: &Instruction) -> Option<isize> {
+    match inst {
+        Instruction::PointerIncrement => Some(1),
+        Instruction::PointerDecrement => Some(-1),
+        Instruction::PointerAdd(value) => Some(*value as isize),
+        Instruction::PointerSubtract(value) => Some(-(*value as isize)),
+        // 本体を実行してもしなくても同じ位置に戻る場合だけ分かる
+        Instruction::Loop(body) | Instruction::IfNotZero(body) => {
+            let shift = body.iter().map(shift).sum::<Option<isize>>()?;
+            (shift == 0).then_some(0)
+        }
+        _ => Some(0),
+    }
+}
+
+/// 最適化した命令とソースコードの対応を取る
+///
+/// 連続した同じ文字は1つの命令にまとめられ、`optimizer::mul_loop`はループ全体を1つの命令に
+/// 置き換えるので、命令の順にソースコードの命令の文字を読み進めれば対応が取れる
+struct SourceCursor {
+    /// 命令になる文字とそのバイト位置
+    commands: Vec<(usize, char)>,
+    /// `[`に対応する`]`の`commands`での位置
+    matching: Vec<usize>,
+    position: usize,
+    /// 対応が取れなくなった
+    lost: bool,
+}
+
+impl SourceCursor {
+    fn new(source: &str) -> Self {
+        let commands = source
+            .char_indices()
+            .filter(|(_, c)| matches!(c, '+' | '-' | '>' | '<' | '.' | ',' | '[' | ']'))
+            .collect::<Vec<_>>();
+        let mut matching = vec![0; commands.len()];
+        let mut stack = Vec::new();
+        for (i, &(_, c)) in commands.iter().enumerate() {
+            match c {
+                '[' => stack.push(i),
+                ']' => {
+                    if let Some(start) = stack.pop() {
+                        matching[start] = i;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self {
+            commands,
+            matching,
+            position: 0,
+            lost: false,
+        }
+    }
+
+    /// 命令に対応する文字を読み進め、そのバイト位置の範囲を返す。
+    /// ループの場合は`]`の手前まで読み進め、`close`で`]`を読む
+    fn take(&mut self, inst: &Instruction) -> Option<Range<usize>> {
+        if self.lost {
+            return None;
+        }
+        let span = self.do_take(inst);
+        if span.is_none() {
+            self.lost = true;
+        }
+        span
+    }
+
+    fn do_take(&mut self, inst: &Instruction) -> Option<Range<usize>> {
+        let start = self.position;
+        let (c, count) = match inst {
+            Instruction::Increment => ('+', 1),
+            Instruction::Decrement => ('-', 1),
+            Instruction::PointerIncrement => ('>', 1),
+            Instruction::PointerDecrement => ('<', 1),
+            Instruction::PutChar => ('.', 1),
+            Instruction::GetChar => (',', 1),
+            Instruction::Add(value) => ('+', *value as usize),
+            Instruction::Subtract(value) => ('-', *value as usize),
+            Instruction::PointerAdd(value) => ('>', *value),
+            Instruction::PointerSubtract(value) => ('<', *value),
+            Instruction::Loop(_) => {
+                let end = self.loop_end()?;
+                self.position += 1;
+                return Some(self.range(start, end));
+            }
+            // 最適化でループ全体から作られた命令
+            _ => {
+                let end = self.loop_end()?;
+                self.position = end + 1;
+                return Some(self.range(start, end));
+            }
+        };
+        let end = start + count;
+        if count == 0
+            || end > self.commands.len()
+            || self.commands[start..end].iter().any(|&(_, d)| d != c)
+        {
+            return None;
+        }
+        self.position = end;
+        Some(self.range(start, end - 1))
+    }
+
+    /// ループの本体を読み終えたところで`]`を読む
+    fn close(&mut self) {
+        if self.lost {
+            return;
+        }
+        match self.commands.get(self.position) {
+            Some((_, ']')) => self.position += 1,
+            _ => self.lost = true,
+        }
+    }
+
+    fn loop_end(&self) -> Option<usize> {
+        match self.commands.get(self.position) {
+            Some((_, '[')) => Some(self.matching[self.position]),
+            _ => None,
+        }
+    }
+
+    /// `commands`の`first..=last`に対応するバイト位置の範囲
+    fn range(&self, first: usize, last: usize) -> Range<usize> {
+        let (end, c) = self.commands[last];
+        self.commands[first].0..end + c.len_utf8()
+    }
+}
+
+/// `print`や`print_annotated`で書き出したテキストを読み込む
+pub fn parse(text: &str) -> anyhow::Result<Vec<Instruction>> {
+    // 読み込み中のブロック。先頭はプログラム全体
+    let mut blocks = vec![(None, Vec::new())];
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.split(';').next().unwrap();
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            continue;
+        };
+        let operands = words.collect::<Vec<_>>();
+        let inst = (|| {
+            let inst = match (name, operands.as_slice()) {
+                ("loop" | "if", []) => {
+                    blocks.push((Some(name), Vec::new()));
+                    return Ok(None);
+                }
+                ("end", []) => {
+                    let (Some(kind), body) = blocks.pop().unwrap() else {
+                        bail!("`end` without `loop` or `if`");
+                    };
+                    match kind {
+                        "loop" => Instruction::Loop(body),
+                        _ => Instruction::IfNotZero(body),
+                    }
+                }
+                ("inc", []) => Instruction::Increment,
+                ("dec", []) => Instruction::Decrement,
+                ("right", []) => Instruction::PointerIncrement,
+                ("left", []) => Instruction::PointerDecrement,
+                ("out", []) => Instruction::PutChar,
+                ("in", []) => Instruction::GetChar,
+                ("zero", []) => Instruction::SetZero,
+                ("neg", []) => Instruction::Negate,
+                ("add", [value]) => Instruction::Add(number(value)?),
+                ("sub", [value]) => Instruction::Subtract(number(value)?),
+                ("right", [value]) => Instruction::PointerAdd(number(value)?),
+                ("left", [value]) => Instruction::PointerSubtract(number(value)?),
+                ("add_at", [at]) => Instruction::AddValueAt(number(at)?),
+                ("sub_at", [at]) => Instruction::SubtractValueAt(number(at)?),
+                ("add_mul", [value, at]) => {
+                    Instruction::AddValueMultipliedBy(number(value)?, number(at)?)
+                }
+                ("sub_mul", [value, at]) => {
+                    Instruction::SubtractValueMultipliedBy(number(value)?, number(at)?)
+                }
+                (
+                    "loop" | "if" | "end" | "inc" | "dec" | "right" | "left" | "out" | "in"
+                    | "zero" | "neg" | "add" | "sub" | "add_at" | "sub_at" | "add_mul" | "sub_mul",
+                    _,
+                ) => bail!("wrong number of operands for `{}`", name),
+                _ => bail!("unknown instruction `{}`", name),
+            };
+            Ok(Some(inst))
+        })()
+        .with_context(|| format!("line {}", line_number))?;
+        if let Some(inst) = inst {
+            blocks.last_mut().unwrap().1.push(inst);
+        }
+    }
+    if let Some((Some(kind), _)) = blocks.last() {
+        bail!("`{}` is not closed with `end`", kind);
+    }
+    Ok(blocks.pop().unwrap().1)
+}
+
+fn number<T: std::str::FromStr>(text: &str) -> anyhow::Result<T> {
+    text.trim_start_matches('+')
+        .parse()
+        .ok()
+        .with_context(|| format!("invalid operand `{}`", text))
+}
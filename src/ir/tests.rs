@@ -0,0 +1,88 @@
+use crate::{
+    optimizer::{self, Optimization},
+    parser,
+};
+
+use super::*;
+
+const HELLO_WORLD: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+fn optimized(source: &str) -> Vec<Instruction> {
+    optimizer::optimize(parser::parse(source).unwrap(), &[Optimization::All])
+}
+
+#[test]
+fn round_trip() {
+    for program in [parser::parse(HELLO_WORLD).unwrap(), optimized(HELLO_WORLD)] {
+        assert_eq!(parse(&print(&program)).unwrap(), program);
+        assert_eq!(
+            parse(&print_annotated(&program, Some(HELLO_WORLD))).unwrap(),
+            program
+        );
+    }
+}
+
+#[test]
+fn annotations() {
+    let source = "+++ [->+<] >>[>]<.";
+    let text = print_annotated(&optimized(source), Some(source));
+    let lines = text
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            "add 3 ; ip=0 cost=1 ptr=+0 src=0..3",
+            "if ; ip=1 cost=6 ptr=+0 src=4..10",
+            "right ; ip=2 cost=1 ptr=+0",
+            "add_at -1 ; ip=3 cost=1 ptr=+1",
+            "left ; ip=4 cost=1 ptr=+1",
+            "zero ; ip=5 cost=1 ptr=+0",
+            "zero ; ip=6 cost=1 ptr=+0",
+            "end",
+            "right 2 ; ip=7 cost=1 ptr=+0 src=11..13",
+            "loop ; ip=8 cost=3 ptr=+2 src=13..16",
+            "right ; ip=9 cost=1 ptr=+0 src=14..15",
+            "end",
+            "left ; ip=11 cost=1 ptr=? src=16..17",
+            "out ; ip=12 cost=1 ptr=? src=17..18",
+        ]
+    );
+}
+
+#[test]
+fn parse_hand_written() {
+    let text = "
+        ; コメントと空行は無視する
+        add 2
+        loop
+          right 1   ; 注釈も無視する
+          add_mul 3 -1
+          left +1
+          dec
+        end
+    ";
+    assert_eq!(
+        parse(text).unwrap(),
+        [
+            Instruction::Add(2),
+            Instruction::Loop(vec![
+                Instruction::PointerAdd(1),
+                Instruction::AddValueMultipliedBy(3, -1),
+                Instruction::PointerSubtract(1),
+                Instruction::Decrement,
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn parse_errors() {
+    let error = |text| format!("{:#}", parse(text).unwrap_err());
+    assert_eq!(error("inc\njump 3"), "line 2: unknown instruction `jump`");
+    assert_eq!(error("add"), "line 1: wrong number of operands for `add`");
+    assert_eq!(error("add 256"), "line 1: invalid operand `256`");
+    assert_eq!(error("end"), "line 1: `end` without `loop` or `if`");
+    assert_eq!(error("loop\ninc"), "`loop` is not closed with `end`");
+}
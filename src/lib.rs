@@ -1,6 +1,18 @@
 pub mod assembler;
 pub mod compiler;
+pub mod debugger;
+pub mod elf;
+pub mod engine;
+pub mod formatter;
+pub mod fuzz;
+pub mod generator;
+pub mod golden;
 pub mod instruction;
+pub mod ir;
+pub mod lint;
 pub mod optimizer;
 pub mod parser;
+pub mod process;
+pub mod profiler;
 pub mod runtime;
+pub mod tape;
@@ -0,0 +1,340 @@
+//! ソースコードからよくある間違いを見つける
+//!
+//! `optimizer::mul_loop`のシミュレーションと同じように、すべてのセルが0の状態から
+//! テープの値とポインタの位置を先頭から追いかける。入力やループで値がわからなくなったセルは`None`にする
+
+use clap::ValueEnum;
+
+use crate::{
+    instruction::Instruction,
+    parser::{self, Node},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// 警告の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Lint {
+    /// Loops which move the pointer back and forth but do not return to the same cell
+    UnbalancedLoop,
+    /// Loops which never end
+    InfiniteLoop,
+    /// Loops which never run
+    DeadLoop,
+    /// Moves left of the cell where the program starts
+    MoveLeftOfStart,
+    /// Command characters which seem to be a part of a comment
+    CommandInComment,
+}
+
+impl Lint {
+    pub fn name(self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub lint: Lint,
+    /// 警告の対象になる命令のバイト位置
+    pub position: usize,
+    pub message: String,
+}
+
+/// 警告を位置の順に返す。構文が正しくなければエラーを返す
+pub fn check(source: &str) -> anyhow::Result<Vec<Warning>> {
+    let program = parser::parse_with_comments(source)?;
+    let mut checker = Checker {
+        positions: parser::command_positions(source),
+        next: 0,
+        state: State::default(),
+        warnings: Vec::new(),
+    };
+    checker.check_block(&program);
+    let mut warnings = checker.warnings;
+    check_comments(source, &program, &mut warnings);
+    warnings.sort_by_key(|warning| warning.position);
+    Ok(warnings)
+}
+
+/// 実行する位置から見たテープの状態
+#[derive(Debug, Clone)]
+struct State {
+    /// 左端からのセルの値
+    memory: Vec<Option<u8>>,
+    /// `memory`より右にあるセルの値
+    rest: Option<u8>,
+    /// 開始位置からのポインタの位置
+    pointer: Option<usize>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            memory: Vec::new(),
+            rest: Some(0),
+            pointer: Some(0),
+        }
+    }
+}
+
+impl State {
+    fn cell(&self) -> Option<u8> {
+        let pointer = self.pointer?;
+        self.memory.get(pointer).copied().unwrap_or(self.rest)
+    }
+
+    fn set(&mut self, value: Option<u8>) {
+        match self.pointer {
+            Some(pointer) => {
+                if pointer >= self.memory.len() {
+                    self.memory.resize(pointer + 1, self.rest);
+                }
+                self.memory[pointer] = value;
+            }
+            // どのセルに書き込むかわからない
+            None => self.forget(),
+        }
+    }
+
+    fn add(&mut self, value: u8) {
+        self.set(self.cell().map(|cell| cell.wrapping_add(value)));
+    }
+
+    /// すべてのセルの値を忘れる
+    fn forget(&mut self) {
+        self.memory.clear();
+        self.rest = None;
+    }
+}
+
+struct Checker {
+    /// 命令の位置。`next`番目が次に読む命令
+    positions: Vec<usize>,
+    next: usize,
+    state: State,
+    warnings: Vec<Warning>,
+}
+
+impl Checker {
+    fn next_position(&mut self) -> usize {
+        self.next += 1;
+        self.positions[self.next - 1]
+    }
+
+    fn warn(&mut self, lint: Lint, position: usize, message: String) {
+        self.warnings.push(Warning {
+            lint,
+            position,
+            message,
+        });
+    }
+
+    fn check_block(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Command(inst) => {
+                    let position = self.next_position();
+                    self.command(inst, position);
+                }
+                Node::Loop(body) => self.check_loop(body),
+                Node::Comment(_) => {}
+            }
+        }
+    }
+
+    fn command(&mut self, inst: &Instruction, position: usize) {
+        match inst {
+            Instruction::Increment => self.state.add(1),
+            Instruction::Decrement => self.state.add(255),
+            Instruction::PointerIncrement => self.state.pointer = self.state.pointer.map(|p| p + 1),
+            Instruction::PointerDecrement => {
+                if self.state.pointer == Some(0) {
+                    self.warn(
+                        Lint::MoveLeftOfStart,
+                        position,
+                        "the pointer moves left of the cell where the program starts".to_string(),
+                    );
+                    // 同じ原因の警告を続けて出さないように、位置をわからなくする
+                    self.state.pointer = None;
+                }
+                self.state.pointer = self.state.pointer.map(|p| p - 1);
+            }
+            Instruction::PutChar => {}
+            Instruction::GetChar => self.state.set(None),
+            _ => unreachable!("the parser only produces unoptimized instructions"),
+        }
+    }
+
+    fn check_loop(&mut self, body: &[Node]) {
+        let start = self.next_position();
+        let entry = self.state.cell();
+        if entry == Some(0) {
+            self.warn(
+                Lint::DeadLoop,
+                start,
+                "this loop never runs because the current cell is always 0 here".to_string(),
+            );
+            // 実行されない本体は調べない
+            self.next += count_commands(body) + 1;
+            return;
+        }
+
+        let shape = Shape::of(body);
+        if let (Some(value), Some(0), Some(0)) = (entry, shape.offset, shape.delta) {
+            self.warn(
+                Lint::InfiniteLoop,
+                start,
+                format!(
+                    "this loop never ends because the current cell is always {} here and the loop does not change it",
+                    value
+                ),
+            );
+        }
+        if let (Some(offset), true, true, true) = (
+            shape.offset,
+            shape.moves_right && shape.moves_left,
+            shape.changes_cells,
+            shape.delta.is_some(),
+        ) {
+            if offset != 0 {
+                self.warn(
+                    Lint::UnbalancedLoop,
+                    start,
+                    format!(
+                        "the pointer moves {:+} cells on each iteration of this loop; a `>` or `<` may be missing",
+                        offset
+                    ),
+                );
+            }
+        }
+
+        // 繰り返すたびに値は変わるので、値を忘れてから本体を調べる。
+        // ポインタが元の位置に戻らないループでも、1回目の繰り返しの位置はわかる
+        let pointer = self.state.pointer;
+        self.state.forget();
+        self.check_block(body);
+        if shape.offset != Some(0) || pointer.is_none() {
+            self.state.pointer = None;
+        }
+        self.next_position();
+        // ループを抜けたのは値が0だったから
+        if self.state.pointer.is_some() {
+            self.state.set(Some(0));
+        }
+    }
+}
+
+/// ループの本体の形
+struct Shape {
+    /// 1回の繰り返しでのポインタの移動量。ループの中のループで決まらなくなる場合は`None`
+    offset: Option<isize>,
+    /// 1回の繰り返しで開始位置のセルに加算する値。中にループや入力がある場合は`None`
+    delta: Option<u8>,
+    moves_right: bool,
+    moves_left: bool,
+    changes_cells: bool,
+}
+
+impl Shape {
+    fn of(body: &[Node]) -> Self {
+        let mut shape = Shape {
+            offset: Some(0),
+            delta: Some(0),
+            moves_right: false,
+            moves_left: false,
+            changes_cells: false,
+        };
+        let mut offset = 0isize;
+        for node in body {
+            match node {
+                Node::Command(Instruction::PointerIncrement) => {
+                    offset += 1;
+                    shape.moves_right = true;
+                }
+                Node::Command(Instruction::PointerDecrement) => {
+                    offset -= 1;
+                    shape.moves_left = true;
+                }
+                Node::Command(inst @ (Instruction::Increment | Instruction::Decrement)) => {
+                    shape.changes_cells = true;
+                    if offset == 0 {
+                        let value = if *inst == Instruction::Increment {
+                            1
+                        } else {
+                            255
+                        };
+                        shape.delta = shape.delta.map(|delta: u8| delta.wrapping_add(value));
+                    }
+                }
+                Node::Command(Instruction::GetChar) => shape.delta = None,
+                Node::Loop(body) => {
+                    shape.delta = None;
+                    if Shape::of(body).offset != Some(0) {
+                        shape.offset = None;
+                    }
+                }
+                _ => {}
+            }
+        }
+        shape.offset = shape.offset.map(|_| offset);
+        shape
+    }
+}
+
+fn count_commands(nodes: &[Node]) -> usize {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::Command(_) => 1,
+            Node::Loop(body) => count_commands(body) + 2,
+            Node::Comment(_) => 0,
+        })
+        .sum()
+}
+
+/// コメントの文字と空白を挟まずに隣り合う命令を探す。同じ行では最初の1つだけを警告する
+fn check_comments(source: &str, nodes: &[Node], warnings: &mut Vec<Warning>) {
+    let mut positions = Vec::new();
+    collect_comment_neighbors(source, nodes, &mut positions);
+    positions.sort_unstable();
+    positions.dedup();
+    let mut last_line = None;
+    for position in positions {
+        let (line, _) = parser::line_column(source, position);
+        if last_line == Some(line) {
+            continue;
+        }
+        last_line = Some(line);
+        let command = source[position..].chars().next().unwrap();
+        warnings.push(Warning {
+            lint: Lint::CommandInComment,
+            position,
+            message: format!(
+                "`{}` is next to comment text but is executed as a command",
+                command
+            ),
+        });
+    }
+}
+
+fn collect_comment_neighbors(source: &str, nodes: &[Node], positions: &mut Vec<usize>) {
+    for node in nodes {
+        match node {
+            Node::Comment(comment) => {
+                if let Some(c) = source[..comment.position].chars().next_back() {
+                    if parser::is_command(c) {
+                        positions.push(comment.position - c.len_utf8());
+                    }
+                }
+                let end = comment.position + comment.text.len();
+                if source[end..].chars().next().is_some_and(parser::is_command) {
+                    positions.push(end);
+                }
+            }
+            Node::Loop(body) => collect_comment_neighbors(source, body, positions),
+            Node::Command(_) => {}
+        }
+    }
+}
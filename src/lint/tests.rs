@@ -0,0 +1,70 @@
+use super::*;
+
+/// 警告の種類と`行:列`の組
+fn lints(source: &str) -> Vec<(Lint, String)> {
+    check(source)
+        .unwrap()
+        .into_iter()
+        .map(|warning| {
+            let (line, column) = parser::line_column(source, warning.position);
+            (warning.lint, format!("{}:{}", line, column))
+        })
+        .collect()
+}
+
+#[test]
+fn no_warnings() {
+    let hello_world = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+    assert_eq!(lints(hello_world), []);
+    assert_eq!(lints("read a line\n,[.,] echo it back"), []);
+}
+
+#[test]
+fn infinite_loop() {
+    assert_eq!(lints("+[]"), [(Lint::InfiniteLoop, "1:2".to_string())]);
+    assert_eq!(lints("+++[>+<]"), [(Lint::InfiniteLoop, "1:4".to_string())]);
+    // 値がわからなければ警告しない
+    assert_eq!(lints(",[]"), []);
+}
+
+#[test]
+fn dead_loop() {
+    assert_eq!(lints("[-]+"), [(Lint::DeadLoop, "1:1".to_string())]);
+    // ループを抜けた直後のセルは0
+    assert_eq!(lints(",[-]\n[.]"), [(Lint::DeadLoop, "2:1".to_string())]);
+    assert_eq!(lints(",[>+<-]>[<+>-]"), []);
+}
+
+#[test]
+fn unbalanced_loop() {
+    assert_eq!(
+        lints("+[->+<<]"),
+        [
+            (Lint::UnbalancedLoop, "1:2".to_string()),
+            (Lint::MoveLeftOfStart, "1:7".to_string())
+        ]
+    );
+    // 一方向に進むループは意図したもの
+    assert_eq!(lints(">+>+>+[<]"), []);
+}
+
+#[test]
+fn move_left_of_start() {
+    assert_eq!(
+        lints(">><<<+"),
+        [(Lint::MoveLeftOfStart, "1:5".to_string())]
+    );
+    // 位置がわからなくなった後は警告しない
+    assert_eq!(lints(",[>,]<<<<"), []);
+}
+
+#[test]
+fn command_in_comment() {
+    assert_eq!(
+        lints("Hello, world.\n+++ set x-y\n"),
+        [
+            (Lint::CommandInComment, "1:6".to_string()),
+            (Lint::CommandInComment, "2:10".to_string())
+        ]
+    );
+}
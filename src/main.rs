@@ -1,58 +1,1079 @@
-use std::io::Read;
+use std::{
+    io::{BufWriter, Read, Write},
+    os::unix::fs::PermissionsExt,
+    time::{Duration, Instant},
+};
 
-use clap::Parser;
+use anyhow::Context;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use optimizer::Optimization;
+use runtime::{
+    limits::Limits,
+    trace::{TraceFilter, TraceFormat, TraceOptions, Tracer},
+    vm::snapshot::Snapshot,
+    Backend,
+};
+use tape::{CellWidth, Eof, TapeOptions};
 
 mod assembler;
 mod compiler;
+mod debugger;
+mod elf;
+mod formatter;
+mod fuzz;
+mod generator;
+mod golden;
 mod instruction;
+mod ir;
+mod lint;
 mod optimizer;
 mod parser;
+mod process;
+mod profiler;
 mod runtime;
+mod tape;
 
 #[derive(Parser)]
-struct Args {
+#[clap(name = "bf", version, about = "Brainf*ck interpreter and compiler")]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a program
+    Run(Box<RunArgs>),
+    /// Compile a program into a file
+    Build(BuildArgs),
+    /// Print the optimized intermediate representation
+    Ir(IrArgs),
+    /// Print formatted source code
+    Fmt(FmtArgs),
+    /// Run a program in an interactive debugger. Optimizations are not applied
+    Debug(DebugArgs),
+    /// Check a program for syntax errors and report lint warnings
+    Check(CheckArgs),
+    /// Measure the running time of a program on each backend
+    Bench(BenchArgs),
+    /// Generate a program
+    Gen(GenArgs),
+    /// Run every program in a directory and compare its output with the expected output
+    Test(TestArgs),
+    /// Run random programs on several backends and report the smallest program whose output differs
+    Fuzz(FuzzArgs),
+}
+
+#[derive(clap::Args)]
+struct SourceArgs {
+    /// Source file. If neither this nor -e is provided, reads from stdin
+    filename: Option<String>,
+    /// Use the given code as the program instead of reading a file
+    #[clap(
+        short = 'e',
+        long = "expression",
+        value_name = "CODE",
+        conflicts_with = "filename"
+    )]
+    code: Option<String>,
+}
+
+#[derive(clap::Args)]
+struct InputArgs {
+    /// Give the contents of a file to the program as its input instead of stdin
+    #[clap(long, value_name = "PATH")]
+    input_file: Option<String>,
+    /// Give the text to the program as its input instead of stdin
+    #[clap(long, value_name = "TEXT", conflicts_with = "input_file")]
+    input: Option<String>,
+}
+
+#[derive(clap::Args)]
+struct OptimizeArgs {
     /// Enable specified optimizations
-    #[clap(short, long, value_delimiter = ',')]
+    #[clap(short = 'O', long, value_delimiter = ',')]
     optimize: Vec<Optimization>,
-    /// Print optimized intermediate representation
-    #[clap(long)]
-    print_optimized: bool,
-    /// Trace execution. Only works if not using native code generation
+}
+
+#[derive(clap::Args)]
+#[clap(mut_arg("filename", |arg| arg.help(
+    "Source file, or bytecode written by `bf build --emit bytecode`. \
+     If neither this nor -e is provided, reads from stdin",
+)))]
+struct RunArgs {
+    #[clap(flatten)]
+    optimize: OptimizeArgs,
+    /// How to run the program
+    #[clap(long, value_enum, default_value = "vm")]
+    backend: Backend,
+    /// Print every executed instruction and the tape
     #[clap(long)]
     trace: bool,
-    /// Enable native code generation
-    #[clap(long, default_value = "false")]
-    native_codegen: bool,
-    /// Input file. If not provided, reads from stdin
-    filename: Option<String>,
+    /// Write a trace of every executed instruction to a file
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["trace"])]
+    trace_file: Option<String>,
+    /// Format of --trace-file
+    #[clap(long, value_enum, default_value = "jsonl", requires = "trace_file")]
+    trace_format: TraceFormat,
+    /// Number of cells recorded on each side of the pointer in --trace-file
+    #[clap(long, default_value_t = 8, requires = "trace_file")]
+    trace_window: usize,
+    /// Record only every N-th matching step in --trace-file
+    #[clap(long, value_name = "N", default_value_t = 1, requires = "trace_file")]
+    trace_every: u64,
+    /// Record only these steps in --trace-file. Records all steps if not provided
+    #[clap(long, value_enum, value_delimiter = ',', requires = "trace_file")]
+    trace_filter: Vec<TraceFilter>,
+    /// Count executed instructions per loop and print a report to stderr after the program ends
+    #[clap(long, conflicts_with_all = ["trace", "trace_file"])]
+    profile: bool,
+    /// Write the profile in folded stack format (for flamegraph tools) to a file. Implies --profile
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["trace", "trace_file"])]
+    profile_folded: Option<String>,
+    /// Fuse common instruction sequences into superinstructions
+    #[clap(long, value_enum, value_delimiter = ',', conflicts_with_all = ["trace_file", "profile", "profile_folded"])]
+    fuse: Vec<compiler::vm::Fusion>,
+    /// Report to stderr which superinstructions would reduce the executed instructions instead of printing the output
+    #[clap(long, conflicts_with_all = ["trace", "trace_file", "profile", "profile_folded", "fuse"])]
+    fusion_report: bool,
+    /// Stop with an error after executing N instructions. With --backend native, limits loop iterations instead
+    #[clap(long, value_name = "N", conflicts_with_all = ["trace", "trace_file", "profile", "profile_folded", "fuse", "fusion_report"])]
+    max_steps: Option<u64>,
+    /// Stop with an error when the program tries to output more than N bytes
+    #[clap(long, value_name = "N", conflicts_with_all = ["trace", "trace_file", "profile", "profile_folded", "fuse", "fusion_report"])]
+    max_output: Option<usize>,
+    /// Grow the tape on demand up to N cells and stop with an error beyond that
+    #[clap(long, value_name = "N", conflicts_with_all = ["trace", "trace_file", "profile", "profile_folded", "fuse", "fusion_report"])]
+    max_tape: Option<usize>,
+    /// Stop with an error after running for the given number of seconds
+    #[clap(long, value_name = "SECONDS", conflicts_with_all = ["trace", "trace_file", "profile", "profile_folded", "fuse", "fusion_report"])]
+    timeout: Option<f64>,
+    /// Save the state of the VM to a file after executing --snapshot-after instructions and exit
+    #[clap(long, value_name = "FILE", requires = "snapshot_after", conflicts_with_all = ["trace", "trace_file", "profile", "profile_folded", "fuse", "fusion_report", "max_steps", "max_output", "max_tape", "timeout"])]
+    snapshot: Option<String>,
+    /// Number of instructions to execute before saving --snapshot
+    #[clap(long, value_name = "N", requires = "snapshot")]
+    snapshot_after: Option<u64>,
+    /// Resume from a file saved with --snapshot. The same input must be given; the part already read is skipped
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["trace", "trace_file", "profile", "profile_folded", "fuse", "fusion_report", "max_steps", "max_output", "max_tape", "timeout"])]
+    resume: Option<String>,
+    /// Number of iterations after which --backend tiered compiles a loop [default: 1000]
+    #[clap(long, value_name = "N")]
+    jit_threshold: Option<u64>,
+    /// Print how many loops --backend tiered compiled to stderr after the program ends
+    #[clap(long)]
+    tiered_stats: bool,
+    #[clap(flatten)]
+    input: InputArgs,
+    #[clap(flatten)]
+    source: SourceArgs,
+}
+
+impl RunArgs {
+    /// バックエンドが対応していないオプションを指定していれば、そのオプションの名前を返す
+    fn unsupported_option(&self) -> Option<&'static str> {
+        use Backend::*;
+        let options: [(&str, bool, &[Backend]); 12] = [
+            ("--trace", self.trace, &[Vm, Closure]),
+            ("--trace-file", self.trace_file.is_some(), &[Vm, Native]),
+            (
+                "--profile",
+                self.profile || self.profile_folded.is_some(),
+                &[Vm],
+            ),
+            ("--fuse", !self.fuse.is_empty(), &[Vm, Closure]),
+            ("--fusion-report", self.fusion_report, &[Vm]),
+            ("--max-steps", self.max_steps.is_some(), &[Vm, Native]),
+            ("--max-output", self.max_output.is_some(), &[Vm]),
+            ("--max-tape", self.max_tape.is_some(), &[Vm]),
+            ("--timeout", self.timeout.is_some(), &[Vm]),
+            (
+                "--snapshot",
+                self.snapshot.is_some() || self.resume.is_some(),
+                &[Vm],
+            ),
+            ("--jit-threshold", self.jit_threshold.is_some(), &[Tiered]),
+            ("--tiered-stats", self.tiered_stats, &[Tiered]),
+        ];
+        options
+            .into_iter()
+            .find(|(_, used, backends)| *used && !backends.contains(&self.backend))
+            .map(|(name, _, _)| name)
+    }
+}
+
+#[derive(clap::Args)]
+struct BuildArgs {
+    #[clap(flatten)]
+    optimize: OptimizeArgs,
+    /// Output format
+    #[clap(long, value_enum)]
+    emit: Emit,
+    /// Output file
+    #[clap(short, long)]
+    output: String,
+    /// Number of cells on the tape. Only used by --emit c/rust/wat/wasm/bytecode [default: 30000]
+    #[clap(long)]
+    tape_size: Option<usize>,
+    /// Bit width of a cell. Only used by --emit c/rust/wat/wasm/bytecode [default: 8]
+    #[clap(long, value_enum)]
+    cell_width: Option<CellWidth>,
+    /// Value stored by `,` at the end of input. Only used by --emit c/rust/wat/wasm/bytecode [default: zero]
+    #[clap(long, value_enum)]
+    eof: Option<Eof>,
+    /// Name of the function exported by --emit obj [default: bf_main]
+    #[clap(long)]
+    symbol: Option<String>,
+    /// Fuse common instruction sequences into superinstructions. Only used by --emit bytecode
+    #[clap(long, value_enum, value_delimiter = ',')]
+    fuse: Vec<compiler::vm::Fusion>,
+    #[clap(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Emit {
+    /// Standalone x86_64 ELF executable which does not depend on libc
+    Exe,
+    /// x86_64 ELF relocatable object exporting `void <symbol>(uint8_t *tape, void *ctx)`,
+    /// which calls `void bf_putchar(void *ctx, int c)` and `int bf_getchar(void *ctx)`
+    Obj,
+    /// C source code
+    C,
+    /// Rust source code (main.rs)
+    Rust,
+    /// WebAssembly text format
+    Wat,
+    /// WebAssembly binary format
+    Wasm,
+    /// VM bytecode, which can be run with `bf run`
+    Bytecode,
+    /// Minified Brainf*ck source code without comments. Optimized instructions are turned back into loops
+    Bf,
+}
+
+impl BuildArgs {
+    /// 出力形式が使わないオプションを指定していれば、そのオプションの名前を返す
+    fn unsupported_option(&self) -> Option<&'static str> {
+        use Emit::*;
+        let tape = &[C, Rust, Wat, Wasm, Bytecode][..];
+        let options: [(&str, bool, &[Emit]); 5] = [
+            ("--tape-size", self.tape_size.is_some(), tape),
+            ("--cell-width", self.cell_width.is_some(), tape),
+            ("--eof", self.eof.is_some(), tape),
+            ("--symbol", self.symbol.is_some(), &[Obj]),
+            ("--fuse", !self.fuse.is_empty(), &[Bytecode]),
+        ];
+        options
+            .into_iter()
+            .find(|(_, used, emits)| *used && !emits.contains(&self.emit))
+            .map(|(name, _, _)| name)
+    }
+}
+
+#[derive(clap::Args)]
+struct IrArgs {
+    #[clap(flatten)]
+    optimize: OptimizeArgs,
+    /// Omit the annotations (VM position, cost, pointer offset and source span) after each instruction
+    #[clap(long)]
+    plain: bool,
+    /// Read the input as textual IR printed by `bf ir` instead of Brainf*ck source, and apply -O to it
+    #[clap(long)]
+    from_ir: bool,
+    #[clap(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(clap::Args)]
+struct FmtArgs {
+    /// Maximum width of a line. Comments are not wrapped
+    #[clap(long, default_value_t = 80)]
+    width: usize,
+    /// Number of spaces per loop level
+    #[clap(long, default_value_t = 2)]
+    indent: usize,
+    /// Do not separate runs of the same command with spaces
+    #[clap(long)]
+    no_group: bool,
+    #[clap(flatten)]
+    source: SourceArgs,
+}
+
+/// 標準入力はデバッガのコマンドを読むので、プログラムは標準入力から読めない
+#[derive(clap::Args)]
+#[clap(mut_arg("filename", |arg| arg.help("Source file").required_unless_present("code")))]
+#[clap(mut_arg("input_file", |arg| arg.help(
+    "Give the contents of a file to the program as its input. \
+     The input is empty if neither this nor --input is provided",
+)))]
+#[clap(mut_arg("input", |arg| arg.help("Give the text to the program as its input")))]
+struct DebugArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+    #[clap(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(clap::Args)]
+struct CheckArgs {
+    /// Do not report these warnings
+    #[clap(long, value_enum, value_delimiter = ',')]
+    allow: Vec<lint::Lint>,
+    /// Exit with an error if there are any warnings
+    #[clap(long)]
+    deny_warnings: bool,
+    #[clap(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(clap::Args)]
+#[clap(mut_arg("filename", |arg| arg
+    .help("Source file. The program's output is discarded and its input is empty")
+    .required_unless_present("code")))]
+struct BenchArgs {
+    #[clap(flatten)]
+    optimize: OptimizeArgs,
+    /// Number of runs on each backend
+    #[clap(long, default_value_t = 5)]
+    runs: u32,
+    /// Backends to measure. Measures all backends if not provided
+    #[clap(long, value_enum, value_delimiter = ',')]
+    backend: Vec<Backend>,
+    #[clap(flatten)]
+    source: SourceArgs,
+}
+
+#[derive(clap::Args)]
+struct GenArgs {
+    #[clap(subcommand)]
+    kind: Generate,
+}
+
+#[derive(clap::Args)]
+struct TestArgs {
+    /// Directory containing programs NAME.bf, their expected output NAME.out and optionally their input NAME.in
+    dir: String,
+    /// Backends to run each program on. Uses all backends if not provided
+    #[clap(long, value_enum, value_delimiter = ',')]
+    backend: Vec<Backend>,
+    /// Optimizations to run each program with. Uses all presets if not provided
+    #[clap(long, value_enum, value_delimiter = ',')]
+    preset: Vec<golden::Preset>,
+    /// Fail a run which does not finish in the given number of seconds
+    #[clap(long, value_name = "SECONDS", default_value_t = 60.0)]
+    timeout: f64,
+}
+
+#[derive(Subcommand)]
+enum Generate {
+    /// Generate a compact program which prints the given text
+    Text {
+        /// Text to print. If not provided, reads from stdin
+        text: Option<String>,
+    },
+    /// Generate a random program which always terminates and stays in the tape
+    Random {
+        /// Seed of the random numbers. Chosen from the current time if not provided
+        #[clap(long)]
+        seed: Option<u64>,
+        #[clap(flatten)]
+        random: RandomArgs,
+    },
+}
+
+#[derive(clap::Args)]
+struct RandomArgs {
+    /// Number of operations (runs of `+`, `-`, `>` or `<`, `.`, `,` and loops) in a program, not counting loop bodies
+    #[clap(long, default_value_t = 50)]
+    length: usize,
+    /// Number of cells a program uses
+    #[clap(long, default_value_t = 300)]
+    cells: usize,
+    /// Maximum nesting depth of loops, not counting `[-]`
+    #[clap(long, default_value_t = 2)]
+    depth: usize,
+}
+
+impl RandomArgs {
+    fn options(&self) -> generator::random::RandomOptions {
+        generator::random::RandomOptions {
+            length: self.length,
+            cells: self.cells,
+            depth: self.depth,
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct FuzzArgs {
+    /// Number of programs to run
+    #[clap(long, default_value_t = 1000)]
+    runs: u64,
+    /// Seed of the first program. The following programs use the next seeds.
+    /// Chosen from the current time if not provided
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Fail a run which does not finish in the given number of seconds
+    #[clap(long, value_name = "SECONDS", default_value_t = 10.0)]
+    timeout: f64,
+    #[clap(flatten)]
+    random: RandomArgs,
 }
 
 fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+    match Cli::parse().command {
+        Command::Run(args) => run(*args),
+        Command::Build(args) => build(args),
+        Command::Ir(args) => {
+            let source = args.source.read()?;
+            let source = std::str::from_utf8(&source).context("the input is not valid UTF-8")?;
+            let program = if args.from_ir {
+                ir::parse(source)?
+            } else {
+                parser::parse(source)?
+            };
+            let optimized = optimizer::optimize(program, &args.optimize.optimize);
+            if args.plain {
+                print!("{}", ir::print(&optimized));
+            } else {
+                // 手で書いた中間表現にはソースコードの位置がない
+                let source = (!args.from_ir).then_some(source);
+                print!("{}", ir::print_annotated(&optimized, source));
+            }
+            Ok(())
+        }
+        Command::Fmt(args) => {
+            let source = args.source.read()?;
+            let source =
+                std::str::from_utf8(&source).context("the source code is not valid UTF-8")?;
+            let options = formatter::FormatOptions {
+                width: args.width,
+                indent: args.indent,
+                group: !args.no_group,
+            };
+            print!("{}", formatter::format(source, &options)?);
+            Ok(())
+        }
+        Command::Debug(args) => {
+            let source = args.source.read()?;
+            let source = String::from_utf8(source).context("the source code is not valid UTF-8")?;
+            debugger::run(&source, args.input.read()?.unwrap_or_default())
+        }
+        Command::Check(args) => check(args),
+        Command::Test(args) => test(args),
+        Command::Bench(args) => bench(args),
+        Command::Gen(args) => match args.kind {
+            Generate::Text { text } => {
+                let text = match text {
+                    Some(text) => text.into_bytes(),
+                    None => read_source(None, None)?,
+                };
+                let code = generator::text(&text);
+                // 生成したプログラムをVMで実行して確かめる
+                let output =
+                    runtime::vm::run_captured(&compiler::vm::compile(&parser::parse(&code)?));
+                if output != text {
+                    anyhow::bail!(
+                        "the generated program prints {:?} instead of the text",
+                        String::from_utf8_lossy(&output)
+                    );
+                }
+                println!("{}", code);
+                Ok(())
+            }
+            Generate::Random { seed, random } => {
+                let seed = seed.unwrap_or_else(time_seed);
+                let mut rng = generator::random::Rng::new(seed);
+                println!(
+                    "{}",
+                    generator::random::program(&mut rng, &random.options())
+                );
+                Ok(())
+            }
+        },
+        Command::Fuzz(args) => fuzz(args),
+    }
+}
+
+/// 引数の組み合わせが正しくないことを、clapと同じ形式で表示して終了する
+fn invalid_arguments(command: &str, message: String) -> ! {
+    let mut cli = Cli::command();
+    // 使い方の表示に`bf run`のような名前を使うため、先に組み立てておく
+    cli.build();
+    let subcommand = cli.find_subcommand_mut(command).unwrap();
+    subcommand
+        .error(clap::error::ErrorKind::ArgumentConflict, message)
+        .exit()
+}
+
+impl SourceArgs {
+    fn read(&self) -> anyhow::Result<Vec<u8>> {
+        read_source(self.filename.as_deref(), self.code.as_deref())
+    }
+}
+
+impl InputArgs {
+    /// `--input-file`か`--input`で与えた入力。どちらもなければ`None`
+    fn read(&self) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(path) = &self.input_file {
+            let data = std::fs::read(path).with_context(|| format!("failed to read {}", path))?;
+            Ok(Some(data))
+        } else {
+            Ok(self.input.as_ref().map(|text| text.clone().into_bytes()))
+        }
+    }
+}
 
-    let input = if let Some(filename) = args.filename {
-        std::fs::read_to_string(filename)?
+/// `-e`のコード、ファイル、標準入力の順にソースコードを探して読み込む
+fn read_source(filename: Option<&str>, code: Option<&str>) -> anyhow::Result<Vec<u8>> {
+    if let Some(code) = code {
+        Ok(code.as_bytes().to_vec())
+    } else if let Some(filename) = filename {
+        std::fs::read(filename).with_context(|| format!("failed to read {}", filename))
     } else {
-        let mut input = String::new();
-        std::io::stdin().read_to_string(&mut input)?;
-        input
+        let mut input = Vec::new();
+        std::io::stdin().read_to_end(&mut input)?;
+        Ok(input)
+    }
+}
+
+fn parse(source: &[u8]) -> anyhow::Result<Vec<instruction::Instruction>> {
+    let source = std::str::from_utf8(source).context("the source code is not valid UTF-8")?;
+    parser::parse(source)
+}
+
+fn run(args: RunArgs) -> anyhow::Result<()> {
+    if let Some(option) = args.unsupported_option() {
+        let backend = args.backend.to_possible_value().unwrap();
+        invalid_arguments(
+            "run",
+            format!(
+                "{} cannot be used with --backend {}",
+                option,
+                backend.get_name()
+            ),
+        );
+    }
+
+    let source = args.source.read()?;
+    let input = args
+        .input
+        .read()?
+        .map_or(runtime::Input::Stdin, runtime::Input::bytes);
+    if compiler::bytecode::is_bytecode(&source) {
+        return run_bytecode(&args, &source, input);
+    }
+    let program = parse(&source)?;
+    let optimized = optimizer::optimize(program, &args.optimize.optimize);
+
+    if args.profile || args.profile_folded.is_some() {
+        let profile = profiler::run(&optimized, input);
+        eprint!("{}", profile.report());
+        if let Some(output) = args.profile_folded {
+            std::fs::write(&output, profile.folded())
+                .with_context(|| format!("failed to write {}", output))?;
+        }
+        return Ok(());
+    }
+
+    if args.fusion_report {
+        eprint!("{}", profiler::fusion_report(&optimized, input));
+        return Ok(());
+    }
+
+    if let Some(trace_file) = args.trace_file {
+        let file = std::fs::File::create(&trace_file)
+            .with_context(|| format!("failed to create {}", trace_file))?;
+        let options = TraceOptions {
+            format: args.trace_format,
+            window: args.trace_window,
+            every: args.trace_every,
+            filters: args.trace_filter,
+        };
+        let instructions = compiler::vm::compile(&optimized);
+        let mut tracer = Tracer::new(BufWriter::new(file), options, &instructions)?;
+        if args.backend == Backend::Native {
+            let mut trace = runtime::native::NativeTrace::new(tracer);
+            let options = compiler::x86_64::CompileOptions {
+                trace: Some((trace.hook(), trace.context())),
+                ..native_io_options()
+            };
+            let compiled = compiler::x86_64::compile(&optimized, options);
+            with_native_io(input, || runtime::native::run_traced(&compiled, &mut trace))??;
+            trace.finish()?;
+        } else {
+            runtime::vm::run_traced(&instructions, input, &mut tracer)?;
+            tracer.finish()?;
+        }
+        return Ok(());
+    }
+
+    if args.snapshot.is_some() || args.resume.is_some() {
+        let resume = match &args.resume {
+            Some(path) => {
+                let file = std::fs::File::open(path)
+                    .with_context(|| format!("failed to open {}", path))?;
+                Some(Snapshot::read(&mut std::io::BufReader::new(file))?)
+            }
+            None => None,
+        };
+        let compiled = compiler::vm::compile(&optimized);
+        let snapshot =
+            runtime::vm::run_checkpointed(&compiled, input, resume.as_ref(), args.snapshot_after)?;
+        if let (Some(snapshot), Some(path)) = (snapshot, args.snapshot) {
+            let mut file = std::fs::File::create(&path)
+                .with_context(|| format!("failed to create {}", path))?;
+            snapshot.write(&mut file)?;
+        }
+        return Ok(());
+    }
+
+    let limits = Limits {
+        max_steps: args.max_steps,
+        max_output: args.max_output,
+        max_tape: args.max_tape,
+        timeout: args.timeout.map(Duration::from_secs_f64),
     };
 
-    let program = parser::parse(&input)?;
-    let optimized = optimizer::optimize(program, &args.optimize);
+    match args.backend {
+        Backend::Native => {
+            if let Some(max_iterations) = limits.max_steps {
+                let mut fuel = compiler::x86_64::Fuel {
+                    remaining: 0,
+                    pointer: std::ptr::null_mut(),
+                };
+                // テープの範囲は実行するときに設定する
+                let mut bounds = compiler::x86_64::Bounds {
+                    start: std::ptr::null_mut(),
+                    len: 0,
+                    reach: 0,
+                };
+                // 生成したコードが書き換えるので、同じ生ポインタだけを通して読み書きする
+                let fuel: *mut _ = &mut fuel;
+                let bounds: *mut _ = &mut bounds;
+                let options = compiler::x86_64::CompileOptions {
+                    fuel: Some(fuel),
+                    bounds: Some(bounds),
+                    ..native_io_options()
+                };
+                let compiled = compiler::x86_64::compile(&optimized, options);
+                with_native_io(input, || unsafe {
+                    runtime::native::run_with_fuel(&compiled, fuel, bounds, max_iterations)
+                })??;
+            } else {
+                let compiled = compiler::x86_64::compile(&optimized, native_io_options());
+                with_native_io(input, || runtime::native::run(&compiled))?;
+            }
+        }
+        Backend::Tiered => {
+            let threshold = args
+                .jit_threshold
+                .unwrap_or(runtime::tiered::DEFAULT_THRESHOLD);
+            let stats = runtime::tiered::run(&optimized, input, threshold)?;
+            if args.tiered_stats {
+                eprintln!(
+                    "compiled loops: {}, native entries: {}, vm steps: {}",
+                    stats.compiled_loops, stats.native_entries, stats.vm_steps
+                );
+            }
+        }
+        Backend::Vm
+            if limits.max_steps.is_some()
+                || limits.max_output.is_some()
+                || limits.max_tape.is_some()
+                || limits.timeout.is_some() =>
+        {
+            let compiled = compiler::vm::compile(&optimized);
+            runtime::vm::run_limited(&compiled, input, &limits)?;
+        }
+        Backend::Vm | Backend::Closure => {
+            let compiled = compiler::vm::fuse(&compiler::vm::compile(&optimized), &args.fuse);
+            run_vm(
+                &compiled,
+                TapeOptions::default(),
+                input,
+                args.backend,
+                args.trace,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `with_native_io`の中で実行するネイティブコードの入出力関数
+fn native_io_options() -> compiler::x86_64::CompileOptions {
+    compiler::x86_64::CompileOptions {
+        putchar: Some(runtime::native::io_putchar),
+        getchar: Some(runtime::native::io_getchar),
+        ..Default::default()
+    }
+}
+
+/// `native_io_options`でコンパイルしたコードを`f`で実行し、`input`を読んで標準出力に書くようにする
+fn with_native_io<R>(mut input: runtime::Input, f: impl FnOnce() -> R) -> anyhow::Result<R> {
+    let mut output = BufWriter::new(std::io::stdout());
+    let mut io = runtime::native::NativeIo::new(&mut input, &mut output, Eof::Zero);
+    let result = io.attach(f);
+    if let Some(error) = io.error {
+        return Err(error.into());
+    }
+    output.flush()?;
+    Ok(result)
+}
 
-    if args.print_optimized {
-        dbg!(&optimized);
+/// `bf build --emit bytecode`で出力したファイルをVMで実行する。
+/// 構文解析と最適化は済んでいるので、それらに関するオプションは受け付けない
+fn run_bytecode(args: &RunArgs, bytecode: &[u8], input: runtime::Input) -> anyhow::Result<()> {
+    let unsupported = [
+        ("-O", !args.optimize.optimize.is_empty()),
+        ("--fuse", !args.fuse.is_empty()),
+        (
+            "--backend",
+            !matches!(args.backend, Backend::Vm | Backend::Closure),
+        ),
+        ("--profile", args.profile || args.profile_folded.is_some()),
+        ("--fusion-report", args.fusion_report),
+        ("--trace-file", args.trace_file.is_some()),
+        (
+            "--snapshot",
+            args.snapshot.is_some() || args.resume.is_some(),
+        ),
+        (
+            "--max-steps",
+            args.max_steps.is_some()
+                || args.max_output.is_some()
+                || args.max_tape.is_some()
+                || args.timeout.is_some(),
+        ),
+    ];
+    if let Some((option, _)) = unsupported.iter().find(|(_, used)| *used) {
+        invalid_arguments(
+            "run",
+            format!("{} cannot be used when running bytecode", option),
+        );
     }
 
-    if args.native_codegen {
-        let compiled = compiler::x86_64::compile(&optimized, Default::default());
-        runtime::native::run(&compiled);
+    let bytecode = compiler::bytecode::decode(bytecode)?;
+    if bytecode.tape.cell_width != CellWidth::U8 {
+        anyhow::bail!(
+            "the VM only supports 8-bit cells, but the bytecode uses {}-bit cells",
+            bytecode.tape.cell_width.bits()
+        );
+    }
+    run_vm(
+        &bytecode.instructions,
+        bytecode.tape,
+        input,
+        args.backend,
+        args.trace,
+    )
+}
+
+fn run_vm(
+    instructions: &[runtime::vm::VMInstruction],
+    tape: TapeOptions,
+    input: runtime::Input,
+    backend: Backend,
+    trace: bool,
+) -> anyhow::Result<()> {
+    match backend {
+        Backend::Closure if trace => {
+            runtime::closure::compile_traced(instructions).run(tape, input)?
+        }
+        Backend::Closure => runtime::closure::compile(instructions).run(tape, input)?,
+        _ => runtime::vm::run_with_tape(instructions, tape, input, trace)?,
+    }
+    Ok(())
+}
+
+fn build(args: BuildArgs) -> anyhow::Result<()> {
+    if let Some(option) = args.unsupported_option() {
+        let emit = args.emit.to_possible_value().unwrap();
+        invalid_arguments(
+            "build",
+            format!("{} cannot be used with --emit {}", option, emit.get_name()),
+        );
+    }
+    // バイトコードを実行するVMは8ビットのセルにしか対応していない
+    if let Some(cell_width) = args.cell_width.filter(|_| args.emit == Emit::Bytecode) {
+        if cell_width != CellWidth::U8 {
+            invalid_arguments(
+                "build",
+                format!(
+                    "--emit bytecode only supports 8-bit cells, but --cell-width {} was given",
+                    cell_width.bits()
+                ),
+            );
+        }
+    }
+
+    let program = parse(&args.source.read()?)?;
+    let output = args.output;
+    let tape = TapeOptions {
+        size: args.tape_size.unwrap_or(tape::DEFAULT_TAPE_SIZE),
+        cell_width: args.cell_width.unwrap_or_default(),
+        eof: args.eof.unwrap_or_default(),
+    };
+    let optimized =
+        optimizer::optimize_for_cell_width(program, &args.optimize.optimize, tape.cell_width);
+    let contents = match args.emit {
+        Emit::Exe => {
+            let compiled = compiler::x86_64::compile_relocatable(&optimized);
+            elf::executable::build(&compiled)
+        }
+        Emit::Obj => {
+            let compiled = compiler::x86_64::compile_relocatable(&optimized);
+            let symbol = args.symbol.as_deref().unwrap_or("bf_main");
+            elf::object::build(&compiled, symbol)
+        }
+        Emit::C => compiler::c::compile(&optimized, tape).into_bytes(),
+        Emit::Rust => compiler::rust::compile(&optimized, tape).into_bytes(),
+        Emit::Wat => compiler::wasm::compile(&optimized, tape).wat().into_bytes(),
+        Emit::Wasm => compiler::wasm::compile(&optimized, tape).wasm(),
+        Emit::Bytecode => {
+            let compiled = compiler::vm::fuse(&compiler::vm::compile(&optimized), &args.fuse);
+            compiler::bytecode::encode(&compiled, tape)
+        }
+        Emit::Bf => compiler::bf::compile(&optimized)?.into_bytes(),
+    };
+    std::fs::write(&output, contents).with_context(|| format!("failed to write {}", output))?;
+    if args.emit == Emit::Exe {
+        std::fs::set_permissions(&output, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+fn check(args: CheckArgs) -> anyhow::Result<()> {
+    let source = args.source.read()?;
+    let source = std::str::from_utf8(&source).context("the source code is not valid UTF-8")?;
+    let warnings = lint::check(source)?
+        .into_iter()
+        .filter(|warning| !args.allow.contains(&warning.lint))
+        .collect::<Vec<_>>();
+    for warning in &warnings {
+        let (line, column) = parser::line_column(source, warning.position);
+        println!(
+            "{}:{}: warning[{}]: {}",
+            line,
+            column,
+            warning.lint.name(),
+            warning.message
+        );
+    }
+    let summary = match warnings.len() {
+        0 => "ok".to_string(),
+        1 => "1 warning".to_string(),
+        n => format!("{} warnings", n),
+    };
+    if args.deny_warnings && !warnings.is_empty() {
+        anyhow::bail!(summary);
+    }
+    println!("{}", summary);
+    Ok(())
+}
+
+fn test(args: TestArgs) -> anyhow::Result<()> {
+    let cases = golden::load(std::path::Path::new(&args.dir))?;
+    if cases.is_empty() {
+        anyhow::bail!("no programs found in {}", args.dir);
+    }
+    let backends = if args.backend.is_empty() {
+        Backend::value_variants().to_vec()
     } else {
-        let compiled = compiler::vm::compile(&optimized);
-        runtime::vm::run(&compiled, args.trace);
+        args.backend
+    };
+    let presets = if args.preset.is_empty() {
+        golden::Preset::value_variants().to_vec()
+    } else {
+        args.preset
+    };
+    // 各プログラムはこの実行ファイルの`bf run`で実行する
+    let bf = std::env::current_exe()?;
+    let timeout = Duration::from_secs_f64(args.timeout);
+
+    let mut failed = 0;
+    let mut total = 0;
+    for case in &cases {
+        for &backend in &backends {
+            for &preset in presets.iter().filter(|preset| preset.supports(backend)) {
+                total += 1;
+                let outcome = golden::run(&bf, case, backend, preset, timeout)?;
+                let status = match &outcome {
+                    golden::Outcome::Passed => "ok".to_string(),
+                    golden::Outcome::Failed(message) => {
+                        failed += 1;
+                        format!("FAILED: {}", message)
+                    }
+                };
+                println!(
+                    "{} [{}, {}] ... {}",
+                    case.name,
+                    backend.to_possible_value().unwrap().get_name(),
+                    preset.name(),
+                    status
+                );
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} runs failed", failed, total);
+    }
+    println!("{} runs passed", total);
+    Ok(())
+}
+
+fn fuzz(args: FuzzArgs) -> anyhow::Result<()> {
+    let first = args.seed.unwrap_or_else(time_seed);
+    let options = args.random.options();
+    // 各プログラムはこの実行ファイルの`bf run`で実行する
+    let bf = std::env::current_exe()?;
+    let timeout = Duration::from_secs_f64(args.timeout);
+    let diverges = |case: &fuzz::Case| -> anyhow::Result<bool> {
+        Ok(matches!(
+            fuzz::check(&bf, case, timeout)?,
+            fuzz::Verdict::Diverged(_)
+        ))
+    };
+
+    println!("seeds {} to {}", first, first.wrapping_add(args.runs - 1));
+    for seed in (0..args.runs).map(|i| first.wrapping_add(i)) {
+        let case = fuzz::Case::random(seed, &options);
+        match fuzz::check(&bf, &case, timeout)? {
+            fuzz::Verdict::Same => continue,
+            fuzz::Verdict::Invalid(behavior) => anyhow::bail!(
+                "the program generated from seed {} did not finish on the unoptimized VM: {:?}",
+                seed,
+                behavior
+            ),
+            fuzz::Verdict::Diverged(_) => {}
+        }
+        println!("seed {}: found a difference, shrinking", seed);
+        let shrunk = fuzz::shrink(case, diverges)?;
+        let fuzz::Verdict::Diverged(behaviors) = fuzz::check(&bf, &shrunk, timeout)? else {
+            anyhow::bail!("the shrunk program no longer behaves differently");
+        };
+        println!("program: {}", shrunk.code);
+        println!("input: \"{}\"", shrunk.input.escape_ascii());
+        for ((backend, preset), behavior) in fuzz::CONFIGS.iter().zip(behaviors) {
+            let description = match behavior {
+                fuzz::Behavior::Finished(output) => format!("output \"{}\"", output.escape_ascii()),
+                fuzz::Behavior::Failed(message) => message,
+                fuzz::Behavior::TimedOut => format!("timed out after {:?}", timeout),
+            };
+            println!(
+                "  --backend {} -O {}: {}",
+                backend.to_possible_value().unwrap().get_name(),
+                preset.name(),
+                description
+            );
+        }
+        anyhow::bail!("found a program which behaves differently");
     }
+    println!("{} programs behaved the same", args.runs);
+    Ok(())
+}
 
+/// 現在時刻から乱数のシードを作る
+fn time_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+fn bench(args: BenchArgs) -> anyhow::Result<()> {
+    let program = parse(&args.source.read()?)?;
+    let optimized = optimizer::optimize(program, &args.optimize.optimize);
+    let backends = if args.backend.is_empty() {
+        Backend::value_variants().to_vec()
+    } else {
+        args.backend
+    };
+
+    for backend in backends {
+        let mut times = Vec::new();
+        for _ in 0..args.runs {
+            let started = Instant::now();
+            // コンパイルも含めて計る
+            with_null_stdio(|| {
+                match backend {
+                    Backend::Native => {
+                        let compiled = compiler::x86_64::compile(&optimized, Default::default());
+                        runtime::native::run(&compiled);
+                    }
+                    Backend::Tiered => {
+                        runtime::tiered::run(
+                            &optimized,
+                            runtime::Input::Stdin,
+                            runtime::tiered::DEFAULT_THRESHOLD,
+                        )?;
+                    }
+                    Backend::Vm => runtime::vm::run(&compiler::vm::compile(&optimized), false)?,
+                    Backend::Closure => {
+                        runtime::closure::compile(&compiler::vm::compile(&optimized))
+                            .run(TapeOptions::default(), runtime::Input::Stdin)?
+                    }
+                }
+                Ok(())
+            })?;
+            times.push(started.elapsed());
+        }
+        let min = times.iter().min().copied().unwrap_or_default();
+        let mean = times.iter().sum::<Duration>() / args.runs.max(1);
+        println!(
+            "{:<8} min {:>10.3?}  mean {:>10.3?}",
+            backend.to_possible_value().unwrap().get_name(),
+            min,
+            mean
+        );
+    }
     Ok(())
 }
+
+/// 標準入出力を`/dev/null`につなぎ替えて`f`を実行する
+fn with_null_stdio(f: impl FnOnce() -> anyhow::Result<()>) -> anyhow::Result<()> {
+    let null = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")?;
+    std::io::stdout().flush()?;
+    // `f`がエラーを返したりパニックしたりしても、戻すときに元の標準入出力につなぎ直す
+    let _saved = SavedStdio::redirect(std::os::fd::AsRawFd::as_raw_fd(&null));
+    f()
+}
+
+/// つなぎ替える前の標準入出力。ドロップすると元に戻す
+struct SavedStdio {
+    stdin: i32,
+    stdout: i32,
+}
+
+impl SavedStdio {
+    fn redirect(fd: i32) -> Self {
+        unsafe {
+            let saved = Self {
+                stdin: libc::dup(0),
+                stdout: libc::dup(1),
+            };
+            libc::dup2(fd, 0);
+            libc::dup2(fd, 1);
+            saved
+        }
+    }
+}
+
+impl Drop for SavedStdio {
+    fn drop(&mut self) {
+        // ネイティブコードはlibcの入出力関数を使うので、そのバッファも書き出す
+        let _ = std::io::stdout().flush();
+        unsafe {
+            libc::fflush(std::ptr::null_mut());
+            libc::dup2(self.stdin, 0);
+            libc::dup2(self.stdout, 1);
+            libc::close(self.stdin);
+            libc::close(self.stdout);
+        }
+    }
+}
@@ -1,6 +1,9 @@
-use crate::instruction::Instruction;
+use crate::{instruction::Instruction, tape::CellWidth};
 
-pub fn optimize_consecutive_inc_dec(instructions: &[Instruction]) -> Vec<Instruction> {
+pub fn optimize_consecutive_inc_dec(
+    instructions: &[Instruction],
+    cell_width: CellWidth,
+) -> Vec<Instruction> {
     let mut i = 0;
     let mut optimized = Vec::new();
     while i < instructions.len() {
@@ -13,7 +16,7 @@ pub fn optimize_consecutive_inc_dec(instructions: &[Instruction]) -> Vec<Instruc
                     count += 1;
                     i += 1;
                 }
-                optimized.push(Instruction::Add(count));
+                fold(count, cell_width, Instruction::Add, &mut optimized);
             }
             // 連続するDecrementをSubtractに変換
             Instruction::Decrement if instructions.get(i + 1) == Some(&Instruction::Decrement) => {
@@ -22,7 +25,7 @@ pub fn optimize_consecutive_inc_dec(instructions: &[Instruction]) -> Vec<Instruc
                     count += 1;
                     i += 1;
                 }
-                optimized.push(Instruction::Subtract(count));
+                fold(count, cell_width, Instruction::Subtract, &mut optimized);
             }
             // 連続するPointerIncrementをPointerAddに変換
             Instruction::PointerIncrement
@@ -48,7 +51,7 @@ pub fn optimize_consecutive_inc_dec(instructions: &[Instruction]) -> Vec<Instruc
             }
             // ループの中身を最適化
             Instruction::Loop(loop_instructions) => {
-                let optimized_loop = optimize_consecutive_inc_dec(loop_instructions);
+                let optimized_loop = optimize_consecutive_inc_dec(loop_instructions, cell_width);
                 optimized.push(Instruction::Loop(optimized_loop));
             }
             inst => optimized.push(inst.clone()),
@@ -59,3 +62,24 @@ pub fn optimize_consecutive_inc_dec(instructions: &[Instruction]) -> Vec<Instruc
 
     optimized
 }
+
+/// `count`回の`+`か`-`を、`make`で作る命令にまとめる
+fn fold(
+    count: usize,
+    cell_width: CellWidth,
+    make: fn(u8) -> Instruction,
+    optimized: &mut Vec<Instruction>,
+) {
+    if cell_width == CellWidth::U8 {
+        // セルの値は256で一周するので、回数も同じように回り込ませる
+        optimized.push(make(count as u8));
+        return;
+    }
+    // 広いセルでは回り込まないので、オペランドに収まる255回ずつに分ける
+    let mut remaining = count;
+    while remaining > 0 {
+        let n = remaining.min(u8::MAX as usize);
+        optimized.push(make(n as u8));
+        remaining -= n;
+    }
+}
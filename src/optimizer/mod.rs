@@ -1,9 +1,11 @@
-use crate::instruction::Instruction;
+use crate::{instruction::Instruction, tape::CellWidth};
 
 use self::{consecutive_inc_dec::optimize_consecutive_inc_dec, mul_loop::optimize_mul_loop};
 
 mod consecutive_inc_dec;
 mod mul_loop;
+#[cfg(test)]
+mod tests;
 
 #[derive(Debug, Clone, clap::ValueEnum, PartialEq, Eq)]
 #[clap(rename_all = "snake_case")]
@@ -14,14 +16,24 @@ pub enum Optimization {
     MulLoop,
 }
 
-pub fn optimize(mut instructions: Vec<Instruction>, options: &[Optimization]) -> Vec<Instruction> {
+pub fn optimize(instructions: Vec<Instruction>, options: &[Optimization]) -> Vec<Instruction> {
+    optimize_for_cell_width(instructions, options, CellWidth::U8)
+}
+
+/// セルが`cell_width`ビットのときに結果が変わらないように最適化する。
+/// 命令のオペランドは8ビットなので、広いセルでは回り込みを前提にした畳み込みをしない
+pub fn optimize_for_cell_width(
+    mut instructions: Vec<Instruction>,
+    options: &[Optimization],
+    cell_width: CellWidth,
+) -> Vec<Instruction> {
     let all = options.contains(&Optimization::All);
 
     if all || options.contains(&Optimization::ConsecutiveIncDec) {
-        instructions = optimize_consecutive_inc_dec(&instructions);
+        instructions = optimize_consecutive_inc_dec(&instructions, cell_width);
     }
     if all || options.contains(&Optimization::MulLoop) {
-        instructions = optimize_mul_loop(&instructions);
+        instructions = optimize_mul_loop(&instructions, cell_width);
     }
     instructions
 }
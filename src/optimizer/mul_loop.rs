@@ -1,14 +1,19 @@
-use crate::instruction::Instruction;
+use crate::{instruction::Instruction, tape::CellWidth};
 
 const MEMORY_SIZE: usize = 1024;
 const MEMORY_OFFSET: usize = MEMORY_SIZE / 2;
 
-pub fn optimize_mul_loop(instructions: &[Instruction]) -> Vec<Instruction> {
-    let (optimized, _) = do_optimize_mul_loop(instructions);
+pub fn optimize_mul_loop(instructions: &[Instruction], cell_width: CellWidth) -> Vec<Instruction> {
+    let (optimized, _) = do_optimize_mul_loop(instructions, cell_width);
     optimized
 }
 
-fn do_optimize_mul_loop(instructions: &[Instruction]) -> (Vec<Instruction>, bool) {
+fn do_optimize_mul_loop(
+    instructions: &[Instruction],
+    cell_width: CellWidth,
+) -> (Vec<Instruction>, bool) {
+    // 1回の繰り返しでループのカウンタが1だけ増減するかは、セルの大きさを法として比べる
+    let modulus = 1i64 << cell_width.bits();
     let mut optimized = Vec::new();
     let mut can_be_calculated = true;
     for inst in instructions {
@@ -21,20 +26,21 @@ fn do_optimize_mul_loop(instructions: &[Instruction]) -> (Vec<Instruction>, bool
                 can_be_calculated = false;
 
                 let (loop_instructions, loop_can_be_calculated) =
-                    do_optimize_mul_loop(loop_instructions);
+                    do_optimize_mul_loop(loop_instructions, cell_width);
 
                 if loop_can_be_calculated {
                     if let Some(simulation_result) = simulate(&loop_instructions) {
+                        let counter = simulation_result.memory[MEMORY_OFFSET].rem_euclid(modulus);
                         if simulation_result.pointer_offset == 0 {
-                            if simulation_result.memory[MEMORY_OFFSET] == 1
-                                || simulation_result.memory[MEMORY_OFFSET] == 255
-                            {
+                            if counter == 1 || counter == modulus - 1 {
                                 let mut optimized_loop = Vec::new();
 
-                                if simulation_result.memory[MEMORY_OFFSET] == 1 {
+                                if counter == 1 {
                                     optimized_loop.push(Instruction::Negate);
                                 }
 
+                                // カウンタは掛ける回数として最後まで読むので書き換えない。
+                                // 本体の途中での増減は、合わせて1になることを確かめたので最後のSetZeroで済む
                                 let mut offset = 0isize;
                                 for inst in loop_instructions {
                                     match inst {
@@ -59,16 +65,22 @@ fn do_optimize_mul_loop(instructions: &[Instruction]) -> (Vec<Instruction>, bool
                                             optimized_loop.push(Instruction::PointerDecrement);
                                         }
                                         Instruction::Add(value) => {
-                                            optimized_loop.push(Instruction::AddValueMultipliedBy(
-                                                value, -offset,
-                                            ));
+                                            if offset != 0 {
+                                                optimized_loop.push(
+                                                    Instruction::AddValueMultipliedBy(
+                                                        value, -offset,
+                                                    ),
+                                                );
+                                            }
                                         }
                                         Instruction::Subtract(value) => {
-                                            optimized_loop.push(
-                                                Instruction::SubtractValueMultipliedBy(
-                                                    value, -offset,
-                                                ),
-                                            );
+                                            if offset != 0 {
+                                                optimized_loop.push(
+                                                    Instruction::SubtractValueMultipliedBy(
+                                                        value, -offset,
+                                                    ),
+                                                );
+                                            }
                                         }
                                         Instruction::SetZero => {
                                             optimized_loop.push(Instruction::SetZero);
@@ -126,21 +138,22 @@ fn do_optimize_mul_loop(instructions: &[Instruction]) -> (Vec<Instruction>, bool
 }
 
 struct SimulationResult {
-    memory: Vec<u8>,
+    /// 各セルの変化量。回り込ませずに数える
+    memory: Vec<i64>,
     pointer_offset: isize,
 }
 
 fn simulate(instructions: &[Instruction]) -> Option<SimulationResult> {
-    let mut memory = vec![0u8; MEMORY_SIZE];
+    let mut memory = vec![0i64; MEMORY_SIZE];
     let mut pointer = MEMORY_OFFSET;
     let mut i = 0;
     while i < instructions.len() {
         match instructions[i] {
             Instruction::Increment => {
-                memory[pointer] = memory[pointer].wrapping_add(1);
+                memory[pointer] += 1;
             }
             Instruction::Decrement => {
-                memory[pointer] = memory[pointer].wrapping_sub(1);
+                memory[pointer] -= 1;
             }
             Instruction::PointerIncrement => {
                 if pointer == MEMORY_SIZE - 1 {
@@ -159,10 +172,10 @@ fn simulate(instructions: &[Instruction]) -> Option<SimulationResult> {
             }
             Instruction::GetChar | Instruction::PutChar => unreachable!(),
             Instruction::Add(value) => {
-                memory[pointer] = memory[pointer].wrapping_add(value);
+                memory[pointer] += value as i64;
             }
             Instruction::Subtract(value) => {
-                memory[pointer] = memory[pointer].wrapping_sub(value);
+                memory[pointer] -= value as i64;
             }
             Instruction::SetZero => {
                 return None;
@@ -0,0 +1,71 @@
+use super::*;
+use crate::parser;
+
+fn consecutive(source: &str) -> Vec<Instruction> {
+    optimize(
+        parser::parse(source).unwrap(),
+        &[Optimization::ConsecutiveIncDec],
+    )
+}
+
+#[test]
+fn fold_increments_and_decrements() {
+    assert_eq!(
+        consecutive("+++>>--<"),
+        [
+            Instruction::Add(3),
+            Instruction::PointerAdd(2),
+            Instruction::Subtract(2),
+            Instruction::PointerDecrement,
+        ]
+    );
+}
+
+#[test]
+fn fold_256_or_more_wraps_around() {
+    // セルの値は256で一周するので、256回は0回、257回は1回と同じになる
+    assert_eq!(consecutive(&"+".repeat(256)), [Instruction::Add(0)]);
+    assert_eq!(consecutive(&"+".repeat(257)), [Instruction::Add(1)]);
+    assert_eq!(consecutive(&"-".repeat(300)), [Instruction::Subtract(44)]);
+}
+
+#[test]
+fn split_long_runs_on_wide_cells() {
+    // 16ビットのセルは256で一周しないので、255回ずつに分ける
+    let optimized = optimize_for_cell_width(
+        parser::parse(&"+".repeat(300)).unwrap(),
+        &[Optimization::ConsecutiveIncDec],
+        CellWidth::U16,
+    );
+    assert_eq!(optimized, [Instruction::Add(255), Instruction::Add(45)]);
+}
+
+#[test]
+fn keep_loops_not_counting_down_by_one_on_wide_cells() {
+    // 8ビットなら257回の`-`は1回と同じだが、16ビットでは掛け算のループにならない
+    let source = format!("[{}>+<]", "-".repeat(257));
+    let program = parser::parse(&source).unwrap();
+    let narrow = optimize_for_cell_width(program.clone(), &[Optimization::All], CellWidth::U8);
+    let wide = optimize_for_cell_width(program, &[Optimization::All], CellWidth::U16);
+    assert!(!narrow.iter().any(|i| matches!(i, Instruction::Loop(_))));
+    assert!(wide.iter().any(|i| matches!(i, Instruction::Loop(_))));
+}
+
+#[test]
+fn keep_counter_of_mul_loop_until_the_end() {
+    // カウンタを本体の途中で増減しても、掛ける回数はループに入ったときの値で決まる
+    let optimized = optimize(parser::parse("[>+<--+>+<]").unwrap(), &[Optimization::All]);
+    assert_eq!(
+        optimized,
+        [Instruction::IfNotZero(vec![
+            Instruction::PointerIncrement,
+            Instruction::AddValueAt(-1),
+            Instruction::PointerDecrement,
+            Instruction::PointerIncrement,
+            Instruction::AddValueAt(-1),
+            Instruction::PointerDecrement,
+            Instruction::SetZero,
+            Instruction::SetZero,
+        ])]
+    );
+}
@@ -1,32 +1,125 @@
-use std::str::Chars;
+use std::{iter::Peekable, str::CharIndices};
 
 use anyhow::bail;
 
 use crate::instruction::Instruction;
 
+/// コメントも残した構文木の要素
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    /// ループ以外の命令
+    Command(Instruction),
+    Loop(Vec<Node>),
+    Comment(Comment),
+}
+
+/// 命令以外の文字の並び。行ごとに分け、前後の空白を取り除いてある
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    pub text: String,
+    /// ソースコードでの先頭のバイト位置
+    pub position: usize,
+}
+
 pub fn parse(input: &str) -> anyhow::Result<Vec<Instruction>> {
-    let mut chars = input.chars();
-    let program = do_parse(&mut chars);
-    if chars.next().is_some() {
+    Ok(strip_comments(parse_with_comments(input)?))
+}
+
+/// 命令の間にあるコメントを残したまま構文解析する
+pub fn parse_with_comments(input: &str) -> anyhow::Result<Vec<Node>> {
+    let mut chars = input.char_indices().peekable();
+    let (program, closed) = do_parse(input, &mut chars)?;
+    if closed {
         bail!("Unexpected end of loop");
     }
     Ok(program)
 }
 
-fn do_parse(chars: &mut Chars) -> Vec<Instruction> {
-    let mut instructions = Vec::new();
-    while let Some(c) = chars.next() {
-        match c {
-            '+' => instructions.push(Instruction::Increment),
-            '-' => instructions.push(Instruction::Decrement),
-            '>' => instructions.push(Instruction::PointerIncrement),
-            '<' => instructions.push(Instruction::PointerDecrement),
-            '.' => instructions.push(Instruction::PutChar),
-            ',' => instructions.push(Instruction::GetChar),
-            '[' => instructions.push(Instruction::Loop(do_parse(chars))),
-            ']' => return instructions,
-            _ => {}
+/// `]`まで、または入力の終わりまで読む。`]`で終わった場合は`true`を返す
+fn do_parse(input: &str, chars: &mut Peekable<CharIndices>) -> anyhow::Result<(Vec<Node>, bool)> {
+    let mut nodes = Vec::new();
+    while let Some((start, c)) = chars.next() {
+        let command = match c {
+            '+' => Instruction::Increment,
+            '-' => Instruction::Decrement,
+            '>' => Instruction::PointerIncrement,
+            '<' => Instruction::PointerDecrement,
+            '.' => Instruction::PutChar,
+            ',' => Instruction::GetChar,
+            '[' => {
+                let (body, closed) = do_parse(input, chars)?;
+                if !closed {
+                    bail!("Unclosed loop");
+                }
+                nodes.push(Node::Loop(body));
+                continue;
+            }
+            ']' => return Ok((nodes, true)),
+            _ => {
+                // 次の命令の手前までをまとめてコメントにする
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if is_command(c) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                push_comments(input, start, end, &mut nodes);
+                continue;
+            }
+        };
+        nodes.push(Node::Command(command));
+    }
+    Ok((nodes, false))
+}
+
+/// `input[start..end]`を行ごとに分け、空白だけではない行をコメントとして追加する
+fn push_comments(input: &str, start: usize, end: usize, nodes: &mut Vec<Node>) {
+    let mut offset = start;
+    for line in input[start..end].split_inclusive('\n') {
+        let text = line.trim();
+        if !text.is_empty() {
+            nodes.push(Node::Comment(Comment {
+                text: text.to_string(),
+                position: offset + line.len() - line.trim_start().len(),
+            }));
         }
+        offset += line.len();
     }
-    instructions
+}
+
+fn strip_comments(nodes: Vec<Node>) -> Vec<Instruction> {
+    nodes
+        .into_iter()
+        .filter_map(|node| match node {
+            Node::Command(inst) => Some(inst),
+            Node::Loop(body) => Some(Instruction::Loop(strip_comments(body))),
+            Node::Comment(_) => None,
+        })
+        .collect()
+}
+
+/// 命令になる文字か
+pub fn is_command(c: char) -> bool {
+    matches!(c, '+' | '-' | '>' | '<' | '.' | ',' | '[' | ']')
+}
+
+/// 命令になる文字のバイト位置を出現順に返す。
+/// 最適化していないプログラムを`compiler::vm::compile`した結果の命令と1対1に対応する
+pub fn command_positions(input: &str) -> Vec<usize> {
+    input
+        .char_indices()
+        .filter(|&(_, c)| is_command(c))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// バイト位置を1から数えた`(行, 列)`にする。列は文字単位で数える
+pub fn line_column(input: &str, position: usize) -> (usize, usize) {
+    let before = &input[..position];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
 }
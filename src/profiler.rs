@@ -0,0 +1,362 @@
+//! VMで実行しながら命令ごとの実行回数を数え、ループ単位に集計するプロファイラ
+
+use std::{collections::HashMap, fmt::Write};
+
+use clap::ValueEnum;
+
+use crate::{
+    compiler::{self, vm::FUSIONS},
+    instruction::Instruction,
+    runtime::{
+        vm::{VMInstruction, VM},
+        Input,
+    },
+};
+
+#[cfg(test)]
+mod tests;
+
+/// ループの内容を表示するときの最大の文字数
+const BODY_WIDTH: usize = 40;
+
+pub struct Profile {
+    /// 実行した命令の総数
+    pub total_steps: u64,
+    /// ソース上の出現順に並べたループ
+    pub loops: Vec<LoopProfile>,
+}
+
+pub struct LoopProfile {
+    /// 1から始まる出現順の番号
+    pub id: usize,
+    /// 外側のループの`loops`での位置
+    pub parent: Option<usize>,
+    pub depth: usize,
+    /// ループの内容を短く表したもの
+    pub body: String,
+    /// ループの先頭に到達した回数
+    pub entries: u64,
+    /// ループの本体を実行した回数
+    pub iterations: u64,
+    /// 内側のループを含めて、このループで実行した命令の数
+    pub total_steps: u64,
+    /// 内側のループを除いて、このループで実行した命令の数
+    pub self_steps: u64,
+}
+
+impl LoopProfile {
+    /// 1回の到達あたりの平均の繰り返し回数
+    pub fn average_trips(&self) -> f64 {
+        if self.entries == 0 {
+            0.0
+        } else {
+            self.iterations as f64 / self.entries as f64
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("#{} {}", self.id, self.body)
+    }
+}
+
+/// `fusion_report`で表示する命令の組の数
+const PAIR_COUNT: usize = 10;
+
+/// プログラムをVMで実行し、スーパー命令でまとめられる命令の並びと、
+/// まとめた場合に減る命令の実行回数を調べる。よく続けて実行される命令の組も表示する
+pub fn fusion_report(instructions: &[Instruction], input: Input) -> String {
+    let compiled = compiler::vm::compile(instructions);
+    let mut counts = vec![0u64; compiled.len()];
+    let mut pairs = HashMap::<_, u64>::new();
+    let mut previous = None;
+    let mut vm = VM::new(&compiled);
+    vm.input = input;
+    while !vm.is_finished() {
+        let ip = vm.instruction_pointer;
+        counts[ip] += 1;
+        let kind = kind(compiled[ip]);
+        if let Some(previous) = previous {
+            *pairs.entry((previous, kind.clone())).or_default() += 1;
+        }
+        previous = Some(kind);
+        vm.step();
+    }
+    let total_steps = counts.iter().sum::<u64>();
+    let percent = |count: u64| {
+        if total_steps == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / total_steps as f64
+        }
+    };
+
+    let mut out = String::new();
+    writeln!(out, "total steps: {}", total_steps).unwrap();
+    writeln!(
+        out,
+        "{:<12} {:>8} {:>16} {:>8}",
+        "fusion", "sites", "saved steps", "%"
+    )
+    .unwrap();
+    for fusion in FUSIONS {
+        let sites = compiler::vm::fusion_sites(&compiled, fusion);
+        // まとめた命令は先頭の命令と同じ回数だけ実行される
+        let saved = sites
+            .iter()
+            .map(|&(start, len)| counts[start..start + len].iter().sum::<u64>() - counts[start])
+            .sum::<u64>();
+        writeln!(
+            out,
+            "{:<12} {:>8} {:>16} {:>7.2}%",
+            fusion.to_possible_value().unwrap().get_name(),
+            sites.len(),
+            saved,
+            percent(saved)
+        )
+        .unwrap();
+    }
+
+    let mut pairs = pairs.into_iter().collect::<Vec<_>>();
+    pairs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    writeln!(out, "most frequent instruction pairs:").unwrap();
+    for ((first, second), count) in pairs.into_iter().take(PAIR_COUNT) {
+        writeln!(
+            out,
+            "{:>16} {:>7.2}%  {} -> {}",
+            count,
+            percent(count),
+            first,
+            second
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// オペランドを除いた命令の名前
+fn kind(inst: VMInstruction) -> String {
+    let name = format!("{:?}", inst);
+    match name.find('(') {
+        Some(i) => name[..i].to_string(),
+        None => name,
+    }
+}
+
+/// VMの命令列上でのループの範囲
+struct LoopRange {
+    /// `JumpIfZero`の位置
+    start: usize,
+    /// `JumpIfNotZero`の位置
+    end: usize,
+}
+
+/// プログラムをVMで実行し、プロファイルを取る
+pub fn run(instructions: &[Instruction], input: Input) -> Profile {
+    let counts = count(&compiler::vm::compile(instructions), input);
+
+    let mut loops = Vec::new();
+    let mut ranges = Vec::new();
+    collect_loops(instructions, 0, None, 0, &mut loops, &mut ranges);
+
+    for (i, range) in ranges.iter().enumerate() {
+        let total_steps = counts[range.start..=range.end].iter().sum();
+        let profile = &mut loops[i];
+        profile.entries = counts[range.start];
+        profile.iterations = counts[range.end];
+        profile.total_steps = total_steps;
+        profile.self_steps = total_steps;
+    }
+    for i in 0..loops.len() {
+        if let Some(parent) = loops[i].parent {
+            loops[parent].self_steps -= loops[i].total_steps;
+        }
+    }
+
+    Profile {
+        total_steps: counts.iter().sum(),
+        loops,
+    }
+}
+
+/// VMの命令列を実行し、命令ごとの実行回数を数える
+fn count(compiled: &[VMInstruction], input: Input) -> Vec<u64> {
+    let mut counts = vec![0u64; compiled.len()];
+    let mut vm = VM::new(compiled);
+    vm.input = input;
+    while !vm.is_finished() {
+        counts[vm.instruction_pointer] += 1;
+        vm.step();
+    }
+    counts
+}
+
+/// IRのネストからループを集める。戻り値はVMの命令列上での命令の数
+fn collect_loops(
+    instructions: &[Instruction],
+    offset: usize,
+    parent: Option<usize>,
+    depth: usize,
+    loops: &mut Vec<LoopProfile>,
+    ranges: &mut Vec<LoopRange>,
+) -> usize {
+    let mut position = offset;
+    for inst in instructions {
+        match inst {
+            Instruction::Loop(body) => {
+                let index = loops.len();
+                loops.push(LoopProfile {
+                    id: index + 1,
+                    parent,
+                    depth,
+                    body: describe(inst),
+                    entries: 0,
+                    iterations: 0,
+                    total_steps: 0,
+                    self_steps: 0,
+                });
+                ranges.push(LoopRange {
+                    start: position,
+                    end: 0,
+                });
+                let len = collect_loops(body, position + 1, Some(index), depth + 1, loops, ranges);
+                ranges[index].end = position + 1 + len;
+                position += len + 2;
+            }
+            Instruction::IfNotZero(body) => {
+                position += collect_loops(body, position + 1, parent, depth, loops, ranges) + 1;
+            }
+            _ => position += 1,
+        }
+    }
+    position - offset
+}
+
+/// 命令をBrainfuckに近い表記で短く表す
+fn describe(inst: &Instruction) -> String {
+    let mut out = String::new();
+    write_instruction(inst, &mut out);
+    if out.chars().count() > BODY_WIDTH {
+        out = out.chars().take(BODY_WIDTH - 3).collect();
+        out.push_str("...");
+    }
+    out
+}
+
+fn write_instruction(inst: &Instruction, out: &mut String) {
+    // 長いループでも表示する分だけ書けばよい
+    if out.len() > BODY_WIDTH * 4 {
+        return;
+    }
+    match inst {
+        Instruction::Increment => out.push('+'),
+        Instruction::Decrement => out.push('-'),
+        Instruction::PointerIncrement => out.push('>'),
+        Instruction::PointerDecrement => out.push('<'),
+        Instruction::PutChar => out.push('.'),
+        Instruction::GetChar => out.push(','),
+        Instruction::Loop(body) => {
+            out.push('[');
+            for inst in body {
+                write_instruction(inst, out);
+            }
+            out.push(']');
+        }
+        Instruction::Add(value) => write!(out, "+{}", value).unwrap(),
+        Instruction::Subtract(value) => write!(out, "-{}", value).unwrap(),
+        Instruction::SetZero => out.push_str("[-]"),
+        Instruction::PointerAdd(value) => write!(out, ">{}", value).unwrap(),
+        Instruction::PointerSubtract(value) => write!(out, "<{}", value).unwrap(),
+        Instruction::AddValueAt(at) => write!(out, "{{+@{}}}", at).unwrap(),
+        Instruction::SubtractValueAt(at) => write!(out, "{{-@{}}}", at).unwrap(),
+        Instruction::AddValueMultipliedBy(value, at) => {
+            write!(out, "{{+{}*@{}}}", value, at).unwrap()
+        }
+        Instruction::SubtractValueMultipliedBy(value, at) => {
+            write!(out, "{{-{}*@{}}}", value, at).unwrap()
+        }
+        Instruction::Negate => out.push_str("{neg}"),
+        Instruction::IfNotZero(body) => {
+            out.push_str("{if ");
+            for inst in body {
+                write_instruction(inst, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+impl Profile {
+    /// 実行した命令の多い順にループを並べた表
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "total steps: {}", self.total_steps).unwrap();
+        if self.loops.is_empty() {
+            return out;
+        }
+
+        let mut loops = self.loops.iter().collect::<Vec<_>>();
+        loops.sort_by(|a, b| b.total_steps.cmp(&a.total_steps).then(a.id.cmp(&b.id)));
+
+        writeln!(
+            out,
+            "{:>14} {:>7} {:>14} {:>12} {:>14} {:>10}  loop",
+            "steps", "%", "self", "entries", "iterations", "avg trips"
+        )
+        .unwrap();
+        for profile in loops {
+            let percent = if self.total_steps == 0 {
+                0.0
+            } else {
+                profile.total_steps as f64 * 100.0 / self.total_steps as f64
+            };
+            writeln!(
+                out,
+                "{:>14} {:>6.2}% {:>14} {:>12} {:>14} {:>10.1}  {}{}",
+                profile.total_steps,
+                percent,
+                profile.self_steps,
+                profile.entries,
+                profile.iterations,
+                profile.average_trips(),
+                "  ".repeat(profile.depth),
+                profile.name()
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    /// flamegraphなどで読めるfolded stack形式。ループのネストをスタックとみなす
+    pub fn folded(&self) -> String {
+        let top_level = self
+            .loops
+            .iter()
+            .filter(|profile| profile.parent.is_none())
+            .map(|profile| profile.total_steps)
+            .sum::<u64>();
+
+        let mut out = String::new();
+        if self.total_steps > top_level {
+            writeln!(out, "main {}", self.total_steps - top_level).unwrap();
+        }
+        for (i, profile) in self.loops.iter().enumerate() {
+            if profile.self_steps == 0 {
+                continue;
+            }
+            writeln!(out, "{} {}", self.stack(i), profile.self_steps).unwrap();
+        }
+        out
+    }
+
+    fn stack(&self, index: usize) -> String {
+        let mut frames = Vec::new();
+        let mut current = Some(index);
+        while let Some(i) = current {
+            frames.push(self.loops[i].name());
+            current = self.loops[i].parent;
+        }
+        frames.push("main".to_string());
+        frames.reverse();
+        frames.join(";")
+    }
+}
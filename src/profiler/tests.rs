@@ -0,0 +1,72 @@
+use super::*;
+use crate::{
+    optimizer::{self, Optimization},
+    parser,
+};
+
+fn profile(source: &str, optimizations: &[Optimization]) -> Profile {
+    let program = optimizer::optimize(parser::parse(source).unwrap(), optimizations);
+    run(&program, Input::bytes(Vec::new()))
+}
+
+#[test]
+fn count_instructions_and_loops() {
+    // 外側のループは2回、内側のループは1回の到達あたり3回繰り返す
+    let source = "++[>+++[>+<-]<-]";
+    let program = parser::parse(source).unwrap();
+    assert_eq!(
+        count(&compiler::vm::compile(&program), Input::bytes(Vec::new())),
+        [1, 1, 1, 2, 2, 2, 2, 2, 6, 6, 6, 6, 6, 2, 2, 2]
+    );
+    let profile = profile(source, &[]);
+    assert_eq!(profile.total_steps, 49);
+
+    let [outer, inner] = &profile.loops[..] else {
+        panic!("expected 2 loops");
+    };
+    assert_eq!(
+        (outer.id, outer.parent, outer.depth, outer.body.as_str()),
+        (1, None, 0, "[>+++[>+<-]<-]")
+    );
+    assert_eq!(
+        (
+            outer.entries,
+            outer.iterations,
+            outer.total_steps,
+            outer.self_steps
+        ),
+        (1, 2, 47, 15)
+    );
+    assert_eq!(
+        (inner.id, inner.parent, inner.depth, inner.body.as_str()),
+        (2, Some(0), 1, "[>+<-]")
+    );
+    assert_eq!(
+        (
+            inner.entries,
+            inner.iterations,
+            inner.total_steps,
+            inner.self_steps
+        ),
+        (2, 6, 32, 32)
+    );
+    assert_eq!(inner.average_trips(), 3.0);
+
+    assert_eq!(
+        profile.folded(),
+        "main 2\nmain;#1 [>+++[>+<-]<-] 15\nmain;#1 [>+++[>+<-]<-];#2 [>+<-] 32\n"
+    );
+}
+
+#[test]
+fn find_loops_after_ifs() {
+    // 最初のループは掛け算のループとしてifになるので、数えるのは2つ目のループだけ
+    let profile = profile("++[>+<-]>+[,]", &[Optimization::All]);
+    let [profile_loop] = &profile.loops[..] else {
+        panic!("expected 1 loop");
+    };
+    assert_eq!(profile_loop.body, "[,]");
+    // 入力が空なので`,`で0になり、1回で抜ける
+    assert_eq!((profile_loop.entries, profile_loop.iterations), (1, 1));
+    assert_eq!(profile_loop.total_steps, 3);
+}
@@ -0,0 +1,334 @@
+//! `VMInstruction`をクロージャの木に変換してから実行するVM
+//!
+//! 命令ごとの`match`による分岐をなくし、ループは本体のクロージャを繰り返し呼び出す。
+//! テープは`TapeOptions::size`のセルで、VMと同じくポインタがテープの外に出ようとしたら実行を止める
+
+use std::{
+    fmt,
+    io::{ErrorKind, Read, Write},
+};
+
+use super::{vm::VMInstruction, Input};
+use crate::tape::{Eof, TapeOptions};
+
+/// 出力をまとめて書き出す大きさ
+const OUTPUT_BUFFER_SIZE: usize = 8192;
+
+/// ポインタがテープの外に出ようとしたために実行を止めた
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfTape {
+    /// 移動しようとした先か、読もうとしたセルの位置
+    pub cell: isize,
+    /// テープのセルの数
+    pub size: usize,
+}
+
+impl fmt::Display for OutOfTape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.cell < 0 {
+            write!(f, "pointer moved to the left of cell 0")?;
+        } else {
+            write!(f, "pointer moved out of the tape of {} cells", self.size)?;
+        }
+        write!(f, " (cell: {})", self.cell)
+    }
+}
+
+impl std::error::Error for OutOfTape {}
+
+struct State {
+    memory: Vec<u8>,
+    /// 常にテープの範囲内を指す
+    pointer: usize,
+    eof: Eof,
+    input: Input,
+    output: Vec<u8>,
+}
+
+impl State {
+    fn new(tape: TapeOptions, input: Input) -> Self {
+        Self {
+            memory: vec![0; tape.size],
+            pointer: 0,
+            eof: tape.eof,
+            input,
+            output: Vec::with_capacity(OUTPUT_BUFFER_SIZE),
+        }
+    }
+
+    fn current(&mut self) -> &mut u8 {
+        &mut self.memory[self.pointer]
+    }
+
+    /// ポインタから`offset`離れたセルの位置。テープの外ならエラーを返す
+    fn index(&self, offset: isize) -> Result<usize, OutOfTape> {
+        // 負の位置は符号なしにすると大きな値になるので、1回の比較で範囲外になる
+        let cell = self.pointer.wrapping_add(offset as usize);
+        if cell < self.memory.len() {
+            Ok(cell)
+        } else {
+            Err(OutOfTape {
+                cell: self.pointer as isize + offset,
+                size: self.memory.len(),
+            })
+        }
+    }
+
+    fn move_by(&mut self, offset: isize) -> Result<(), OutOfTape> {
+        self.pointer = self.index(offset)?;
+        Ok(())
+    }
+
+    fn at(&self, at: isize) -> Result<u8, OutOfTape> {
+        Ok(self.memory[self.index(at)?])
+    }
+
+    fn put_char(&mut self, value: u8) {
+        self.output.push(value);
+        if self.output.len() >= OUTPUT_BUFFER_SIZE {
+            self.flush();
+        }
+    }
+
+    fn get_char(&mut self) {
+        self.flush();
+        let mut input = [0];
+        let value = match self.input.read_exact(&mut input) {
+            Ok(()) => input[0],
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => match self.eof {
+                Eof::Zero => 0,
+                Eof::MinusOne => u8::MAX,
+                Eof::Unchanged => return,
+            },
+            Err(e) => panic!("Error reading input: {:?}", e),
+        };
+        *self.current() = value;
+    }
+
+    fn flush(&mut self) {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(&self.output).unwrap();
+        stdout.flush().unwrap();
+        self.output.clear();
+    }
+
+    fn trace(&self, ip: usize, inst: VMInstruction) {
+        println!(
+            "ip: {}, inst: {:?}, ptr: {}, mem: {}\n{:?}",
+            ip,
+            inst,
+            self.pointer,
+            self.memory[self.pointer],
+            &self.memory[0..self.memory.len().min(30)]
+        );
+    }
+}
+
+type Op = Box<dyn Fn(&mut State) -> Result<(), OutOfTape>>;
+
+pub struct Program {
+    ops: Vec<Op>,
+}
+
+impl Program {
+    /// セルは8ビットのみ
+    pub fn run(&self, tape: TapeOptions, input: Input) -> Result<(), OutOfTape> {
+        let mut state = State::new(tape, input);
+        let result = run_ops(&self.ops, &mut state);
+        state.flush();
+        result
+    }
+}
+
+fn run_ops(ops: &[Op], state: &mut State) -> Result<(), OutOfTape> {
+    for op in ops {
+        op(state)?;
+    }
+    Ok(())
+}
+
+/// 命令列をクロージャに変換する
+pub fn compile(instructions: &[VMInstruction]) -> Program {
+    Program {
+        ops: build::<false>(instructions, 0, instructions.len()),
+    }
+}
+
+/// `runtime::vm::run`と同じ形式で、命令を実行するたびにトレースを出力するクロージャに変換する
+pub fn compile_traced(instructions: &[VMInstruction]) -> Program {
+    Program {
+        ops: build::<true>(instructions, 0, instructions.len()),
+    }
+}
+
+/// `start..end`の命令をクロージャに変換する。
+/// ジャンプ先からループとifを復元し、それぞれの本体を再帰的に変換する
+fn build<const TRACE: bool>(instructions: &[VMInstruction], start: usize, end: usize) -> Vec<Op> {
+    let mut ops = Vec::new();
+    let mut i = start;
+    while i < end {
+        let inst = instructions[i];
+        match inst {
+            VMInstruction::JumpIfZero(target)
+                if instructions.get(target) == Some(&VMInstruction::JumpIfNotZero(i)) =>
+            {
+                let body = build::<TRACE>(instructions, i + 1, target);
+                let ip = i;
+                let op: Op = if TRACE {
+                    Box::new(move |state: &mut State| {
+                        state.trace(ip, inst);
+                        if *state.current() == 0 {
+                            return Ok(());
+                        }
+                        loop {
+                            run_ops(&body, state)?;
+                            state.trace(target, VMInstruction::JumpIfNotZero(ip));
+                            if *state.current() == 0 {
+                                return Ok(());
+                            }
+                        }
+                    })
+                } else {
+                    Box::new(move |state: &mut State| {
+                        while *state.current() != 0 {
+                            run_ops(&body, state)?;
+                        }
+                        Ok(())
+                    })
+                };
+                ops.push(op);
+                i = target + 1;
+            }
+            // ループでなければifで、targetは本体の最後の命令
+            VMInstruction::JumpIfZero(target) => {
+                let body = build::<TRACE>(instructions, i + 1, target + 1);
+                let ip = i;
+                ops.push(Box::new(move |state: &mut State| {
+                    if TRACE {
+                        state.trace(ip, inst);
+                    }
+                    if *state.current() != 0 {
+                        run_ops(&body, state)?;
+                    }
+                    Ok(())
+                }));
+                i = target + 1;
+            }
+            _ => {
+                let op = simple(inst);
+                let ip = i;
+                if TRACE {
+                    ops.push(Box::new(move |state: &mut State| {
+                        state.trace(ip, inst);
+                        op(state)?;
+                        // トレースと出力の順序を保つ
+                        if inst == VMInstruction::PutChar {
+                            state.flush();
+                        }
+                        Ok(())
+                    }));
+                } else {
+                    ops.push(op);
+                }
+                i += 1;
+            }
+        }
+    }
+    ops
+}
+
+fn simple(inst: VMInstruction) -> Op {
+    match inst {
+        VMInstruction::Increment => Box::new(|state| {
+            let cell = state.current();
+            *cell = cell.wrapping_add(1);
+            Ok(())
+        }),
+        VMInstruction::Decrement => Box::new(|state| {
+            let cell = state.current();
+            *cell = cell.wrapping_sub(1);
+            Ok(())
+        }),
+        VMInstruction::PointerIncrement => Box::new(|state| state.move_by(1)),
+        VMInstruction::PointerDecrement => Box::new(|state| state.move_by(-1)),
+        VMInstruction::PutChar => Box::new(|state| {
+            let value = *state.current();
+            state.put_char(value);
+            Ok(())
+        }),
+        VMInstruction::GetChar => Box::new(|state| {
+            state.get_char();
+            Ok(())
+        }),
+        VMInstruction::Add(value) => Box::new(move |state| {
+            let cell = state.current();
+            *cell = cell.wrapping_add(value);
+            Ok(())
+        }),
+        VMInstruction::Subtract(value) => Box::new(move |state| {
+            let cell = state.current();
+            *cell = cell.wrapping_sub(value);
+            Ok(())
+        }),
+        VMInstruction::SetZero => Box::new(|state| {
+            *state.current() = 0;
+            Ok(())
+        }),
+        VMInstruction::PointerAdd(value) => {
+            let value = value as isize;
+            Box::new(move |state| state.move_by(value))
+        }
+        VMInstruction::PointerSubtract(value) => {
+            let value = -(value as isize);
+            Box::new(move |state| state.move_by(value))
+        }
+        VMInstruction::AddValueAt(at) => Box::new(move |state| {
+            let value = state.at(at)?;
+            let cell = state.current();
+            *cell = cell.wrapping_add(value);
+            Ok(())
+        }),
+        VMInstruction::SubtractValueAt(at) => Box::new(move |state| {
+            let value = state.at(at)?;
+            let cell = state.current();
+            *cell = cell.wrapping_sub(value);
+            Ok(())
+        }),
+        VMInstruction::AddValueMultipliedBy(multiplier, at) => Box::new(move |state| {
+            let value = state.at(at)?.wrapping_mul(multiplier);
+            let cell = state.current();
+            *cell = cell.wrapping_add(value);
+            Ok(())
+        }),
+        VMInstruction::SubtractValueMultipliedBy(multiplier, at) => Box::new(move |state| {
+            let value = state.at(at)?.wrapping_mul(multiplier);
+            let cell = state.current();
+            *cell = cell.wrapping_sub(value);
+            Ok(())
+        }),
+        VMInstruction::Negate => Box::new(|state| {
+            let cell = state.current();
+            *cell = cell.wrapping_neg();
+            Ok(())
+        }),
+        VMInstruction::MoveAdd(offset, value) => Box::new(move |state| {
+            state.move_by(offset)?;
+            let cell = state.current();
+            *cell = cell.wrapping_add(value);
+            Ok(())
+        }),
+        VMInstruction::SetValue(value) => Box::new(move |state| {
+            *state.current() = value;
+            Ok(())
+        }),
+        VMInstruction::Scan(step) => Box::new(move |state| {
+            while *state.current() != 0 {
+                state.move_by(step)?;
+            }
+            Ok(())
+        }),
+        VMInstruction::JumpIfZero(_) | VMInstruction::JumpIfNotZero(_) => {
+            unreachable!("jumps are converted into loops and ifs")
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! 信頼できないプログラムを実行するときの制限
+
+use std::{fmt, time::Duration};
+
+/// 実行の制限。`None`の項目は制限しない
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    /// 実行する命令の最大数。ネイティブコードではループの繰り返し回数の上限になる
+    pub max_steps: Option<u64>,
+    /// 出力する最大のバイト数
+    pub max_output: Option<usize>,
+    /// テープを伸ばせる最大のセル数
+    pub max_tape: Option<usize>,
+    /// 実行時間の上限。入力を待っている間は中断できない
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Steps(u64),
+    /// ネイティブコードのループの繰り返し回数
+    Fuel(u64),
+    Output(usize),
+    /// テープの範囲外に出ようとした。上限を指定していない場合はテープの左端を越えたとき
+    Tape(Option<usize>),
+    Timeout(Duration),
+}
+
+/// 制限を超えたために実行を中断した
+#[derive(Debug, Clone)]
+pub struct LimitExceeded {
+    pub limit: Limit,
+    /// 中断するまでに実行した命令の数。ネイティブコードではループの繰り返し回数
+    pub steps: u64,
+    /// 次に実行する命令の位置。ネイティブコードでは不明
+    pub instruction_pointer: Option<usize>,
+    pub pointer: usize,
+    /// 中断したときのテープ
+    pub memory: Vec<u8>,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.limit {
+            Limit::Steps(max) => write!(f, "step limit of {} exceeded", max)?,
+            Limit::Fuel(max) => write!(f, "loop iteration limit of {} exceeded", max)?,
+            Limit::Output(max) => write!(f, "output limit of {} bytes exceeded", max)?,
+            Limit::Tape(Some(max)) => write!(f, "pointer moved out of the tape of {} cells", max)?,
+            Limit::Tape(None) => write!(f, "pointer moved to the left of cell 0")?,
+            Limit::Timeout(timeout) => write!(f, "timed out after {:?}", timeout)?,
+        }
+        write!(f, " (steps: {}, pointer: {}", self.steps, self.pointer)?;
+        if let Some(value) = self.memory.get(self.pointer) {
+            write!(f, ", value: {}", value)?;
+        }
+        if let Some(ip) = self.instruction_pointer {
+            write!(f, ", ip: {}", ip)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
@@ -1,2 +1,46 @@
+pub mod closure;
+pub mod limits;
 pub mod native;
+pub mod tiered;
+pub mod trace;
 pub mod vm;
+
+use std::io::{Cursor, Read};
+
+/// プログラムを実行する方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// VM dispatching each instruction with a `match` loop. Supports all debugging and limit options
+    Vm,
+    /// VM compiled into a tree of closures before running
+    Closure,
+    /// Start on the VM and compile loops into native code once they repeat --jit-threshold times
+    Tiered,
+    /// x86_64 native code
+    Native,
+}
+
+/// プログラムが`,`で読む入力
+#[derive(Debug, Default)]
+pub enum Input {
+    /// 標準入力から読む
+    #[default]
+    Stdin,
+    /// 与えたバイト列を先頭から読む
+    Bytes(Cursor<Vec<u8>>),
+}
+
+impl Input {
+    pub fn bytes(data: Vec<u8>) -> Self {
+        Input::Bytes(Cursor::new(data))
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Input::Stdin => std::io::stdin().read(buf),
+            Input::Bytes(bytes) => bytes.read(buf),
+        }
+    }
+}
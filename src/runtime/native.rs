@@ -1,18 +1,286 @@
+use std::{
+    cell::Cell,
+    ffi::c_void,
+    io::{ErrorKind, Read, Write},
+};
+
+use super::{
+    limits::{Limit, LimitExceeded},
+    trace::Tracer,
+};
+use crate::{
+    compiler::x86_64::{Bounds, Fuel, TraceHook},
+    tape::Eof,
+};
+
 pub fn run(code: &[u8]) {
     let mut memory = vec![0u8; 30000];
+    run_with_memory(code, &mut memory);
+}
 
-    unsafe {
-        let executable_code = libc::mmap(
-            std::ptr::null_mut(),
-            code.len(),
-            libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
-            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
-            -1,
-            0,
-        );
-        std::ptr::copy_nonoverlapping(code.as_ptr(), executable_code as *mut u8, code.len());
-        let f: extern "C" fn(memory: *mut u8) = std::mem::transmute(executable_code);
-        f(memory.as_mut_ptr());
-        libc::munmap(executable_code, code.len());
+/// `CompileOptions::fuel`と`bounds`を指定してコンパイルしたコードを、燃料を`max_iterations`にして実行する
+///
+/// # Safety
+/// `fuel`と`bounds`はコンパイル時に渡したものと同じポインタでなければならない。
+/// 生成したコードが書き換えるので、実行中に参照を経由して読み書きしてはいけない
+pub unsafe fn run_with_fuel(
+    code: &[u8],
+    fuel: *mut Fuel,
+    bounds: *mut Bounds,
+    max_iterations: u64,
+) -> Result<(), LimitExceeded> {
+    let mut memory = vec![0u8; 30000];
+    (*fuel).remaining = max_iterations;
+    (*fuel).pointer = std::ptr::null_mut();
+    (*bounds).start = memory.as_mut_ptr();
+    (*bounds).len = memory.len() as u64;
+    (*bounds).reach = 0;
+    let (limit, steps, pointer) = match run_with_memory(code, &mut memory) {
+        0 => return Ok(()),
+        1 => (
+            Limit::Fuel(max_iterations),
+            max_iterations,
+            (*fuel).pointer.offset_from(memory.as_ptr()) as usize,
+        ),
+        _ => {
+            // ネイティブコードのテープは伸ばせないので、右端を越えたときはテープの長さを上限として報告する
+            let reach = (*bounds).reach;
+            let limit = if reach < 0 {
+                Limit::Tape(None)
+            } else {
+                Limit::Tape(Some(memory.len()))
+            };
+            (
+                limit,
+                max_iterations - (*fuel).remaining,
+                reach.max(0) as usize,
+            )
+        }
     };
+    Err(LimitExceeded {
+        limit,
+        steps,
+        instruction_pointer: None,
+        pointer,
+        memory,
+    })
+}
+
+/// 生成した関数の戻り値を返す。燃料を使わないコードの戻り値は不定
+fn run_with_memory(code: &[u8], memory: &mut [u8]) -> u32 {
+    let function = NativeFunction::new(code);
+    unsafe { function.call(memory.as_mut_ptr()) as u32 }
+}
+
+/// 実行可能なメモリに配置した、生成したコードの関数
+pub struct NativeFunction {
+    code: *mut c_void,
+    len: usize,
+}
+
+impl NativeFunction {
+    pub fn new(code: &[u8]) -> Self {
+        unsafe {
+            let executable_code = libc::mmap(
+                std::ptr::null_mut(),
+                code.len(),
+                libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            assert_ne!(
+                executable_code,
+                libc::MAP_FAILED,
+                "failed to map executable memory"
+            );
+            std::ptr::copy_nonoverlapping(code.as_ptr(), executable_code as *mut u8, code.len());
+            Self {
+                code: executable_code,
+                len: code.len(),
+            }
+        }
+    }
+
+    /// `pointer`を第1引数にして呼び出し、戻り値を返す
+    ///
+    /// # Safety
+    /// 生成したコードはテープの範囲を確かめないので、`pointer`から到達するセルはすべて有効でなければならない
+    pub unsafe fn call(&self, pointer: *mut u8) -> u64 {
+        let f: extern "C" fn(pointer: *mut u8) -> u64 = std::mem::transmute(self.code);
+        f(pointer)
+    }
+}
+
+impl Drop for NativeFunction {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.code, self.len);
+        }
+    }
+}
+
+/// ネイティブコードのトレース関数から`Tracer`を呼び出すためのコンテキスト
+///
+/// コンパイル時に`hook()`と`context()`を`CompileOptions::trace`に渡し、
+/// 同じコンテキストで`run_traced`を呼び出す
+pub struct NativeTrace<'a, W: Write> {
+    tracer: Tracer<'a, W>,
+    /// 実行中のテープの先頭と長さ
+    tape: *const u8,
+    tape_len: usize,
+    /// トレース関数の中で起きたエラー。最初の1つだけ残し、以降は記録しない
+    error: Option<std::io::Error>,
+}
+
+impl<'a, W: Write> NativeTrace<'a, W> {
+    pub fn new(tracer: Tracer<'a, W>) -> Box<Self> {
+        Box::new(Self {
+            tracer,
+            tape: std::ptr::null(),
+            tape_len: 0,
+            error: None,
+        })
+    }
+
+    pub fn hook(&self) -> TraceHook {
+        trace_hook::<W>
+    }
+
+    pub fn context(self: &mut Box<Self>) -> *mut c_void {
+        &mut **self as *mut Self as *mut c_void
+    }
+
+    pub fn finish(self) -> std::io::Result<()> {
+        self.tracer.finish()
+    }
+}
+
+/// トレース関数を埋め込んだコードを実行する。`trace`はコンパイル時に渡したものと同じでなければならない
+pub fn run_traced<W: Write>(code: &[u8], trace: &mut Box<NativeTrace<W>>) -> std::io::Result<()> {
+    let mut memory = vec![0u8; 30000];
+    trace.tape = memory.as_ptr();
+    trace.tape_len = memory.len();
+    run_with_memory(code, &mut memory);
+    trace.tape = std::ptr::null();
+    match trace.error.take() {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+unsafe extern "C" fn trace_hook<W: Write>(context: *mut c_void, ip: u64, pointer: *mut u8) {
+    let trace = &mut *(context as *mut NativeTrace<W>);
+    if trace.error.is_some() {
+        return;
+    }
+    let memory = std::slice::from_raw_parts(trace.tape, trace.tape_len);
+    let pointer = pointer.offset_from(trace.tape) as usize;
+    if let Err(error) = trace.tracer.step(ip as usize, pointer, memory) {
+        trace.error = Some(error);
+    }
+}
+
+/// ネイティブコードの入出力関数`io_putchar`と`io_getchar`が読み書きする先
+///
+/// `attach`に渡した処理を実行する間、このスレッドの入出力関数はこれを使う
+pub struct NativeIo<'a> {
+    input: &'a mut dyn Read,
+    output: &'a mut dyn Write,
+    eof: Eof,
+    /// 読んだ入力のバイト数。入力の終わりに達した後の`,`は数えない
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    /// 入出力で起きた最初のエラー。起きると入出力関数が負の値を返し、生成したコードは実行を中断する
+    pub error: Option<std::io::Error>,
+}
+
+impl<'a> NativeIo<'a> {
+    /// `Eof::Unchanged`には対応しない。入出力関数には現在のセルが渡らないので、0を返す
+    pub fn new(input: &'a mut dyn Read, output: &'a mut dyn Write, eof: Eof) -> Self {
+        Self {
+            input,
+            output,
+            eof,
+            input_bytes: 0,
+            output_bytes: 0,
+            error: None,
+        }
+    }
+
+    /// `f`を実行する間、このスレッドの入出力関数がこれを読み書きするようにする
+    pub fn attach<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        /// 入出力関数の中で別のネイティブコードを実行しても戻せるように、前の値を残しておく
+        struct Restore(*mut c_void);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                NATIVE_IO.set(self.0);
+            }
+        }
+        let _restore = Restore(NATIVE_IO.replace(self as *mut NativeIo as *mut c_void));
+        f()
+    }
+}
+
+thread_local! {
+    /// このスレッドで実行中のネイティブコードの`NativeIo`
+    static NATIVE_IO: Cell<*mut c_void> = const { Cell::new(std::ptr::null_mut()) };
+}
+
+/// 1バイト読む。入力の終わりなら`None`を返す
+pub fn read_byte(input: &mut dyn Read) -> std::io::Result<Option<u8>> {
+    let mut buffer = [0];
+    match input.read_exact(&mut buffer) {
+        Ok(()) => Ok(Some(buffer[0])),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// `NativeIo`に書き込む出力関数
+///
+/// # Safety
+/// `NativeIo::attach`の中で実行するコードからだけ呼び出せる
+pub unsafe extern "C" fn io_putchar(c: i32) -> i32 {
+    let io = &mut *(NATIVE_IO.get() as *mut NativeIo);
+    if io.error.is_some() {
+        return -1;
+    }
+    match io.output.write_all(&[c as u8]) {
+        Ok(()) => {
+            io.output_bytes += 1;
+            c
+        }
+        Err(error) => {
+            io.error = Some(error);
+            -1
+        }
+    }
+}
+
+/// `NativeIo`から読む入力関数
+///
+/// # Safety
+/// `NativeIo::attach`の中で実行するコードからだけ呼び出せる
+pub unsafe extern "C" fn io_getchar() -> i32 {
+    let io = &mut *(NATIVE_IO.get() as *mut NativeIo);
+    if io.error.is_some() {
+        return -1;
+    }
+    // 入力を待つ前に、それまでの出力を見えるようにする
+    let result = io.output.flush().and_then(|()| read_byte(io.input));
+    match result {
+        Ok(Some(value)) => {
+            io.input_bytes += 1;
+            value as i32
+        }
+        Ok(None) => match io.eof {
+            Eof::MinusOne => u8::MAX as i32,
+            _ => 0,
+        },
+        Err(error) => {
+            io.error = Some(error);
+            -1
+        }
+    }
 }
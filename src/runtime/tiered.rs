@@ -0,0 +1,140 @@
+//! VMで実行を始め、何度も繰り返されるループだけをネイティブコードにコンパイルして実行する
+//!
+//! VMはループの終わりで繰り返すたびにループごとのカウンタを増やし、しきい値に達したら
+//! そのループを`compiler::x86_64`でコンパイルする。コンパイルしたループは同じテープの上で
+//! ネイティブコードとして実行し、ループを抜けたらVMに戻ってループの次の命令から続ける
+
+use std::io::Write;
+
+use super::{
+    native::{io_getchar, io_putchar, NativeFunction, NativeIo},
+    vm::{VMInstruction, VM},
+    Input,
+};
+use crate::{
+    compiler::{self, x86_64::CompileOptions},
+    instruction::Instruction,
+    tape::Eof,
+};
+
+/// ループをコンパイルするまでの繰り返し回数の既定値
+pub const DEFAULT_THRESHOLD: u64 = 1000;
+
+/// 実行中にコンパイルしたループの数などの統計
+#[derive(Debug, Clone, Default)]
+pub struct TierStats {
+    /// コンパイルしたループの数
+    pub compiled_loops: usize,
+    /// ネイティブコードに入った回数
+    pub native_entries: u64,
+    /// VMで実行した命令の数
+    pub vm_steps: u64,
+}
+
+/// 実行中のループの状態
+struct LoopState<'a> {
+    /// `Instruction::Loop`の本体
+    body: &'a [Instruction],
+    /// 対応する`JumpIfNotZero`の位置
+    end: usize,
+    iterations: u64,
+    native: Option<NativeFunction>,
+}
+
+/// プログラムを段階的に実行する。`threshold`回繰り返したループをネイティブコードにコンパイルする
+///
+/// コンパイルしたループの中で入出力が失敗したら、そこで実行をやめてエラーを返す
+pub fn run(
+    instructions: &[Instruction],
+    input: Input,
+    threshold: u64,
+) -> std::io::Result<TierStats> {
+    let compiled = compiler::vm::compile(instructions);
+    // `JumpIfZero`の位置からループを引けるようにする。ifの位置は`None`のまま
+    let mut loops = (0..compiled.len()).map(|_| None).collect::<Vec<_>>();
+    collect_loops(instructions, 0, &mut loops);
+
+    let mut stats = TierStats::default();
+    let mut vm = VM::new(&compiled);
+    vm.input = input;
+    // VMと出力の順序やバッファを共有するため、libcではなくRustの標準出力を使う
+    let mut stdout = std::io::stdout();
+    while !vm.is_finished() {
+        let ip = vm.instruction_pointer;
+        let start = match compiled[ip] {
+            VMInstruction::JumpIfZero(_) => Some(ip),
+            VMInstruction::JumpIfNotZero(start) => Some(start),
+            _ => None,
+        };
+        if vm.memory[vm.pointer] != 0 {
+            if let Some(state) = start.and_then(|start| loops[start].as_mut()) {
+                if ip == state.end {
+                    state.iterations += 1;
+                }
+                if state.native.is_none() && state.iterations >= threshold {
+                    state.native = Some(compile_loop(state.body));
+                    stats.compiled_loops += 1;
+                }
+                // ループの先頭でも終わりでも、値が0でなければ本体から実行することになる
+                if let Some(native) = &state.native {
+                    let tape = vm.memory.as_mut_ptr();
+                    let mut io = NativeIo::new(&mut vm.input, &mut stdout, Eof::Zero);
+                    let pointer =
+                        io.attach(|| unsafe { native.call(tape.add(vm.pointer)) }) as *mut u8;
+                    if let Some(error) = io.error {
+                        return Err(error);
+                    }
+                    // VMは1バイトごとに書き出すので、それに合わせてループの出力も見えるようにする
+                    std::io::stdout().flush()?;
+                    vm.pointer = unsafe { pointer.offset_from(tape) } as usize;
+                    vm.instruction_pointer = state.end + 1;
+                    stats.native_entries += 1;
+                    continue;
+                }
+            }
+        }
+        vm.step();
+        stats.vm_steps += 1;
+    }
+    Ok(stats)
+}
+
+/// IRのネストからループを集める。戻り値はVMの命令列上での命令の数
+fn collect_loops<'a>(
+    instructions: &'a [Instruction],
+    offset: usize,
+    loops: &mut [Option<LoopState<'a>>],
+) -> usize {
+    let mut position = offset;
+    for inst in instructions {
+        match inst {
+            Instruction::Loop(body) => {
+                let len = collect_loops(body, position + 1, loops);
+                loops[position] = Some(LoopState {
+                    body,
+                    end: position + 1 + len,
+                    iterations: 0,
+                    native: None,
+                });
+                position += len + 2;
+            }
+            Instruction::IfNotZero(body) => {
+                position += collect_loops(body, position + 1, loops) + 1;
+            }
+            _ => position += 1,
+        }
+    }
+    position - offset
+}
+
+/// ループ全体を、終了したときのポインタを返す関数にコンパイルする
+fn compile_loop(body: &[Instruction]) -> NativeFunction {
+    let options = CompileOptions {
+        putchar: Some(io_putchar),
+        getchar: Some(io_getchar),
+        return_pointer: true,
+        ..Default::default()
+    };
+    let code = compiler::x86_64::compile(&[Instruction::Loop(body.to_vec())], options);
+    NativeFunction::new(&code)
+}
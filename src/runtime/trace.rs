@@ -0,0 +1,171 @@
+//! 実行のトレースをファイルに書き出す
+//!
+//! VMとネイティブコードのどちらからも、命令を実行する直前の状態を`Tracer::step`に渡す。
+//! 命令の位置は`compiler::vm::compile`の出力での位置で表す
+
+use std::io::{self, Write};
+
+use super::vm::VMInstruction;
+
+#[cfg(test)]
+mod tests;
+
+/// バイナリ形式のファイルの先頭に置くマジックナンバー
+pub const BINARY_MAGIC: &[u8; 4] = b"BFT1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TraceFormat {
+    /// 1行に1つのJSONオブジェクト
+    #[value(help = "One JSON object per line")]
+    Jsonl,
+    /// ヘッダ付きのCSV。テープの値は空白区切りで1つの列に入れる
+    #[value(help = "CSV with a header. Tape values are space-separated in a single column")]
+    Csv,
+    /// リトルエンディアンの固定長ヘッダと可変長のレコード。
+    ///
+    /// ヘッダは`BFT1`とウィンドウの半径(u32)。レコードは
+    /// ステップ(u64)、命令の位置(u32)、命令の種類(u8、`VMInstruction::opcode`を参照)、
+    /// ポインタ(u32)、ウィンドウの先頭のセル(u32)、セルの数(u16)、セルの値
+    #[value(
+        help = "Little-endian `BFT1` header and window radius, followed by variable-length records"
+    )]
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "snake_case")]
+pub enum TraceFilter {
+    /// ループに入るときと、ループの終わりで繰り返すか判定するとき
+    #[value(help = "Entering a loop and deciding whether to repeat it at its end")]
+    LoopHeads,
+    /// `.`と`,`
+    #[value(help = "`.` and `,`")]
+    Io,
+}
+
+pub struct TraceOptions {
+    pub format: TraceFormat,
+    /// ポインタの前後に記録するセルの数
+    pub window: usize,
+    /// 条件に合うステップのうち、このステップ数ごとに1つ記録する
+    pub every: u64,
+    /// 空の場合はすべての命令を記録する。複数指定した場合はいずれかに合えば記録する
+    pub filters: Vec<TraceFilter>,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        Self {
+            format: TraceFormat::Jsonl,
+            window: 8,
+            every: 1,
+            filters: Vec::new(),
+        }
+    }
+}
+
+pub struct Tracer<'a, W: Write> {
+    out: W,
+    options: TraceOptions,
+    instructions: &'a [VMInstruction],
+    /// 実行した命令の数
+    step: u64,
+    /// 条件に合ったステップの数
+    matched: u64,
+}
+
+impl<'a, W: Write> Tracer<'a, W> {
+    pub fn new(
+        mut out: W,
+        options: TraceOptions,
+        instructions: &'a [VMInstruction],
+    ) -> io::Result<Self> {
+        match options.format {
+            TraceFormat::Jsonl => {}
+            TraceFormat::Csv => writeln!(out, "step,ip,instruction,pointer,window_start,window")?,
+            TraceFormat::Binary => {
+                out.write_all(BINARY_MAGIC)?;
+                out.write_all(&(options.window as u32).to_le_bytes())?;
+            }
+        }
+        Ok(Self {
+            out,
+            options,
+            instructions,
+            step: 0,
+            matched: 0,
+        })
+    }
+
+    /// `ip`の命令を実行する直前に呼ぶ
+    pub fn step(&mut self, ip: usize, pointer: usize, memory: &[u8]) -> io::Result<()> {
+        let step = self.step;
+        self.step += 1;
+
+        let instruction = self.instructions[ip];
+        if !self.options.filters.is_empty()
+            && !self
+                .options
+                .filters
+                .iter()
+                .any(|&filter| matches(filter, instruction))
+        {
+            return Ok(());
+        }
+        let matched = self.matched;
+        self.matched += 1;
+        if !matched.is_multiple_of(self.options.every.max(1)) {
+            return Ok(());
+        }
+
+        let start = pointer
+            .saturating_sub(self.options.window)
+            .min(memory.len());
+        let end = (pointer + self.options.window + 1).min(memory.len());
+        let window = &memory[start..end];
+        match self.options.format {
+            TraceFormat::Jsonl => writeln!(
+                self.out,
+                r#"{{"step":{},"ip":{},"instruction":"{:?}","pointer":{},"window_start":{},"window":{:?}}}"#,
+                step, ip, instruction, pointer, start, window
+            ),
+            TraceFormat::Csv => writeln!(
+                self.out,
+                "{},{},\"{:?}\",{},{},{}",
+                step,
+                ip,
+                instruction,
+                pointer,
+                start,
+                window
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            TraceFormat::Binary => {
+                self.out.write_all(&step.to_le_bytes())?;
+                self.out.write_all(&(ip as u32).to_le_bytes())?;
+                self.out.write_all(&[instruction.opcode()])?;
+                self.out.write_all(&(pointer as u32).to_le_bytes())?;
+                self.out.write_all(&(start as u32).to_le_bytes())?;
+                self.out.write_all(&(window.len() as u16).to_le_bytes())?;
+                self.out.write_all(window)
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn matches(filter: TraceFilter, instruction: VMInstruction) -> bool {
+    match filter {
+        TraceFilter::LoopHeads => matches!(
+            instruction,
+            VMInstruction::JumpIfZero(_) | VMInstruction::JumpIfNotZero(_)
+        ),
+        TraceFilter::Io => matches!(instruction, VMInstruction::PutChar | VMInstruction::GetChar),
+    }
+}
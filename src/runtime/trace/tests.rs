@@ -0,0 +1,92 @@
+use super::*;
+
+const INSTRUCTIONS: [VMInstruction; 4] = [
+    VMInstruction::Increment,
+    VMInstruction::JumpIfZero(3),
+    VMInstruction::PutChar,
+    VMInstruction::JumpIfNotZero(1),
+];
+
+/// 3セルのテープで`+`、`[`、`.`を1回ずつ実行したときのトレース
+fn trace(options: TraceOptions) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut tracer = Tracer::new(&mut out, options, &INSTRUCTIONS).unwrap();
+    tracer.step(0, 1, &[7, 0, 9]).unwrap();
+    tracer.step(1, 1, &[7, 1, 9]).unwrap();
+    tracer.step(2, 1, &[7, 1, 9]).unwrap();
+    tracer.finish().unwrap();
+    out
+}
+
+#[test]
+fn jsonl() {
+    let options = TraceOptions {
+        window: 1,
+        ..Default::default()
+    };
+    assert_eq!(
+        String::from_utf8(trace(options)).unwrap(),
+        concat!(
+            r#"{"step":0,"ip":0,"instruction":"Increment","pointer":1,"window_start":0,"window":[7, 0, 9]}"#,
+            "\n",
+            r#"{"step":1,"ip":1,"instruction":"JumpIfZero(3)","pointer":1,"window_start":0,"window":[7, 1, 9]}"#,
+            "\n",
+            r#"{"step":2,"ip":2,"instruction":"PutChar","pointer":1,"window_start":0,"window":[7, 1, 9]}"#,
+            "\n",
+        )
+    );
+}
+
+#[test]
+fn csv_clips_window_to_the_tape() {
+    let options = TraceOptions {
+        format: TraceFormat::Csv,
+        window: 8,
+        ..Default::default()
+    };
+    assert_eq!(
+        String::from_utf8(trace(options)).unwrap(),
+        "step,ip,instruction,pointer,window_start,window\n\
+         0,0,\"Increment\",1,0,7 0 9\n\
+         1,1,\"JumpIfZero(3)\",1,0,7 1 9\n\
+         2,2,\"PutChar\",1,0,7 1 9\n"
+    );
+}
+
+#[test]
+fn binary() {
+    let options = TraceOptions {
+        format: TraceFormat::Binary,
+        window: 0,
+        filters: vec![TraceFilter::Io],
+        ..Default::default()
+    };
+    let mut expected = Vec::new();
+    expected.extend_from_slice(BINARY_MAGIC);
+    expected.extend_from_slice(&0u32.to_le_bytes());
+    // `.`だけを記録する。ステップ数はすべての命令で数える
+    expected.extend_from_slice(&2u64.to_le_bytes());
+    expected.extend_from_slice(&2u32.to_le_bytes());
+    expected.push(VMInstruction::PutChar.opcode());
+    expected.extend_from_slice(&1u32.to_le_bytes());
+    expected.extend_from_slice(&1u32.to_le_bytes());
+    expected.extend_from_slice(&1u16.to_le_bytes());
+    expected.push(1);
+    assert_eq!(trace(options), expected);
+}
+
+#[test]
+fn record_every_nth_matching_step() {
+    let options = TraceOptions {
+        format: TraceFormat::Csv,
+        window: 0,
+        every: 2,
+        filters: vec![TraceFilter::LoopHeads, TraceFilter::Io],
+    };
+    // 条件に合うのは`[`と`.`で、そのうち1つ目だけを記録する
+    assert_eq!(
+        String::from_utf8(trace(options)).unwrap(),
+        "step,ip,instruction,pointer,window_start,window\n\
+         1,1,\"JumpIfZero(3)\",1,1,1\n"
+    );
+}
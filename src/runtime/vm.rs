@@ -1,4 +1,23 @@
-use std::io::{ErrorKind, Read, Write};
+use std::{
+    io::{ErrorKind, Read, Write},
+    time::Instant,
+};
+
+use self::{
+    history::{History, UndoEntry},
+    snapshot::Snapshot,
+};
+use super::{
+    limits::{Limit, LimitExceeded, Limits},
+    trace::Tracer,
+    Input,
+};
+use crate::tape::{Eof, TapeOptions};
+
+pub mod history;
+pub mod snapshot;
+#[cfg(test)]
+mod tests;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VMInstruction {
@@ -22,99 +41,508 @@ pub enum VMInstruction {
     AddValueMultipliedBy(u8, isize),
     SubtractValueMultipliedBy(u8, isize),
     Negate,
+
+    /// ポインタを移動してから、移動先の値に加算する
+    MoveAdd(isize, u8),
+    /// ポインタが指す値を設定する
+    SetValue(u8),
+    /// ポインタが指す値が0になるまで、ポインタを移動し続ける
+    Scan(isize),
+}
+
+impl VMInstruction {
+    /// トレースやバイトコードで命令の種類を表す番号。宣言順に振る
+    pub fn opcode(self) -> u8 {
+        match self {
+            VMInstruction::Increment => 0,
+            VMInstruction::Decrement => 1,
+            VMInstruction::PointerIncrement => 2,
+            VMInstruction::PointerDecrement => 3,
+            VMInstruction::PutChar => 4,
+            VMInstruction::GetChar => 5,
+            VMInstruction::JumpIfZero(_) => 6,
+            VMInstruction::JumpIfNotZero(_) => 7,
+            VMInstruction::Add(_) => 8,
+            VMInstruction::Subtract(_) => 9,
+            VMInstruction::SetZero => 10,
+            VMInstruction::PointerAdd(_) => 11,
+            VMInstruction::PointerSubtract(_) => 12,
+            VMInstruction::AddValueAt(_) => 13,
+            VMInstruction::SubtractValueAt(_) => 14,
+            VMInstruction::AddValueMultipliedBy(_, _) => 15,
+            VMInstruction::SubtractValueMultipliedBy(_, _) => 16,
+            VMInstruction::Negate => 17,
+            VMInstruction::MoveAdd(_, _) => 18,
+            VMInstruction::SetValue(_) => 19,
+            VMInstruction::Scan(_) => 20,
+        }
+    }
+}
+
+/// 1命令ずつ実行できるVM
+pub struct VM<'a> {
+    instructions: &'a [VMInstruction],
+    pub memory: Vec<u8>,
+    pub pointer: usize,
+    pub instruction_pointer: usize,
+    /// `,`を実行した回数。入力の終わりに達した後も数える
+    pub input_position: u64,
+    /// `.`を実行した回数
+    pub output_position: u64,
+    /// 入力の終わりに達したときの`,`の動作
+    pub eof: Eof,
+    /// `,`で読む入力
+    pub input: Input,
+    /// `Some`なら、出力を標準出力に書かずにここに貯める
+    pub output: Option<Vec<u8>>,
+    history: Option<History>,
 }
 
-pub fn run(instructions: &[VMInstruction], trace: bool) {
-    let mut memory = vec![0u8; 30000];
-    let mut pointer = 0;
-    let mut instruction_pointer = 0;
-    while instruction_pointer < instructions.len() {
+pub fn run(instructions: &[VMInstruction], trace: bool) -> Result<(), LimitExceeded> {
+    run_with_tape(instructions, TapeOptions::default(), Input::Stdin, trace)
+}
+
+/// テープの長さと入力の終わりの扱いを指定して実行する。セルは8ビットのみ
+///
+/// テープは伸ばさないので、ポインタがテープの外に出ようとしたら実行を止める
+pub fn run_with_tape(
+    instructions: &[VMInstruction],
+    tape: TapeOptions,
+    input: Input,
+    trace: bool,
+) -> Result<(), LimitExceeded> {
+    let mut vm = VM::new(instructions);
+    vm.memory = vec![0; tape.size];
+    vm.eof = tape.eof;
+    vm.input = input;
+    let mut steps = 0;
+    while !vm.is_finished() {
+        let reach = vm.reach();
+        if reach < 0 || reach as usize >= vm.memory.len() {
+            let limit = Limit::Tape((reach >= 0).then_some(vm.memory.len()));
+            return Err(LimitExceeded {
+                limit,
+                steps,
+                instruction_pointer: Some(vm.instruction_pointer),
+                pointer: vm.pointer,
+                memory: vm.memory,
+            });
+        }
         if trace {
             println!(
                 "ip: {}, inst: {:?}, ptr: {}, mem: {}\n{:?}",
-                instruction_pointer,
-                instructions[instruction_pointer],
-                pointer,
-                memory[pointer],
-                &memory[0..30]
+                vm.instruction_pointer,
+                instructions[vm.instruction_pointer],
+                vm.pointer,
+                vm.memory[vm.pointer],
+                &vm.memory[0..vm.memory.len().min(30)]
             );
         }
-        match instructions[instruction_pointer] {
+        vm.step();
+        steps += 1;
+    }
+    Ok(())
+}
+
+/// 実行して、出力を標準出力に書かずに返す
+pub fn run_captured(instructions: &[VMInstruction]) -> Vec<u8> {
+    let mut vm = VM::new(instructions);
+    vm.output = Some(Vec::new());
+    while !vm.is_finished() {
+        vm.step();
+    }
+    vm.output.unwrap()
+}
+
+/// 命令を実行するたびに、実行前の状態を`tracer`に渡す
+pub fn run_traced<W: Write>(
+    instructions: &[VMInstruction],
+    input: Input,
+    tracer: &mut Tracer<W>,
+) -> std::io::Result<()> {
+    let mut vm = VM::new(instructions);
+    vm.input = input;
+    while !vm.is_finished() {
+        tracer.step(vm.instruction_pointer, vm.pointer, &vm.memory)?;
+        vm.step();
+    }
+    Ok(())
+}
+
+/// `resume`の状態から実行を再開し、`snapshot_after`ステップ実行しても終わらなければ
+/// その時点の状態を返す
+///
+/// 再開するときは、保存したときまでに読んだ分の入力を読み飛ばす
+pub fn run_checkpointed(
+    instructions: &[VMInstruction],
+    input: Input,
+    resume: Option<&Snapshot>,
+    snapshot_after: Option<u64>,
+) -> anyhow::Result<Option<Snapshot>> {
+    let mut vm = VM::new(instructions);
+    vm.input = input;
+    if let Some(snapshot) = resume {
+        snapshot.restore(&mut vm)?;
+        let mut skipped = Vec::new();
+        (&mut vm.input)
+            .take(snapshot.input_position)
+            .read_to_end(&mut skipped)?;
+    }
+    let mut steps = 0;
+    while !vm.is_finished() {
+        if snapshot_after == Some(steps) {
+            return Ok(Some(Snapshot::capture(&vm)));
+        }
+        vm.step();
+        steps += 1;
+    }
+    Ok(None)
+}
+
+/// 制限を確かめながら実行する。テープは必要に応じて`limits.max_tape`まで伸ばす
+pub fn run_limited(
+    instructions: &[VMInstruction],
+    input: Input,
+    limits: &Limits,
+) -> Result<(), LimitExceeded> {
+    let mut vm = VM::new(instructions);
+    vm.input = input;
+    // 上限が既定のテープより短ければ、最初からその長さにする
+    if let Some(max) = limits.max_tape {
+        vm.memory.truncate(max);
+    }
+    let started = Instant::now();
+    let mut steps = 0;
+    let mut output = 0;
+    while !vm.is_finished() {
+        let instruction = instructions[vm.instruction_pointer];
+        let exceeded = |vm: VM, limit| LimitExceeded {
+            limit,
+            steps,
+            instruction_pointer: Some(vm.instruction_pointer),
+            pointer: vm.pointer,
+            memory: vm.memory,
+        };
+
+        if let Some(max) = limits.max_steps.filter(|&max| steps >= max) {
+            return Err(exceeded(vm, Limit::Steps(max)));
+        }
+        // 時刻の取得は遅いので、ときどきだけ確かめる
+        if let Some(timeout) = limits.timeout {
+            if steps.is_multiple_of(1024) && started.elapsed() >= timeout {
+                return Err(exceeded(vm, Limit::Timeout(timeout)));
+            }
+        }
+        if instruction == VMInstruction::PutChar {
+            if let Some(max) = limits.max_output.filter(|&max| output >= max) {
+                return Err(exceeded(vm, Limit::Output(max)));
+            }
+            output += 1;
+        }
+
+        // 命令が移動するか読み書きするセルがテープに収まるようにする
+        let reach = vm.reach();
+        if reach < 0 {
+            return Err(exceeded(vm, Limit::Tape(limits.max_tape)));
+        }
+        let needed = reach as usize + 1;
+        if needed > vm.memory.len() {
+            let max = limits.max_tape.unwrap_or(usize::MAX);
+            if needed > max {
+                return Err(exceeded(vm, Limit::Tape(limits.max_tape)));
+            }
+            let len = needed.max(vm.memory.len() * 2).min(max);
+            vm.memory.resize(len, 0);
+        }
+
+        vm.step();
+        steps += 1;
+    }
+    Ok(())
+}
+
+impl<'a> VM<'a> {
+    pub fn new(instructions: &'a [VMInstruction]) -> Self {
+        Self {
+            instructions,
+            memory: vec![0u8; 30000],
+            pointer: 0,
+            instruction_pointer: 0,
+            input_position: 0,
+            output_position: 0,
+            eof: Eof::Zero,
+            input: Input::Stdin,
+            output: None,
+            history: None,
+        }
+    }
+
+    /// 実行履歴を記録し、`step_back`で巻き戻せるVMを作る
+    pub fn with_history(instructions: &'a [VMInstruction]) -> Self {
+        let mut vm = Self::new(instructions);
+        vm.history = Some(History::new(&vm.memory));
+        vm
+    }
+
+    pub fn instructions(&self) -> &'a [VMInstruction] {
+        self.instructions
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.instruction_pointer >= self.instructions.len()
+    }
+
+    /// 次の命令が移動する先か、読み書きするセルの位置。`Scan`は現在のセルしか考えない
+    pub fn reach(&self) -> isize {
+        let pointer = self.pointer as isize;
+        match self.instructions[self.instruction_pointer] {
+            VMInstruction::PointerIncrement => pointer + 1,
+            VMInstruction::PointerDecrement => pointer - 1,
+            VMInstruction::PointerAdd(value) => pointer + value as isize,
+            VMInstruction::PointerSubtract(value) => pointer - value as isize,
+            VMInstruction::AddValueAt(at)
+            | VMInstruction::SubtractValueAt(at)
+            | VMInstruction::AddValueMultipliedBy(_, at)
+            | VMInstruction::SubtractValueMultipliedBy(_, at)
+            | VMInstruction::MoveAdd(at, _) => pointer + at,
+            _ => pointer,
+        }
+    }
+
+    /// `instruction_pointer`が指す命令を1つ実行する
+    pub fn step(&mut self) {
+        let memory = &mut self.memory;
+        let pointer = &mut self.pointer;
+        let instruction_pointer = &mut self.instruction_pointer;
+        let history = &mut self.history;
+        if let Some(history) = history {
+            // MoveAddだけは移動先のセルを書き換える
+            let written = match self.instructions[*instruction_pointer] {
+                VMInstruction::MoveAdd(offset, _) => (*pointer as isize + offset) as usize,
+                _ => *pointer,
+            };
+            history.record(UndoEntry {
+                instruction_pointer: *instruction_pointer,
+                pointer: *pointer,
+                cell: written,
+                old_value: memory.get(written).copied().unwrap_or(0),
+                io: None,
+            });
+        }
+        match self.instructions[*instruction_pointer] {
             VMInstruction::Increment => {
-                memory[pointer] = memory[pointer].wrapping_add(1);
+                memory[*pointer] = memory[*pointer].wrapping_add(1);
             }
             VMInstruction::Decrement => {
-                memory[pointer] = memory[pointer].wrapping_sub(1);
+                memory[*pointer] = memory[*pointer].wrapping_sub(1);
             }
             VMInstruction::PointerIncrement => {
-                pointer += 1;
+                *pointer += 1;
             }
             VMInstruction::PointerDecrement => {
-                pointer -= 1;
+                *pointer -= 1;
             }
             VMInstruction::PutChar => {
-                print!("{}", memory[pointer] as char);
-                std::io::stdout().flush().unwrap();
+                self.output_position += 1;
+                let value = memory[*pointer];
+                // 巻き戻した後に再実行するときは、既に出力した文字を再び出力しない
+                let replayed = history
+                    .as_mut()
+                    .is_some_and(|history| history.record_output(value));
+                if !replayed {
+                    if let Some(output) = &mut self.output {
+                        output.push(value);
+                    } else {
+                        let mut stdout = std::io::stdout();
+                        stdout.write_all(&[value]).unwrap();
+                        stdout.flush().unwrap();
+                    }
+                }
             }
             VMInstruction::GetChar => {
-                let mut input = [0];
-                match std::io::stdin().read_exact(&mut input) {
-                    Ok(()) => {
-                        memory[pointer] = input[0];
+                self.input_position += 1;
+                // 巻き戻した後に再実行するときは、記録しておいた入力を読む
+                if let Some(value) = history.as_mut().and_then(|history| history.replay_input()) {
+                    memory[*pointer] = value;
+                } else {
+                    let mut input = [0];
+                    match self.input.read_exact(&mut input) {
+                        Ok(()) => {
+                            memory[*pointer] = input[0];
+                        }
+                        Err(e) if e.kind() == ErrorKind::UnexpectedEof => match self.eof {
+                            Eof::Zero => memory[*pointer] = 0,
+                            Eof::MinusOne => memory[*pointer] = u8::MAX,
+                            Eof::Unchanged => {}
+                        },
+                        Err(e) => {
+                            panic!("Error reading input: {:?}", e);
+                        }
                     }
-                    Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
-                        memory[pointer] = 0;
-                    }
-                    Err(e) => {
-                        panic!("Error reading input: {:?}", e);
+                    if let Some(history) = history {
+                        history.record_input(memory[*pointer]);
                     }
                 }
             }
             VMInstruction::JumpIfZero(jump) => {
-                if memory[pointer] == 0 {
-                    instruction_pointer = jump;
+                if memory[*pointer] == 0 {
+                    *instruction_pointer = jump;
                 }
             }
             VMInstruction::JumpIfNotZero(jump) => {
-                if memory[pointer] != 0 {
-                    instruction_pointer = jump;
+                if memory[*pointer] != 0 {
+                    *instruction_pointer = jump;
                 }
             }
             VMInstruction::Add(value) => {
-                memory[pointer] = memory[pointer].wrapping_add(value);
+                memory[*pointer] = memory[*pointer].wrapping_add(value);
             }
             VMInstruction::Subtract(value) => {
-                memory[pointer] = memory[pointer].wrapping_sub(value);
+                memory[*pointer] = memory[*pointer].wrapping_sub(value);
             }
             VMInstruction::SetZero => {
-                memory[pointer] = 0;
+                memory[*pointer] = 0;
             }
             VMInstruction::PointerAdd(value) => {
-                pointer += value;
+                *pointer += value;
             }
             VMInstruction::PointerSubtract(value) => {
-                pointer -= value;
+                *pointer -= value;
             }
             VMInstruction::AddValueAt(at) => {
-                memory[pointer] =
-                    memory[pointer].wrapping_add(memory[(pointer as isize + at) as usize]);
+                memory[*pointer] =
+                    memory[*pointer].wrapping_add(memory[(*pointer as isize + at) as usize]);
             }
             VMInstruction::SubtractValueAt(at) => {
-                memory[pointer] =
-                    memory[pointer].wrapping_sub(memory[(pointer as isize + at) as usize]);
+                memory[*pointer] =
+                    memory[*pointer].wrapping_sub(memory[(*pointer as isize + at) as usize]);
             }
             VMInstruction::AddValueMultipliedBy(value, at) => {
-                memory[pointer] = memory[pointer]
-                    .wrapping_add(value.wrapping_mul(memory[(pointer as isize + at) as usize]));
+                memory[*pointer] = memory[*pointer]
+                    .wrapping_add(value.wrapping_mul(memory[(*pointer as isize + at) as usize]));
             }
             VMInstruction::SubtractValueMultipliedBy(value, at) => {
-                memory[pointer] = memory[pointer]
-                    .wrapping_sub(value.wrapping_mul(memory[(pointer as isize + at) as usize]));
+                memory[*pointer] = memory[*pointer]
+                    .wrapping_sub(value.wrapping_mul(memory[(*pointer as isize + at) as usize]));
             }
             VMInstruction::Negate => {
-                memory[pointer] = memory[pointer].wrapping_neg();
+                memory[*pointer] = memory[*pointer].wrapping_neg();
+            }
+            VMInstruction::MoveAdd(offset, value) => {
+                *pointer = (*pointer as isize + offset) as usize;
+                memory[*pointer] = memory[*pointer].wrapping_add(value);
+            }
+            VMInstruction::SetValue(value) => {
+                memory[*pointer] = value;
+            }
+            VMInstruction::Scan(step) => {
+                while memory[*pointer] != 0 {
+                    *pointer = (*pointer as isize + step) as usize;
+                }
+            }
+        }
+        *instruction_pointer += 1;
+
+        if let Some(history) = history {
+            history.snapshot_if_needed(memory, *pointer, *instruction_pointer);
+        }
+    }
+
+    /// 直前に実行した命令を取り消す。記録の先頭まで巻き戻っている場合は`false`を返す
+    ///
+    /// 実行履歴を記録していない場合はパニックする
+    pub fn step_back(&mut self) -> bool {
+        let history = self.history.as_ref().expect("history is not recorded");
+        if history.time() <= history.earliest_time() {
+            return false;
+        }
+        let time = history.time() - 1;
+        self.seek(time);
+        true
+    }
+
+    /// 実行履歴を使って、指定した時刻(実行した命令の数)の状態に移動する
+    pub fn seek(&mut self, time: u64) {
+        let history = self.history.as_mut().expect("history is not recorded");
+        let (input, output) = history.io_positions();
+        if time < history.oldest_undo_time() {
+            // 取り消し記録より前にはスナップショットから再実行して移動する
+            let snapshot = history.restore(time);
+            self.memory.clone_from(&snapshot.memory);
+            self.pointer = snapshot.pointer;
+            self.instruction_pointer = snapshot.instruction_pointer;
+        }
+        while history.time() > time {
+            let Some(entry) = history.undo() else {
+                break;
+            };
+            self.instruction_pointer = entry.instruction_pointer;
+            self.pointer = entry.pointer;
+            if let Some(cell) = self.memory.get_mut(entry.cell) {
+                *cell = entry.old_value;
+            }
+        }
+        let (rewound_input, rewound_output) = history.io_positions();
+        self.input_position -= (input - rewound_input) as u64;
+        self.output_position -= (output - rewound_output) as u64;
+        while self.time() < time && !self.is_finished() {
+            self.step();
+        }
+    }
+
+    /// 実行した命令の数。実行履歴を記録していない場合は0
+    pub fn time(&self) -> u64 {
+        self.history.as_ref().map_or(0, |history| history.time())
+    }
+
+    /// 指定したセルの値が最後に変化した時刻と、そのとき実行した命令の位置
+    ///
+    /// 実行履歴を記録していない場合はパニックする
+    pub fn last_change(&mut self, cell: usize) -> Option<(u64, usize)> {
+        let history = self.history.as_ref().expect("history is not recorded");
+        if let Some(change) = history.last_change_in_undo_log(cell, self.memory[cell]) {
+            return Some(change);
+        }
+
+        // 取り消し記録より前は、新しいスナップショットから順に再実行して探す
+        let now = history.time();
+        let mut end = history.oldest_undo_time();
+        let starts = history.snapshot_times().rev().collect::<Vec<_>>();
+        for start in starts {
+            if start >= end {
+                continue;
+            }
+            self.seek(start);
+            let mut change = None;
+            while self.time() < end {
+                let before = self.memory[cell];
+                let (time, ip) = (self.time(), self.instruction_pointer);
+                self.step();
+                if self.memory[cell] != before {
+                    change = Some((time, ip));
+                }
+            }
+            if change.is_some() {
+                self.seek(now);
+                return change;
             }
+            end = start;
+        }
+        self.seek(now);
+        None
+    }
+
+    /// セルやポインタを直接書き換えたときに呼ぶ。それまでの実行履歴は使えなくなるので破棄する
+    pub fn reset_history(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.reset(&self.memory, self.pointer, self.instruction_pointer);
+        }
+    }
+
+    /// 保存した状態を読み込んだときに呼ぶ。実行履歴に加えて、記録した入出力も破棄する
+    fn restart_history(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.reset_io(&self.memory, self.pointer, self.instruction_pointer);
         }
-        instruction_pointer += 1;
     }
 }
@@ -0,0 +1,232 @@
+//! 逆実行のための実行履歴
+//!
+//! 直近の命令は1命令ごとの取り消し記録で巻き戻す。
+//! それより前には定期的に取ったスナップショットから再実行して戻る
+
+use std::collections::VecDeque;
+
+/// 取り消し記録の最大数
+const UNDO_LIMIT: usize = 100_000;
+/// スナップショットを取る間隔の初期値
+const SNAPSHOT_INTERVAL: u64 = 10_000;
+/// スナップショットの最大数。超えたら半分に間引いて間隔を2倍にする
+const SNAPSHOT_LIMIT: usize = 256;
+
+/// 1命令を取り消すための記録
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UndoEntry {
+    pub instruction_pointer: usize,
+    pub pointer: usize,
+    /// 命令が書き換えうるセル。命令が書き換えるのはこのセルだけ
+    pub cell: usize,
+    /// 実行前の`cell`の値
+    pub old_value: u8,
+    pub io: Option<IoEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoEvent {
+    Input(u8),
+    Output(u8),
+}
+
+pub struct Snapshot {
+    pub time: u64,
+    pub memory: Vec<u8>,
+    pub pointer: usize,
+    pub instruction_pointer: usize,
+    input_position: usize,
+    output_count: usize,
+}
+
+pub struct History {
+    /// 実行した命令の数
+    time: u64,
+    undo: VecDeque<UndoEntry>,
+    snapshots: Vec<Snapshot>,
+    snapshot_interval: u64,
+    /// これまでに読んだ入力
+    input: Vec<u8>,
+    /// 現在の時刻までに読んだ入力の数
+    input_position: usize,
+    /// 現在の時刻までに出力した文字の数
+    output_count: usize,
+    /// 実際に出力した文字の数
+    output_emitted: usize,
+}
+
+impl History {
+    pub fn new(memory: &[u8]) -> Self {
+        let mut history = Self {
+            time: 0,
+            undo: VecDeque::new(),
+            snapshots: Vec::new(),
+            snapshot_interval: SNAPSHOT_INTERVAL,
+            input: Vec::new(),
+            input_position: 0,
+            output_count: 0,
+            output_emitted: 0,
+        };
+        history.take_snapshot(memory, 0, 0);
+        history
+    }
+
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// 巻き戻せる最も古い時刻
+    pub fn earliest_time(&self) -> u64 {
+        self.snapshots[0].time
+    }
+
+    /// 取り消し記録で巻き戻せる最も古い時刻
+    pub fn oldest_undo_time(&self) -> u64 {
+        self.time - self.undo.len() as u64
+    }
+
+    pub fn snapshot_times(&self) -> impl DoubleEndedIterator<Item = u64> + '_ {
+        self.snapshots.iter().map(|snapshot| snapshot.time)
+    }
+
+    /// 現在の時刻までに読んだ入力と出力した文字の数
+    pub(super) fn io_positions(&self) -> (usize, usize) {
+        (self.input_position, self.output_count)
+    }
+
+    pub(super) fn record(&mut self, entry: UndoEntry) {
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(entry);
+        self.time += 1;
+    }
+
+    /// 出力を記録する。巻き戻す前に既に出力した文字であれば`true`を返す
+    pub(super) fn record_output(&mut self, value: u8) -> bool {
+        self.set_io(IoEvent::Output(value));
+        self.output_count += 1;
+        if self.output_count <= self.output_emitted {
+            return true;
+        }
+        self.output_emitted = self.output_count;
+        false
+    }
+
+    /// 巻き戻す前に読んだ入力があればそれを返す
+    pub(super) fn replay_input(&mut self) -> Option<u8> {
+        let value = *self.input.get(self.input_position)?;
+        self.input_position += 1;
+        self.set_io(IoEvent::Input(value));
+        Some(value)
+    }
+
+    pub(super) fn record_input(&mut self, value: u8) {
+        self.input.push(value);
+        self.input_position += 1;
+        self.set_io(IoEvent::Input(value));
+    }
+
+    fn set_io(&mut self, io: IoEvent) {
+        if let Some(entry) = self.undo.back_mut() {
+            entry.io = Some(io);
+        }
+    }
+
+    pub(super) fn undo(&mut self) -> Option<UndoEntry> {
+        let entry = self.undo.pop_back()?;
+        self.time -= 1;
+        match entry.io {
+            Some(IoEvent::Input(_)) => self.input_position -= 1,
+            Some(IoEvent::Output(_)) => self.output_count -= 1,
+            None => {}
+        }
+        Some(entry)
+    }
+
+    pub(super) fn snapshot_if_needed(
+        &mut self,
+        memory: &[u8],
+        pointer: usize,
+        instruction_pointer: usize,
+    ) {
+        let last = self.snapshots.last().map_or(0, |snapshot| snapshot.time);
+        if self.time.is_multiple_of(self.snapshot_interval) && self.time > last {
+            self.take_snapshot(memory, pointer, instruction_pointer);
+        }
+    }
+
+    fn take_snapshot(&mut self, memory: &[u8], pointer: usize, instruction_pointer: usize) {
+        self.snapshots.push(Snapshot {
+            time: self.time,
+            memory: memory.to_vec(),
+            pointer,
+            instruction_pointer,
+            input_position: self.input_position,
+            output_count: self.output_count,
+        });
+        if self.snapshots.len() > SNAPSHOT_LIMIT {
+            let mut i = 0;
+            self.snapshots.retain(|_| {
+                i += 1;
+                i % 2 == 1
+            });
+            self.snapshot_interval *= 2;
+        }
+    }
+
+    /// 指定した時刻以前で最も新しいスナップショットの時刻に戻り、そのスナップショットを返す
+    pub(super) fn restore(&mut self, time: u64) -> &Snapshot {
+        let index = self
+            .snapshots
+            .partition_point(|snapshot| snapshot.time <= time)
+            .max(1)
+            - 1;
+        let snapshot = &self.snapshots[index];
+        self.time = snapshot.time;
+        self.input_position = snapshot.input_position;
+        self.output_count = snapshot.output_count;
+        self.undo.clear();
+        snapshot
+    }
+
+    /// 取り消し記録の中で、指定したセルの値が最後に変化した時刻と命令の位置を探す
+    pub(super) fn last_change_in_undo_log(
+        &self,
+        cell: usize,
+        mut value: u8,
+    ) -> Option<(u64, usize)> {
+        for (i, entry) in self.undo.iter().enumerate().rev() {
+            if entry.cell != cell {
+                continue;
+            }
+            if entry.old_value != value {
+                return Some((
+                    self.oldest_undo_time() + i as u64,
+                    entry.instruction_pointer,
+                ));
+            }
+            value = entry.old_value;
+        }
+        None
+    }
+
+    /// セルやポインタが直接書き換えられたので、現在の状態から記録をやり直す。
+    /// 読んだ入力はそのまま残し、以降の出力は再び出力する
+    pub(super) fn reset(&mut self, memory: &[u8], pointer: usize, instruction_pointer: usize) {
+        self.undo.clear();
+        self.snapshots.clear();
+        self.output_emitted = self.output_count;
+        self.take_snapshot(memory, pointer, instruction_pointer);
+    }
+
+    /// 保存した状態を読み込んだので、入出力の記録も捨てて現在の状態から記録をやり直す。
+    /// 読み込む前に読んだ入力は読み込んだ状態の続きとは限らないので、以降の`,`は入力から読み直す
+    pub(super) fn reset_io(&mut self, memory: &[u8], pointer: usize, instruction_pointer: usize) {
+        self.input.clear();
+        self.input_position = 0;
+        self.output_count = 0;
+        self.output_emitted = 0;
+        self.reset(memory, pointer, instruction_pointer);
+    }
+}
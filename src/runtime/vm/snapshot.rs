@@ -0,0 +1,131 @@
+//! VMの状態をファイルに保存し、後から再開する
+//!
+//! 形式はすべてリトルエンディアンで、マジックナンバー`BFSNAP`、バージョン(u16)、
+//! プログラムの指紋(u64)、命令の位置、ポインタ、読んだ入力のバイト数、
+//! 出力したバイト数(それぞれu64)、テープの長さ(u64)とテープの内容の順に並べる
+
+use std::io::{Read, Write};
+
+use anyhow::{bail, Context};
+
+use super::{VMInstruction, VM};
+use crate::compiler::bytecode;
+
+const MAGIC: &[u8; 6] = b"BFSNAP";
+/// 指紋の計算方法を変えたときにも上げる
+const VERSION: u16 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// 保存したときに実行していたプログラムの指紋。別のプログラムで再開しないように確かめる
+    pub program: u64,
+    pub instruction_pointer: usize,
+    pub pointer: usize,
+    /// それまでに`,`を実行した回数。再開するときはこの分だけ入力を読み飛ばす
+    pub input_position: u64,
+    /// それまでに出力したバイト数。再開した後の出力はこの位置から続く
+    pub output_position: u64,
+    pub memory: Vec<u8>,
+}
+
+/// プログラムの指紋。命令列をバイトコードと同じ形式にしてハッシュを取るので、
+/// 同じVMの命令列であればどの環境やバージョンでも同じ値になる
+pub fn fingerprint(instructions: &[VMInstruction]) -> u64 {
+    // FNV-1a
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in bytecode::encode_instructions(instructions) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+impl Snapshot {
+    pub fn capture(vm: &VM) -> Self {
+        Self {
+            program: fingerprint(vm.instructions()),
+            instruction_pointer: vm.instruction_pointer,
+            pointer: vm.pointer,
+            input_position: vm.input_position,
+            output_position: vm.output_position,
+            memory: vm.memory.clone(),
+        }
+    }
+
+    /// VMを保存したときの状態に戻す。実行しているプログラムが違う場合はエラーになる
+    pub fn restore(&self, vm: &mut VM) -> anyhow::Result<()> {
+        if self.program != fingerprint(vm.instructions()) {
+            bail!("the snapshot was taken from a different program or optimization options");
+        }
+        if self.instruction_pointer > vm.instructions().len() || self.pointer >= self.memory.len() {
+            bail!("the snapshot is corrupted");
+        }
+        vm.instruction_pointer = self.instruction_pointer;
+        vm.pointer = self.pointer;
+        vm.input_position = self.input_position;
+        vm.output_position = self.output_position;
+        vm.memory.clone_from(&self.memory);
+        vm.restart_history();
+        Ok(())
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&self.program.to_le_bytes())?;
+        for value in [
+            self.instruction_pointer as u64,
+            self.pointer as u64,
+            self.input_position,
+            self.output_position,
+            self.memory.len() as u64,
+        ] {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        out.write_all(&self.memory)
+    }
+
+    pub fn read(input: &mut impl Read) -> anyhow::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        input
+            .read_exact(&mut magic)
+            .context("not a snapshot file")?;
+        if &magic != MAGIC {
+            bail!("not a snapshot file");
+        }
+        let mut version = [0; 2];
+        input.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version != VERSION {
+            bail!("unsupported snapshot version {}", version);
+        }
+
+        let mut read_u64 = || -> anyhow::Result<u64> {
+            let mut bytes = [0; 8];
+            input
+                .read_exact(&mut bytes)
+                .context("the snapshot is truncated")?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let program = read_u64()?;
+        let instruction_pointer = read_u64()? as usize;
+        let pointer = read_u64()? as usize;
+        let input_position = read_u64()?;
+        let output_position = read_u64()?;
+        let len = read_u64()? as usize;
+
+        let mut memory = Vec::new();
+        input.take(len as u64).read_to_end(&mut memory)?;
+        if memory.len() != len {
+            bail!("the snapshot is truncated");
+        }
+        Ok(Self {
+            program,
+            instruction_pointer,
+            pointer,
+            input_position,
+            output_position,
+            memory,
+        })
+    }
+}
@@ -0,0 +1,143 @@
+use super::{snapshot::fingerprint, *};
+
+#[test]
+fn fingerprint_is_stable() {
+    let instructions = [
+        VMInstruction::GetChar,
+        VMInstruction::JumpIfZero(4),
+        VMInstruction::AddValueMultipliedBy(3, -2),
+        VMInstruction::MoveAdd(1, 5),
+        VMInstruction::JumpIfNotZero(1),
+    ];
+    // 保存したスナップショットが読めなくなるので、変えるときは`snapshot::VERSION`も上げる
+    assert_eq!(fingerprint(&instructions), 0x748eea31e9bd63e2);
+}
+
+#[test]
+fn fingerprint_depends_on_operands() {
+    assert_ne!(
+        fingerprint(&[VMInstruction::Add(1)]),
+        fingerprint(&[VMInstruction::Add(2)])
+    );
+    assert_ne!(
+        fingerprint(&[VMInstruction::AddValueAt(1)]),
+        fingerprint(&[VMInstruction::SubtractValueAt(1)])
+    );
+}
+
+/// 入力を読まずに、テープの上限だけを指定して実行する
+fn run_with_max_tape(instructions: &[VMInstruction], max_tape: usize) -> Result<(), LimitExceeded> {
+    let limits = Limits {
+        max_tape: Some(max_tape),
+        ..Default::default()
+    };
+    run_limited(instructions, Input::bytes(Vec::new()), &limits)
+}
+
+#[test]
+fn max_tape_smaller_than_the_default_tape() {
+    assert!(run_with_max_tape(
+        &[VMInstruction::PointerAdd(9), VMInstruction::Increment],
+        10
+    )
+    .is_ok());
+
+    let error = run_with_max_tape(
+        &[VMInstruction::PointerAdd(10), VMInstruction::Increment],
+        10,
+    )
+    .unwrap_err();
+    assert_eq!(error.limit, Limit::Tape(Some(10)));
+    assert_eq!(error.instruction_pointer, Some(0));
+    assert_eq!(error.memory.len(), 10);
+}
+
+#[test]
+fn max_tape_larger_than_the_default_tape() {
+    let instructions = [VMInstruction::PointerAdd(40000), VMInstruction::Increment];
+    assert!(run_with_max_tape(&instructions, 40001).is_ok());
+    let error = run_with_max_tape(&instructions, 40000).unwrap_err();
+    assert_eq!(error.limit, Limit::Tape(Some(40000)));
+}
+
+/// 巻き戻しで比べるVMの状態。メモリは先頭だけを比べる
+fn state(vm: &VM) -> (Vec<u8>, usize, usize, u64, u64) {
+    (
+        vm.memory[..8].to_vec(),
+        vm.pointer,
+        vm.instruction_pointer,
+        vm.input_position,
+        vm.output_position,
+    )
+}
+
+fn vm_with_history<'a>(instructions: &'a [VMInstruction], input: &[u8]) -> VM<'a> {
+    let mut vm = VM::with_history(instructions);
+    vm.input = Input::bytes(input.to_vec());
+    vm.output = Some(Vec::new());
+    vm
+}
+
+#[test]
+fn step_back_across_io() {
+    // 入力を1文字ずつ出力してから、逆順に出力する
+    let instructions = crate::compiler::vm::compile(&crate::parser::parse(">,[.>,]<[.<]").unwrap());
+    let mut vm = vm_with_history(&instructions, b"abc");
+    let mut states = Vec::new();
+    while !vm.is_finished() {
+        states.push(state(&vm));
+        vm.step();
+    }
+    let last = state(&vm);
+
+    for expected in states.iter().rev() {
+        assert!(vm.step_back());
+        assert_eq!(state(&vm), *expected);
+    }
+    assert!(!vm.step_back());
+    assert_eq!(vm.time(), 0);
+
+    // 再実行では記録した入力を読み、出力済みの文字は出力しない
+    while !vm.is_finished() {
+        vm.step();
+    }
+    assert_eq!(state(&vm), last);
+    assert_eq!(vm.output.unwrap(), b"abccba");
+}
+
+#[test]
+fn seek_before_the_undo_log() {
+    // 最初に読んだ数だけ、入力を読んで出力し、3重ループを回す
+    let instructions = crate::compiler::vm::compile(
+        &crate::parser::parse(",[>,.>++++++++++[>++++++++++[>++++++++++[-]<-]<-]<<-]").unwrap(),
+    );
+    let input = [200].into_iter().chain(1..=200).collect::<Vec<u8>>();
+    let mut vm = vm_with_history(&instructions, &input);
+    let mut states = Vec::new();
+    while !vm.is_finished() {
+        if vm.time().is_multiple_of(997) {
+            states.push((vm.time(), state(&vm)));
+        }
+        vm.step();
+    }
+    let (end, last) = (vm.time(), state(&vm));
+    // 取り消し記録(10万命令)より十分長く実行する
+    assert!(end > 300_000);
+
+    for (time, expected) in states.iter().rev() {
+        vm.seek(*time);
+        assert_eq!(vm.time(), *time);
+        assert_eq!(state(&vm), *expected);
+    }
+    // スナップショットから戻った後も、1命令ずつ巻き戻せる
+    let (time, _) = states[states.len() / 2];
+    vm.seek(time + 3);
+    for _ in 0..3 {
+        assert!(vm.step_back());
+    }
+    assert_eq!(state(&vm), states[states.len() / 2].1);
+
+    vm.seek(end);
+    assert_eq!(state(&vm), last);
+    assert_eq!(vm.output.unwrap(), (1..=200).collect::<Vec<u8>>());
+}
@@ -0,0 +1,59 @@
+/// テープの長さの既定値
+pub const DEFAULT_TAPE_SIZE: usize = 30000;
+
+/// テープの設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TapeOptions {
+    /// セルの数
+    pub size: usize,
+    pub cell_width: CellWidth,
+    pub eof: Eof,
+}
+
+impl Default for TapeOptions {
+    fn default() -> Self {
+        Self {
+            size: DEFAULT_TAPE_SIZE,
+            cell_width: CellWidth::default(),
+            eof: Eof::default(),
+        }
+    }
+}
+
+/// セルのビット幅
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum CellWidth {
+    #[default]
+    #[clap(name = "8")]
+    U8,
+    #[clap(name = "16")]
+    U16,
+    #[clap(name = "32")]
+    U32,
+}
+
+impl CellWidth {
+    pub fn bits(self) -> u32 {
+        match self {
+            CellWidth::U8 => 8,
+            CellWidth::U16 => 16,
+            CellWidth::U32 => 32,
+        }
+    }
+}
+
+/// 入力の終わりに達したときに`,`がセルに書き込む値
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+#[clap(rename_all = "snake_case")]
+pub enum Eof {
+    /// 0を書き込む
+    #[default]
+    #[value(help = "Store 0")]
+    Zero,
+    /// -1 (セルの最大値)を書き込む
+    #[value(help = "Store -1 (the maximum cell value)")]
+    MinusOne,
+    /// セルの値を変更しない
+    #[value(help = "Leave the cell unchanged")]
+    Unchanged,
+}
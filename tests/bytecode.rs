@@ -0,0 +1,76 @@
+//! `bf build --emit bytecode`で書き出したファイルを`bf run`で実行できることと、
+//! ジャンプの壊れたファイルを実行せずに拒否することを確かめる
+
+use std::{path::Path, process::Command, time::Duration};
+
+use bf::{compiler::bytecode, process, runtime::vm::VMInstruction, tape::TapeOptions};
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[test]
+fn run_built_bytecode() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let programs = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs");
+    let file = Path::new(env!("CARGO_TARGET_TMPDIR")).join("hello.bfc");
+    let status = Command::new(bf)
+        .args([
+            "build", "-O", "all", "--fuse", "all", "--emit", "bytecode", "-o",
+        ])
+        .arg(&file)
+        .arg(programs.join("hello.bf"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let expected = std::fs::read(programs.join("hello.out")).unwrap();
+    for backend in ["vm", "closure"] {
+        let output = process::run(
+            Command::new(bf)
+                .args(["run", "--backend", backend])
+                .arg(&file),
+            b"",
+            TIMEOUT,
+        )
+        .unwrap();
+        assert!(
+            output.status.is_some_and(|status| status.success()),
+            "{}",
+            backend
+        );
+        assert_eq!(output.stdout, expected, "{}", backend);
+    }
+}
+
+#[test]
+fn reject_malformed_jumps() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let file = Path::new(env!("CARGO_TARGET_TMPDIR")).join("backward_jump.bfc");
+    // 後ろに戻るJumpIfZeroは、クロージャのVMでは終わらないループになる
+    let instructions = [VMInstruction::Increment, VMInstruction::JumpIfZero(0)];
+    std::fs::write(
+        &file,
+        bytecode::encode(&instructions, TapeOptions::default()),
+    )
+    .unwrap();
+    for backend in ["vm", "closure"] {
+        let output = process::run(
+            Command::new(bf)
+                .args(["run", "--backend", backend])
+                .arg(&file),
+            b"",
+            TIMEOUT,
+        )
+        .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(
+            output.status.and_then(|status| status.code()),
+            Some(1),
+            "{}",
+            backend
+        );
+        assert!(
+            stderr.contains("jump target 0 of instruction 1"),
+            "{}",
+            stderr
+        );
+    }
+}
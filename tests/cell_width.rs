@@ -0,0 +1,68 @@
+//! `bf build`が`--cell-width`を書き出す形式に合わせて扱うことを確かめる
+
+use std::{path::Path, process::Command};
+
+/// 8ビットで回り込ませると結果が変わるプログラム。正しく実行すると`AA`を出力する
+fn program() -> String {
+    let mut source = String::new();
+    // 256は16ビットのセルでは0にならないので、ループの中で`A`を出力する
+    source += &"+".repeat(256);
+    source += &format!("[{}>{}.[-]<]", "-".repeat(256), "+".repeat(65));
+    // 2×257=514を引くと0になるので、ループの中は実行されない
+    source += &format!("++[>{}<-]", "+".repeat(257));
+    source += &format!(">{}[{}.[-]]", "-".repeat(514), "+".repeat(66));
+    source += &format!("{}.", "+".repeat(65));
+    source
+}
+
+#[test]
+fn optimize_wide_cells() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let source = program();
+    let mut outputs = Vec::new();
+    for (name, optimize) in [
+        ("cell_width_16", &[][..]),
+        ("cell_width_16_o", &["-O", "all"][..]),
+    ] {
+        let c = tmp.join(format!("{}.c", name));
+        let exe = tmp.join(name);
+        let status = Command::new(bf)
+            .args(["build", "--emit", "c", "--cell-width", "16"])
+            .args(optimize)
+            .arg("-o")
+            .arg(&c)
+            .args(["-e", &source])
+            .status()
+            .unwrap();
+        assert!(status.success(), "failed to build {}", name);
+        let status = Command::new("cc")
+            .arg("-o")
+            .arg(&exe)
+            .arg(&c)
+            .status()
+            .unwrap();
+        assert!(status.success(), "failed to compile {}", name);
+        let output = Command::new(&exe).output().unwrap();
+        assert!(output.status.success(), "{}: {}", name, output.status);
+        outputs.push(String::from_utf8(output.stdout).unwrap());
+    }
+    assert_eq!(outputs[0], "AA");
+    assert_eq!(outputs[1], outputs[0]);
+}
+
+#[test]
+fn reject_wide_cells_for_bytecode() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cell_width_16.bc");
+    let _ = std::fs::remove_file(&output);
+    // どのバックエンドも読めないファイルを書き出さずに、引数の誤りとして止める
+    let status = Command::new(bf)
+        .args(["build", "--emit", "bytecode", "--cell-width", "16", "-o"])
+        .arg(&output)
+        .args(["-e", "+."])
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(2));
+    assert!(!output.exists());
+}
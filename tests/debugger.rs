@@ -0,0 +1,40 @@
+//! テープの外に出るプログラムを、デバッガがパニックせずに止めることを確かめる
+
+use std::{path::Path, process::Command, time::Duration};
+
+use bf::process;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+fn debug(source: &str, commands: &str) -> String {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let output = process::run(
+        Command::new(bf).args(["debug", "-e", source]),
+        commands.as_bytes(),
+        TIMEOUT,
+    )
+    .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        output.status.is_some_and(|status| status.success()),
+        "{:?}\n{}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    stdout
+}
+
+#[test]
+fn stop_before_moving_left_of_the_tape() {
+    let stdout = debug("<+", "c\nt\nq\n");
+    assert!(stdout.contains("access cell -1"), "{}", stdout);
+    // 止まった命令から動いていない
+    assert!(stdout.contains("step 0, ip 0"), "{}", stdout);
+}
+
+#[test]
+fn stop_before_moving_right_of_the_tape() {
+    let stdout = debug(">+", "ptr 29999\ns\nt 2\nl\nq\n");
+    assert!(stdout.contains("access cell 30000"), "{}", stdout);
+    assert!(stdout.contains("[29999]"), "{}", stdout);
+}
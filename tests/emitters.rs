@@ -0,0 +1,122 @@
+//! `programs/`のプログラムを`bf build`のC、WebAssembly、バイトコードの出力にして実行し、出力を比べる
+//!
+//! 実行ファイルは`executable.rs`、オブジェクトファイルは`object.rs`、Rustは`rust.rs`で確かめる
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use bf::golden;
+
+/// 時間がかかるので除くプログラム
+const SLOW: &[&str] = &["mandelbrot"];
+
+/// `env.putchar`と`env.getchar`を定義して`main`を呼び出す。入力の終わりでは-1を返す
+const WASM_HOST: &str = r#"
+const fs = require("fs");
+const input = fs.readFileSync(0);
+let position = 0;
+const output = [];
+const env = {
+    putchar: (c) => output.push(c & 0xff),
+    getchar: () => (position < input.length ? input[position++] : -1),
+};
+WebAssembly.instantiate(fs.readFileSync(process.argv[2]), { env }).then(({ instance }) => {
+    instance.exports.main();
+    process.stdout.write(Buffer.from(output));
+});
+"#;
+
+/// コマンドが見つからなければテストを飛ばす
+fn found(program: &str) -> bool {
+    let found = Command::new(program).arg("--version").output().is_ok();
+    if !found {
+        eprintln!("skipping: {} is not found", program);
+    }
+    found
+}
+
+/// 各プログラムを`-O all --emit emit`と`options`で`NAME.extension`に書き出し、
+/// `command`が返すコマンドで実行して比べる
+fn run_programs(emit: &str, extension: &str, options: &[&str], command: impl Fn(&Path) -> Command) {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs");
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let mut failures = Vec::new();
+    for case in golden::load(&dir).unwrap() {
+        if SLOW.contains(&case.name.as_str()) {
+            continue;
+        }
+        let output = tmp.join(format!("{}_emitted.{}", case.name, extension));
+        let status = Command::new(bf)
+            .args(["build", "-O", "all", "--emit", emit])
+            .args(options)
+            .arg("-o")
+            .arg(&output)
+            .arg(&case.source)
+            .status()
+            .unwrap();
+        assert!(status.success(), "failed to build {}", case.name);
+        let stdin = match &case.input {
+            Some(input) => Stdio::from(File::open(input).unwrap()),
+            None => Stdio::null(),
+        };
+        let result = command(&output).stdin(stdin).output().unwrap();
+        if !result.status.success() {
+            failures.push(format!(
+                "{}: {}: {}",
+                case.name,
+                result.status,
+                String::from_utf8_lossy(&result.stderr).trim()
+            ));
+        } else if let Some(message) = golden::difference(&case.expected, &result.stdout) {
+            failures.push(format!("{}: {}", case.name, message));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn c() {
+    if !found("cc") {
+        return;
+    }
+    run_programs("c", "c", &[], |source| {
+        let exe: PathBuf = source.with_extension("");
+        let status = Command::new("cc")
+            .arg("-O1")
+            .arg("-o")
+            .arg(&exe)
+            .arg(source)
+            .status()
+            .unwrap();
+        assert!(status.success(), "failed to compile {}", source.display());
+        Command::new(exe)
+    });
+}
+
+#[test]
+fn wasm() {
+    if !found("node") {
+        return;
+    }
+    let host = Path::new(env!("CARGO_TARGET_TMPDIR")).join("wasm_host.js");
+    std::fs::write(&host, WASM_HOST).unwrap();
+    run_programs("wasm", "wasm", &[], |module| {
+        let mut command = Command::new("node");
+        command.arg(&host).arg(module);
+        command
+    });
+}
+
+#[test]
+fn fused_bytecode() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    run_programs("bytecode", "bfc", &["--fuse", "all"], |bytecode| {
+        let mut command = Command::new(bf);
+        command.arg("run").arg(bytecode);
+        command
+    });
+}
@@ -0,0 +1,44 @@
+//! `programs/`のプログラムを`bf build --emit exe`で実行ファイルにして、出力を比べる
+
+use std::{
+    fs::File,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use bf::golden;
+
+/// 時間がかかるので除くプログラム
+const SLOW: &[&str] = &["mandelbrot"];
+
+#[test]
+fn programs() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs");
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let mut failures = Vec::new();
+    for case in golden::load(&dir).unwrap() {
+        if SLOW.contains(&case.name.as_str()) {
+            continue;
+        }
+        let exe = tmp.join(&case.name);
+        let status = Command::new(bf)
+            .args(["build", "-O", "all", "--emit", "exe", "-o"])
+            .arg(&exe)
+            .arg(&case.source)
+            .status()
+            .unwrap();
+        assert!(status.success(), "failed to build {}", case.name);
+        let stdin = match &case.input {
+            Some(input) => Stdio::from(File::open(input).unwrap()),
+            None => Stdio::null(),
+        };
+        let output = Command::new(&exe).stdin(stdin).output().unwrap();
+        if !output.status.success() {
+            failures.push(format!("{}: {}", case.name, output.status));
+        } else if let Some(message) = golden::difference(&case.expected, &output.stdout) {
+            failures.push(format!("{}: {}", case.name, message));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,22 @@
+//! ランダムなプログラムを最適化なしのVM、最適化したVM、ネイティブコードで実行して結果を比べる
+
+use std::{path::Path, time::Duration};
+
+use bf::{
+    fuzz::{self, Case, Verdict},
+    generator::random::RandomOptions,
+};
+
+const RUNS: u64 = 200;
+
+#[test]
+fn random_programs() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let options = RandomOptions::default();
+    for seed in 0..RUNS {
+        let case = Case::random(seed, &options);
+        let verdict = fuzz::check(bf, &case, Duration::from_secs(60)).unwrap();
+        // 異なれば`bf fuzz --seed <seed> --runs 1`で縮めたプログラムを表示できる
+        assert_eq!(verdict, Verdict::Same, "seed {}: {:?}", seed, case);
+    }
+}
@@ -0,0 +1,54 @@
+//! `programs/`のプログラムを、すべてのバックエンドと最適化の組み合わせで実行して出力を比べる
+
+use std::{path::Path, time::Duration};
+
+use bf::{
+    golden::{self, Outcome, Preset},
+    runtime::Backend,
+};
+use clap::ValueEnum;
+
+/// 時間がかかるので`#[ignore]`のテストで実行するプログラム
+const SLOW: &[&str] = &["mandelbrot"];
+
+const TIMEOUT: Duration = Duration::from_secs(300);
+
+fn run_programs(filter: impl Fn(&str) -> bool) {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs");
+    let cases = golden::load(&dir).unwrap();
+    let mut failures = Vec::new();
+    for case in cases.iter().filter(|case| filter(&case.name)) {
+        for &backend in Backend::value_variants() {
+            for &preset in Preset::value_variants()
+                .iter()
+                .filter(|preset| preset.supports(backend))
+            {
+                if let Outcome::Failed(message) =
+                    golden::run(bf, case, backend, preset, TIMEOUT).unwrap()
+                {
+                    failures.push(format!(
+                        "{} [{:?}, {}]: {}",
+                        case.name,
+                        backend,
+                        preset.name(),
+                        message
+                    ));
+                }
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn programs() {
+    run_programs(|name| !SLOW.contains(&name));
+}
+
+/// `cargo test --release -- --include-ignored`で実行する
+#[test]
+#[ignore]
+fn slow_programs() {
+    run_programs(|name| SLOW.contains(&name));
+}
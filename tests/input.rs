@@ -0,0 +1,99 @@
+//! `--input`で与えた入力を、すべてのバックエンドとデバッガが標準入力の代わりに読むことを確かめる
+
+use std::{path::Path, process::Command, time::Duration};
+
+use bf::{process, runtime::Backend};
+use clap::ValueEnum;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[test]
+fn read_input_given_as_an_argument() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let mut failures = Vec::new();
+    for backend in Backend::value_variants() {
+        let name = backend.to_possible_value().unwrap().get_name().to_string();
+        // ソースコードは標準入力から読む
+        let output = process::run(
+            Command::new(bf).args(["run", "--backend", &name, "--input", "abc"]),
+            b",[.,]",
+            TIMEOUT,
+        )
+        .unwrap();
+        if !output.status.is_some_and(|status| status.success()) || output.stdout != b"abc" {
+            failures.push(format!(
+                "{}: {:?} {:?}",
+                name,
+                output.status,
+                String::from_utf8_lossy(&output.stdout)
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn debugger_reads_commands_and_input_separately() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let commands = b"s\ns\nt 2\nq\n";
+    for (args, value) in [(&[][..], 0), (&["--input", "A"][..], 65)] {
+        let output = process::run(
+            Command::new(bf).arg("debug").args(args).args(["-e", ",.+"]),
+            commands,
+            TIMEOUT,
+        )
+        .unwrap();
+        assert!(output.status.is_some_and(|status| status.success()));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        // `,`がコマンドを読んでしまうと、セルの値がコマンドの文字になる
+        assert!(
+            stdout.contains(&format!("value[{:>5}]", value)),
+            "{}",
+            stdout
+        );
+    }
+}
+
+#[test]
+fn debugger_skips_input_read_before_a_loaded_snapshot() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let snapshot = Path::new(env!("CARGO_TARGET_TMPDIR")).join("debugger_input.snapshot");
+    let snapshot = snapshot.to_str().unwrap();
+    // `A`を読んで出力した後で保存し、最初からやり直してから読み込む
+    let commands = format!(
+        "s 2\nsave {}\nrestart\nload {}\ns 2\nq\n",
+        snapshot, snapshot
+    );
+    let output = process::run(
+        Command::new(bf).args(["debug", "--input", "AB", "-e", ",.,."]),
+        commands.as_bytes(),
+        TIMEOUT,
+    )
+    .unwrap();
+    assert!(output.status.is_some_and(|status| status.success()));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let printed = stdout.matches(['A', 'B']).collect::<String>();
+    assert_eq!(printed, "AB", "{}", stdout);
+}
+
+#[test]
+fn debugger_reads_input_after_a_loaded_snapshot_again() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let snapshot = Path::new(env!("CARGO_TARGET_TMPDIR")).join("debugger_rewound.snapshot");
+    let snapshot = snapshot.to_str().unwrap();
+    // `B`まで読んでから巻き戻し、`A`を読んだ直後の状態を読み込む。
+    // 巻き戻した記録の`A`ではなく、入力の続きの`B`を読む
+    let commands = format!(
+        "s 2\nsave {}\ns 2\nrs 4\nload {}\ns 1\nt 0\nq\n",
+        snapshot, snapshot
+    );
+    let output = process::run(
+        Command::new(bf).args(["debug", "--input", "AB", "-e", ",.,."]),
+        commands.as_bytes(),
+        TIMEOUT,
+    )
+    .unwrap();
+    assert!(output.status.is_some_and(|status| status.success()));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("value[   66]"), "{}", stdout);
+}
@@ -0,0 +1,100 @@
+//! テープの外に出るプログラムを、各バックエンドが止めることを確かめる
+
+use std::{path::Path, process::Command, time::Duration};
+
+use bf::process;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[test]
+fn stop_native_code_at_the_edge_of_the_tape() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    for (source, message) in [
+        ("<+", "pointer moved to the left of cell 0"),
+        ("+[>+]", "pointer moved out of the tape of 30000 cells"),
+        ("+[>+<-]>[<<+>>-]", "pointer moved to the left of cell 0"),
+    ] {
+        for optimize in [&[][..], &["-O", "all"][..]] {
+            let output = process::run(
+                Command::new(bf)
+                    .args([
+                        "run",
+                        "--backend",
+                        "native",
+                        "--max-steps",
+                        "1000000",
+                        "-e",
+                        source,
+                    ])
+                    .args(optimize),
+                b"",
+                TIMEOUT,
+            )
+            .unwrap();
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert_eq!(output.status.and_then(|status| status.code()), Some(1));
+            assert!(stderr.contains(message), "{}: {}", source, stderr);
+        }
+    }
+}
+
+#[test]
+fn stop_vms_at_the_edge_of_the_tape() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    for backend in ["vm", "closure"] {
+        for (source, message) in [
+            ("<+", "pointer moved to the left of cell 0"),
+            // クロージャのVMも、VMと同じ30000セルのテープの右端で止まる
+            ("+[>+]", "pointer moved out of the tape of 30000 cells"),
+            ("+[>+]<[<]", "pointer moved out of the tape of 30000 cells"),
+        ] {
+            let output = process::run(
+                Command::new(bf).args(["run", "--backend", backend, "-e", source]),
+                b"",
+                TIMEOUT,
+            )
+            .unwrap();
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert_eq!(output.status.and_then(|status| status.code()), Some(1));
+            assert!(
+                stderr.contains(message),
+                "{} {}: {}",
+                backend,
+                source,
+                stderr
+            );
+        }
+    }
+}
+
+#[test]
+fn use_the_tape_size_of_bytecode() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let file = Path::new(env!("CARGO_TARGET_TMPDIR")).join("tape_size.bfc");
+    // 10セルのテープの右端のセルを使ってから、その先に進む
+    let status = Command::new(bf)
+        .args(["build", "--emit", "bytecode", "--tape-size", "10", "-o"])
+        .arg(&file)
+        .args(["-e", ">>>>>>>>>+++[-<+>]<.>>"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    for backend in ["vm", "closure"] {
+        let output = process::run(
+            Command::new(bf)
+                .args(["run", "--backend", backend])
+                .arg(&file),
+            b"",
+            TIMEOUT,
+        )
+        .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.stdout, [3], "{}", backend);
+        assert!(
+            stderr.contains("pointer moved out of the tape of 10 cells"),
+            "{}: {}",
+            backend,
+            stderr
+        );
+    }
+}
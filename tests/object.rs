@@ -0,0 +1,133 @@
+//! `bf build --emit obj`の出力をCのプログラムとリンクして、出力を比べる
+
+use std::{
+    fs::File,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use bf::golden;
+
+/// 入出力関数を定義して`bf_main`を呼び出すプログラム。
+/// `ctx`が渡されることを確かめるため、出力した文字の数を標準エラー出力に書く
+const HARNESS: &str = r#"
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+void bf_main(uint8_t *tape, void *ctx);
+
+void bf_putchar(void *ctx, int c) {
+    ++*(int *)ctx;
+    putchar(c);
+}
+
+int bf_getchar(void *ctx) {
+    (void)ctx;
+    int c = getchar();
+    return c == EOF ? 0 : c;
+}
+
+int main(void) {
+    uint8_t *tape = calloc(30000, 1);
+    int count = 0;
+    bf_main(tape, &count);
+    fflush(stdout);
+    fprintf(stderr, "%d", count);
+    free(tape);
+    return 0;
+}
+"#;
+
+const PROGRAMS: &[&str] = &["hello", "rot13", "eof"];
+
+/// `cc`がなければテストを飛ばす
+fn has_cc() -> bool {
+    let found = Command::new("cc").arg("--version").output().is_ok();
+    if !found {
+        eprintln!("skipping: cc is not found");
+    }
+    found
+}
+
+#[test]
+fn link_with_c() {
+    if !has_cc() {
+        return;
+    }
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs");
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let harness = tmp.join("object_harness.c");
+    std::fs::write(&harness, HARNESS).unwrap();
+
+    let cases = golden::load(&dir).unwrap();
+    for case in cases
+        .iter()
+        .filter(|case| PROGRAMS.contains(&case.name.as_str()))
+    {
+        let object = tmp.join(format!("{}.o", case.name));
+        let exe = tmp.join(format!("{}_linked", case.name));
+        let status = Command::new(bf)
+            .args(["build", "-O", "all", "--emit", "obj", "-o"])
+            .arg(&object)
+            .arg(&case.source)
+            .status()
+            .unwrap();
+        assert!(status.success(), "failed to build {}", case.name);
+        let status = Command::new("cc")
+            .arg("-o")
+            .arg(&exe)
+            .arg(&harness)
+            .arg(&object)
+            .status()
+            .unwrap();
+        assert!(status.success(), "failed to link {}", case.name);
+
+        let stdin = match &case.input {
+            Some(input) => Stdio::from(File::open(input).unwrap()),
+            None => Stdio::null(),
+        };
+        let output = Command::new(&exe).stdin(stdin).output().unwrap();
+        assert!(output.status.success(), "{}: {}", case.name, output.status);
+        if let Some(message) = golden::difference(&case.expected, &output.stdout) {
+            panic!("{}: {}", case.name, message);
+        }
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            case.expected.len().to_string(),
+            "{}",
+            case.name
+        );
+    }
+}
+
+#[test]
+fn export_the_given_symbol() {
+    if !has_cc() {
+        return;
+    }
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let object = tmp.join("renamed.o");
+    let exe = tmp.join("renamed_linked");
+    let harness = tmp.join("renamed_harness.c");
+    std::fs::write(&harness, HARNESS.replace("bf_main", "run_program")).unwrap();
+    let status = Command::new(bf)
+        .args(["build", "--emit", "obj", "--symbol", "run_program", "-o"])
+        .arg(&object)
+        .args(["-e", "++++++++[>++++++++<-]>+."])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = Command::new("cc")
+        .arg("-o")
+        .arg(&exe)
+        .arg(&harness)
+        .arg(&object)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = Command::new(&exe).output().unwrap();
+    assert_eq!(output.stdout, b"A");
+}
@@ -0,0 +1,56 @@
+//! `bf build --emit rust`の出力を`rustc`でビルドして、出力を比べる
+
+use std::{
+    fs::File,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use bf::golden;
+
+/// 時間がかかるので除くプログラム
+const SLOW: &[&str] = &["mandelbrot"];
+
+#[test]
+fn compile_with_rustc() {
+    if Command::new("rustc").arg("--version").output().is_err() {
+        eprintln!("skipping: rustc is not found");
+        return;
+    }
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs");
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let cases = golden::load(&dir).unwrap();
+    for case in cases
+        .iter()
+        .filter(|case| !SLOW.contains(&case.name.as_str()))
+    {
+        let source = tmp.join(format!("{}.rs", case.name));
+        let exe = tmp.join(format!("{}_rust", case.name));
+        let status = Command::new(bf)
+            .args(["build", "-O", "all", "--emit", "rust", "-o"])
+            .arg(&source)
+            .arg(&case.source)
+            .status()
+            .unwrap();
+        assert!(status.success(), "failed to build {}", case.name);
+        // 生成したコードに警告が出ないことも確かめる
+        let status = Command::new("rustc")
+            .args(["--edition", "2021", "-D", "warnings", "-o"])
+            .arg(&exe)
+            .arg(&source)
+            .status()
+            .unwrap();
+        assert!(status.success(), "failed to compile {}", case.name);
+
+        let stdin = match &case.input {
+            Some(input) => Stdio::from(File::open(input).unwrap()),
+            None => Stdio::null(),
+        };
+        let output = Command::new(&exe).stdin(stdin).output().unwrap();
+        assert!(output.status.success(), "{}: {}", case.name, output.status);
+        if let Some(message) = golden::difference(&case.expected, &output.stdout) {
+            panic!("{}: {}", case.name, message);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! `programs/`のプログラムを、すべてのループをネイティブコードにコンパイルする`--backend tiered`で実行する。
+//! コンパイルしたループの中の入出力も、VMと同じ結果になることを確かめる
+
+use std::{
+    io::Read,
+    path::Path,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use bf::{golden, process};
+
+/// 時間がかかるので除くプログラム
+const SLOW: &[&str] = &["mandelbrot"];
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[test]
+fn programs_with_every_loop_compiled() {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs");
+    let mut failures = Vec::new();
+    for case in golden::load(&dir).unwrap() {
+        if SLOW.contains(&case.name.as_str()) {
+            continue;
+        }
+        for optimize in [&[][..], &["-O", "all"]] {
+            let mut command = Command::new(bf);
+            command
+                .args(["run", "--backend", "tiered", "--jit-threshold", "1"])
+                .args(optimize);
+            if let Some(input) = &case.input {
+                command.arg("--input-file").arg(input);
+            }
+            let output = process::run(command.arg(&case.source), &[], TIMEOUT).unwrap();
+            let result = match output.status {
+                Some(status) if status.success() => {
+                    golden::difference(&case.expected, &output.stdout)
+                }
+                Some(status) => Some(status.to_string()),
+                None => Some("timed out".to_string()),
+            };
+            if let Some(message) = result {
+                failures.push(format!("{} {:?}: {}", case.name, optimize, message));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn stop_when_stdout_is_closed() {
+    // コンパイルしたループの中で出力し続ける
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .args(["run", "--backend", "tiered", "--jit-threshold", "1"])
+        .args(["-e", "+[.]"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let mut head = [0; 2];
+    stdout.read_exact(&mut head).unwrap();
+    drop(stdout);
+
+    let output = child.wait_with_output().unwrap();
+    // パニックでFFIの境界を越えると、終了コードではなくシグナルで終わる
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Broken pipe"), "{}", stderr);
+}
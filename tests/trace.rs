@@ -0,0 +1,58 @@
+//! `--trace-file`の出力が、VMとネイティブコードのトレース関数の呼び出しで同じになることを確かめる
+
+use std::{path::Path, process::Command, time::Duration};
+
+use bf::process;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// ループ、ifになる掛け算のループ、入出力を含むプログラム
+const PROGRAM: &str = "++[>+++<-]>[-<+>]<.,.[>+>+<<-]";
+
+fn trace(backend: &str, name: &str, options: &[&str]) -> Vec<u8> {
+    let bf = Path::new(env!("CARGO_BIN_EXE_bf"));
+    let file = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.{}", name, backend));
+    let output = process::run(
+        Command::new(bf)
+            .args(["run", "--backend", backend, "--input", "x", "--trace-file"])
+            .arg(&file)
+            .args(options)
+            .args(["-e", PROGRAM]),
+        b"",
+        TIMEOUT,
+    )
+    .unwrap();
+    assert!(
+        output.status.is_some_and(|status| status.success()),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    std::fs::read(file).unwrap()
+}
+
+#[test]
+fn native_trace_matches_vm() {
+    let mut failures = Vec::new();
+    for format in ["jsonl", "csv", "binary"] {
+        for (i, options) in [
+            &[][..],
+            &["-O", "all"],
+            &["--trace-filter", "loop_heads,io"],
+            &["-O", "all", "--trace-every", "3", "--trace-window", "2"],
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let name = format!("trace_{}_{}", format, i);
+            let mut options = options.to_vec();
+            options.extend(["--trace-format", format]);
+            let vm = trace("vm", &name, &options);
+            let native = trace("native", &name, &options);
+            assert!(!vm.is_empty());
+            if vm != native {
+                failures.push(format!("{} {:?}", format, options));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}